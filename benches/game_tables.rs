@@ -0,0 +1,258 @@
+//! Throughput of finding tables with many matches played at once, against the single
+//! `RwLock<HashMap>` the registry replaced. Every request finds its table, a few create one.
+//! Then how long it takes to open many matches everyone asks for at once.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    hint::black_box,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use ping_pong_api::{
+    database::{TableDbSyncHandle, TableUid},
+    models::{
+        application::GameTables,
+        game::{GameMode, GameState, TableState},
+        player::Claims,
+        settings::MatchSettings,
+    },
+};
+use sqlx::PgPool;
+use tokio::sync::Semaphore;
+
+const MATCHES: usize = 1000;
+const REQUESTS_PER_THREAD: usize = 10_000;
+/// one request in this many creates a match
+const CREATION_EVERY: usize = 100;
+const OPENED_MATCHES: usize = 100;
+/// Loading or creating a match in the database
+const CREATION_TIME: Duration = Duration::from_millis(1);
+/// Creations wait for one of them, as for the connections of a pool
+const CONNECTIONS: usize = 4;
+
+trait Registry: Sync {
+    fn get(&self, uid: &TableUid) -> Option<TableState>;
+    fn insert(&self, uid: TableUid, table_state: TableState);
+    fn remove(&self, uid: &TableUid);
+    fn get_or_create(
+        &self,
+        uid: &TableUid,
+        database: &Database,
+    ) -> impl Future<Output = TableState> + Send;
+}
+
+impl Registry for RwLock<HashMap<TableUid, TableState>> {
+    fn get(&self, uid: &TableUid) -> Option<TableState> {
+        self.read().unwrap().get(uid).cloned()
+    }
+
+    fn insert(&self, uid: TableUid, table_state: TableState) {
+        self.write().unwrap().insert(uid, table_state);
+    }
+
+    fn remove(&self, uid: &TableUid) {
+        self.write().unwrap().remove(uid);
+    }
+
+    /// Everyone who misses creates a table, the first one inserted wins
+    async fn get_or_create(&self, uid: &TableUid, database: &Database) -> TableState {
+        let found = self.read().unwrap().get(uid).cloned();
+        if let Some(table_state) = found {
+            return table_state;
+        }
+        let created = database.create().await;
+        self.write()
+            .unwrap()
+            .entry(uid.clone())
+            .or_insert(created)
+            .clone()
+    }
+}
+
+impl Registry for GameTables {
+    fn get(&self, uid: &TableUid) -> Option<TableState> {
+        GameTables::get(self, uid)
+    }
+
+    fn insert(&self, uid: TableUid, table_state: TableState) {
+        GameTables::insert(self, uid, table_state);
+    }
+
+    fn remove(&self, uid: &TableUid) {
+        GameTables::remove(self, uid);
+    }
+
+    async fn get_or_create(&self, uid: &TableUid, database: &Database) -> TableState {
+        let created = self
+            .get_or_try_insert_with(uid, || async {
+                Ok::<_, Infallible>(database.create().await)
+            })
+            .await;
+        created.unwrap()
+    }
+}
+
+fn table(pool: &PgPool) -> TableState {
+    TableState::new(
+        GameState::new(GameMode::Classic),
+        Claims::default(),
+        MatchSettings::default(),
+        TableDbSyncHandle::new(0, pool, None),
+    )
+}
+
+/// Stands in for the database when matches are created
+struct Database {
+    pool: PgPool,
+    connections: Semaphore,
+}
+
+impl Database {
+    async fn create(&self) -> TableState {
+        let _connection = self.connections.acquire().await.unwrap();
+        tokio::time::sleep(CREATION_TIME).await;
+        table(&self.pool)
+    }
+}
+
+/// Each thread asks for matches spread over all of them, and creates and removes its own
+fn play(
+    registry: &impl Registry,
+    uids: &[TableUid],
+    spare: &TableState,
+    threads: usize,
+) -> Duration {
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for thread in 0..threads {
+            scope.spawn(move || {
+                let created = TableUid::parse(format!("new{thread}")).unwrap();
+                for request in 0..REQUESTS_PER_THREAD {
+                    if request % CREATION_EVERY == 0 {
+                        registry.insert(created.clone(), spare.clone());
+                        registry.remove(&created);
+                    } else {
+                        let uid = &uids[(request * 7919 + thread * 104_729) % uids.len()];
+                        black_box(registry.get(uid));
+                    }
+                }
+            });
+        }
+    });
+    started.elapsed()
+}
+
+/// Every task asks for every match of an empty registry, as when they all just opened
+fn open<R: Registry + Send + 'static>(
+    runtime: &tokio::runtime::Runtime,
+    registry: R,
+    pool: &PgPool,
+    tasks: usize,
+) -> Duration {
+    let registry = Arc::new(registry);
+    let database = Arc::new(Database {
+        pool: pool.clone(),
+        connections: Semaphore::new(CONNECTIONS),
+    });
+    let started = Instant::now();
+    runtime.block_on(async {
+        let mut requests = tokio::task::JoinSet::new();
+        for task in 0..tasks {
+            let (registry, database) = (registry.clone(), database.clone());
+            requests.spawn(async move {
+                for request in 0..OPENED_MATCHES {
+                    let uid =
+                        TableUid::parse(format!("m{}", (request + task) % OPENED_MATCHES)).unwrap();
+                    black_box(registry.get_or_create(&uid, &database).await);
+                }
+            });
+        }
+        requests.join_all().await;
+    });
+    started.elapsed()
+}
+
+fn opening_matches(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _runtime = runtime.enter();
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+
+    let mut group = c.benchmark_group("opening_matches");
+    // an iteration takes a while, each match is created at least once
+    group.sample_size(10);
+    for tasks in [1, 16, 64] {
+        group.throughput(Throughput::Elements((tasks * OPENED_MATCHES) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("rwlock_hashmap", tasks),
+            &tasks,
+            |b, &tasks| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| open(&runtime, RwLock::<HashMap<_, _>>::default(), &pool, tasks))
+                        .sum()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("game_tables", tasks),
+            &tasks,
+            |b, &tasks| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| open(&runtime, GameTables::default(), &pool, tasks))
+                        .sum()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn parallel_matches(c: &mut Criterion) {
+    // sync handles spawn their writers, nothing is ever written
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _runtime = runtime.enter();
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    let uids: Vec<TableUid> = (0..MATCHES)
+        .map(|i| TableUid::parse(format!("m{i}")).unwrap())
+        .collect();
+    let spare = table(&pool);
+    let locked: RwLock<HashMap<TableUid, TableState>> =
+        RwLock::new(uids.iter().map(|uid| (uid.clone(), table(&pool))).collect());
+    let sharded: GameTables = uids.iter().map(|uid| (uid.clone(), table(&pool))).collect();
+
+    let mut group = c.benchmark_group("parallel_matches");
+    for threads in [1, 4, 16] {
+        group.throughput(Throughput::Elements((threads * REQUESTS_PER_THREAD) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("rwlock_hashmap", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| play(&locked, &uids, &spare, threads))
+                        .sum()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("game_tables", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| play(&sharded, &uids, &spare, threads))
+                        .sum()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parallel_matches, opening_matches);
+criterion_main!(benches);
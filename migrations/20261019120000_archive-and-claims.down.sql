@@ -0,0 +1,3 @@
+DROP TABLE match_claim;
+
+ALTER TABLE match DROP COLUMN archived_at;
@@ -0,0 +1,11 @@
+-- Archived matches are kept for admins, but not loaded or playable anymore
+ALTER TABLE match ADD COLUMN archived_at TIMESTAMPTZ;
+
+CREATE TABLE match_claim(
+    game_state_id BIGINT NOT NULL REFERENCES game_state (id),
+    side TEXT NOT NULL CHECK (side IN ('ping', 'pong')),
+    player VARCHAR(32) NOT NULL,
+    token TEXT NOT NULL,
+    claimed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (game_state_id, side)
+);
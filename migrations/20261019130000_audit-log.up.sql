@@ -0,0 +1,29 @@
+-- No foreign key to match on purpose - the trail has to outlive whatever it's about
+CREATE TABLE audit_log(
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    match_uid VARCHAR(6) NOT NULL,
+    details JSONB NOT NULL,
+    game_state_before JSONB,
+    game_state_after JSONB
+);
+
+CREATE INDEX audit_log_match_uid_idx ON audit_log (match_uid, occurred_at);
+CREATE INDEX audit_log_actor_idx ON audit_log (actor, occurred_at);
+CREATE INDEX audit_log_occurred_at_idx ON audit_log (occurred_at);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only, % is not allowed', TG_OP;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_changes
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_truncate
+    BEFORE TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
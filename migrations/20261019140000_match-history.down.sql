@@ -0,0 +1,3 @@
+DROP TABLE match_event;
+
+ALTER TABLE match DROP COLUMN owner;
@@ -0,0 +1,12 @@
+-- First player to claim a side of the match, can correct its score
+ALTER TABLE match ADD COLUMN owner VARCHAR(32);
+
+CREATE TABLE match_event(
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    game_state_id BIGINT NOT NULL REFERENCES game_state (id),
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    kind TEXT NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX match_event_game_state_id_idx ON match_event (game_state_id, id);
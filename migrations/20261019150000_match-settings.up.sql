@@ -0,0 +1,2 @@
+-- NULL means defaults from the server config
+ALTER TABLE match ADD COLUMN settings JSONB;
@@ -0,0 +1,4 @@
+DELETE FROM match_claim WHERE partner = 'second';
+ALTER TABLE match_claim DROP CONSTRAINT match_claim_pkey;
+ALTER TABLE match_claim ADD PRIMARY KEY (game_state_id, side);
+ALTER TABLE match_claim DROP COLUMN partner;
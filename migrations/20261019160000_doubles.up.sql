@@ -0,0 +1,5 @@
+-- Doubles matches have two players per side
+ALTER TABLE match_claim
+    ADD COLUMN partner TEXT NOT NULL DEFAULT 'first' CHECK (partner IN ('first', 'second'));
+ALTER TABLE match_claim DROP CONSTRAINT match_claim_pkey;
+ALTER TABLE match_claim ADD PRIMARY KEY (game_state_id, side, partner);
@@ -0,0 +1,2 @@
+DROP TABLE tournament_player;
+DROP TABLE tournament;
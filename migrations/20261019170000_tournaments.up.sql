@@ -0,0 +1,18 @@
+-- Tournament matches are regular matches, the bracket only keeps their uids
+CREATE TABLE tournament(
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL,
+    format TEXT NOT NULL CHECK (format IN ('single_elimination', 'double_elimination', 'round_robin')),
+    points_to_win INTEGER NOT NULL,
+    matches JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE tournament_player(
+    tournament_id BIGINT NOT NULL REFERENCES tournament(id) ON DELETE CASCADE,
+    seed INTEGER NOT NULL,
+    player TEXT NOT NULL,
+    token TEXT NOT NULL,
+    PRIMARY KEY (tournament_id, player),
+    UNIQUE (tournament_id, seed)
+);
@@ -0,0 +1,2 @@
+-- Challengers waiting for the loser's seat, first in line first
+ALTER TABLE match ADD COLUMN queue JSONB NOT NULL DEFAULT '[]';
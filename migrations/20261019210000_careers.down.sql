@@ -0,0 +1,2 @@
+DROP INDEX match_event_won_pong_player_idx;
+DROP INDEX match_event_won_ping_player_idx;
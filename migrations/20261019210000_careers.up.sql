@@ -0,0 +1,5 @@
+-- Careers are read from won events, these find the first player of each side
+CREATE INDEX match_event_won_ping_player_idx ON match_event ((data->'ping'->>'player'), id)
+    WHERE kind = 'won';
+CREATE INDEX match_event_won_pong_player_idx ON match_event ((data->'pong'->>'player'), id)
+    WHERE kind = 'won';
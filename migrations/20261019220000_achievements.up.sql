@@ -0,0 +1,9 @@
+-- First unlock of each achievement, ids are defined in code
+CREATE TABLE player_achievement(
+    player VARCHAR(32) NOT NULL,
+    achievement TEXT NOT NULL,
+    -- no foreign key, achievements outlive archived matches just fine
+    match_uid VARCHAR(6) NOT NULL,
+    unlocked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (player, achievement)
+);
@@ -0,0 +1,4 @@
+DROP TABLE webhook_cursor;
+DROP TABLE webhook_delivery;
+DROP TABLE webhook;
+ALTER TABLE record_cursor DROP COLUMN announced_after_event_id;
@@ -0,0 +1,42 @@
+-- Records set by events up to this one aren't announced, they were already before a rebuild
+ALTER TABLE record_cursor ADD COLUMN announced_after_event_id BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE webhook(
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    -- NULL for all matches. No foreign key, matches are created by the first request to them
+    match_uid VARCHAR(6),
+    -- match event kinds to deliver
+    events TEXT[] NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Delivery log, also the queue of deliveries still to be made
+CREATE TABLE webhook_delivery(
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    webhook_id BIGINT NOT NULL REFERENCES webhook (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    attempts INT NOT NULL DEFAULT 0,
+    -- NULL once delivered or given up
+    next_attempt_at TIMESTAMPTZ DEFAULT now(),
+    delivered_at TIMESTAMPTZ,
+    last_status_code INT,
+    last_error TEXT
+);
+
+CREATE INDEX webhook_delivery_due_idx ON webhook_delivery (next_attempt_at)
+    WHERE next_attempt_at IS NOT NULL;
+CREATE INDEX webhook_delivery_webhook_id_idx ON webhook_delivery (webhook_id, id);
+
+-- Match events are turned into deliveries by following match_event up to last_event_id
+CREATE TABLE webhook_cursor(
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    last_event_id BIGINT NOT NULL
+);
+
+-- history from before webhooks isn't delivered
+INSERT INTO webhook_cursor (last_event_id) SELECT COALESCE(max(id), 0) FROM match_event;
@@ -0,0 +1,3 @@
+DROP INDEX match_instance_url_idx;
+ALTER TABLE match DROP COLUMN lease_expires_at;
+ALTER TABLE match DROP COLUMN instance_url;
@@ -0,0 +1,7 @@
+-- Instance serving the match when running several of them, NULL when nobody does.
+-- Its base URL, other instances forward requests for the match there
+ALTER TABLE match ADD COLUMN instance_url TEXT;
+-- Renewed while the instance is up, anyone can take the match over once it has passed
+ALTER TABLE match ADD COLUMN lease_expires_at TIMESTAMPTZ;
+
+CREATE INDEX match_instance_url_idx ON match (instance_url) WHERE instance_url IS NOT NULL;
@@ -0,0 +1,2 @@
+DROP INDEX match_event_won_pong_partner_idx;
+DROP INDEX match_event_won_ping_partner_idx;
@@ -0,0 +1,5 @@
+-- Doubles partners have careers too, every player named in a won event has to be found fast
+CREATE INDEX match_event_won_ping_partner_idx ON match_event ((data->'ping'->>'partner'), id)
+    WHERE kind = 'won';
+CREATE INDEX match_event_won_pong_partner_idx ON match_event ((data->'pong'->>'partner'), id)
+    WHERE kind = 'won';
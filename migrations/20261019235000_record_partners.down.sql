@@ -0,0 +1 @@
+ALTER TABLE record DROP COLUMN pong_partner, DROP COLUMN ping_partner;
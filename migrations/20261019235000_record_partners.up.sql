@@ -0,0 +1,8 @@
+-- Doubles partners hold records along with the first players of their sides
+ALTER TABLE record ADD COLUMN ping_partner VARCHAR(32), ADD COLUMN pong_partner VARCHAR(32);
+
+-- Comebacks counted undone points and games with a set score counted as played,
+-- records are built again without them. Those seen before aren't announced again.
+DELETE FROM record;
+UPDATE record_cursor
+SET last_event_id = 0, announced_after_event_id = (SELECT COALESCE(max(id), 0) FROM match_event);
@@ -0,0 +1 @@
+DROP TABLE rated_player;
@@ -0,0 +1,7 @@
+-- Rated names belong to whoever played their first rated match under them, later rated
+-- matches need the same `X-Player-Token`
+CREATE TABLE rated_player(
+    player TEXT PRIMARY KEY,
+    token TEXT NOT NULL,
+    registered_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,3 @@
+ALTER TABLE rated_match DROP COLUMN loser_change, DROP COLUMN winner_change, DROP COLUMN winner;
+ALTER TABLE record DROP COLUMN event_id;
+DROP FUNCTION won_undone;
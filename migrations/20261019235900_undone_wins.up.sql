@@ -0,0 +1,23 @@
+-- A win is undone when the first change of the score after it is an undo
+CREATE FUNCTION won_undone(won_id BIGINT) RETURNS BOOLEAN AS $$
+    SELECT COALESCE((
+        SELECT after_won.kind = 'undo'
+        FROM match_event won
+        JOIN match_event after_won
+          ON after_won.game_state_id = won.game_state_id AND after_won.id > won.id
+        WHERE won.id = won_id AND after_won.kind IN ('point', 'undo', 'reset', 'scoreSet')
+        ORDER BY after_won.id
+        LIMIT 1
+    ), false)
+$$ LANGUAGE sql STABLE;
+
+-- Records remember the event that set them, the ones set by a win that's undone later are
+-- taken back. Undone wins counted until now, records are built again without them.
+DELETE FROM record;
+ALTER TABLE record ADD COLUMN event_id BIGINT NOT NULL;
+UPDATE record_cursor
+SET last_event_id = 0, announced_after_event_id = (SELECT COALESCE(max(id), 0) FROM match_event);
+
+-- Rating changes of a rated match, taken back if its win is undone
+ALTER TABLE rated_match
+    ADD COLUMN winner TEXT, ADD COLUMN winner_change INTEGER, ADD COLUMN loser_change INTEGER;
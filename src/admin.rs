@@ -0,0 +1,302 @@
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{Span, error};
+
+use crate::{
+    audit::{self, Audit},
+    auth::{Actor, Scope, require_admin},
+    cluster::{Located, forward, locate},
+    database::{TableUid, get_all_matches},
+    game_table::{MatchPath, archive_table},
+    models::{
+        application::AppState,
+        game::{GameState, Partner, Score, Side, TableState},
+        player::Claims,
+        settings::MatchSettings,
+    },
+    records, webhooks,
+};
+
+type AdminResult<T> = Result<T, (StatusCode, String)>;
+
+pub fn admin_routes(state: AppState) -> Router<AppState> {
+    let match_routes = Router::new()
+        .route("/", delete(archive))
+        .route("/reset", post(reset))
+        .route("/score", put(set_score))
+        .route("/server", put(set_server))
+        .route("/settings", put(set_settings))
+        .route("/end-rally", post(end_rally))
+        .route("/claims/{side}", delete(kick))
+        .route_layer(middleware::from_fn_with_state(state.clone(), find_match));
+
+    Router::new()
+        .route("/matches", get(list_matches))
+        .route("/audit", get(audit::list_entries))
+        .route("/records/rebuild", post(records::rebuild))
+        .route("/webhooks", get(webhooks::list).post(webhooks::create))
+        .route("/webhooks/{id}", delete(webhooks::delete))
+        .route("/webhooks/{id}/deliveries", get(webhooks::list_deliveries))
+        .nest("/matches/{id}", match_routes)
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+/// Unlike gameplay routes, admin ones never create matches
+async fn find_match(
+    State(state): State<AppState>,
+    Path(MatchPath { id: uid }): Path<MatchPath>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let uid = match TableUid::parse(&uid) {
+        Ok(uid) => uid,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    };
+    Span::current().record("match_id", uid.as_str());
+
+    match locate(&state, &uid).await {
+        Ok(Located::Local(table_state)) => {
+            request.extensions_mut().insert(table_state);
+            request.extensions_mut().insert(uid);
+            next.run(request).await
+        }
+        Ok(Located::Remote(owner)) => forward(&state, &owner, request).await,
+        Ok(Located::Archived | Located::Missing) => {
+            (StatusCode::NOT_FOUND, format!("Match {uid} not found")).into_response()
+        }
+        Err(e) => {
+            error!(match_id = %uid, error = %e, "Failed to find match");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminMatch {
+    id: TableUid,
+    archived_at: Option<Timestamp>,
+    game_state: GameState,
+    players: Claims,
+}
+
+#[derive(Serialize)]
+struct AdminMatchList {
+    matches: Vec<AdminMatch>,
+}
+
+async fn list_matches(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+) -> AdminResult<Json<AdminMatchList>> {
+    actor.require(Scope::Read)?;
+
+    let stored = get_all_matches(&state.db_pool).await.map_err(|e| {
+        error!(error = %e, "Failed to list matches");
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    let game_tables = state.game_tables;
+    let matches = stored
+        .into_iter()
+        .map(|stored| {
+            // saved state lags behind the live one a bit
+            let (game_state, players) = match game_tables.get(&stored.uid) {
+                Some(table_state) => (
+                    table_state.game_state_snapshot(),
+                    Claims::clone(&table_state.claims()),
+                ),
+                None => (stored.game_state, stored.claims),
+            };
+            AdminMatch {
+                id: stored.uid,
+                archived_at: stored.archived_at,
+                game_state,
+                players,
+            }
+        })
+        .collect();
+
+    Ok(Json(AdminMatchList { matches }))
+}
+
+async fn archive(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+) -> AdminResult<StatusCode> {
+    actor.require(Scope::Write)?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    if let Err(e) = archive_table(&state, &uid, &table_state).await {
+        error!(match_id = %uid, error = %e, "Failed to archive match");
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+    }
+
+    audit.match_gone().record("archive", json!({})).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn reset(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+) -> AdminResult<Json<TableState>> {
+    actor.require(Scope::Write)?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    table_state.reset().await?;
+
+    audit.record("reset", json!({})).await?;
+    Ok(Json(table_state))
+}
+
+async fn set_score(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Json(score): Json<Score>,
+) -> AdminResult<Json<TableState>> {
+    actor.require(Scope::Write)?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    let details = json!({ "score": score });
+    table_state.set_score(score, actor.name.clone()).await?;
+
+    audit.record("set_score", details).await?;
+    Ok(Json(table_state))
+}
+
+#[derive(Deserialize)]
+struct SetServer {
+    server: Side,
+}
+
+async fn set_server(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Json(SetServer { server }): Json<SetServer>,
+) -> AdminResult<Json<TableState>> {
+    actor.require(Scope::Write)?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    if table_state.is_wall(server) {
+        return Err((
+            StatusCode::CONFLICT,
+            "Wall matches are always served from ping".to_string(),
+        ));
+    }
+    if !table_state.set_server(server).await? {
+        return Err((
+            StatusCode::CONFLICT,
+            "Can't change server during a rally".to_string(),
+        ));
+    }
+
+    audit
+        .record("set_server", json!({ "server": server }))
+        .await?;
+    Ok(Json(table_state))
+}
+
+/// Replaces all settings of the match, takes effect from the next hit
+async fn set_settings(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Json(settings): Json<MatchSettings>,
+) -> AdminResult<Json<TableState>> {
+    actor.require(Scope::Write)?;
+    settings
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    let details = json!({ "settings": settings });
+    table_state.set_settings(settings).await?;
+
+    audit.record("set_settings", details).await?;
+    Ok(Json(table_state))
+}
+
+#[derive(Deserialize)]
+struct EndRally {
+    /// `None` ends the rally without a point
+    winner: Option<Side>,
+}
+
+async fn end_rally(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Json(EndRally { winner }): Json<EndRally>,
+) -> AdminResult<Json<TableState>> {
+    actor.require(Scope::Write)?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    if !table_state.end_rally(winner).await? {
+        return Err((StatusCode::CONFLICT, "No rally in progress".to_string()));
+    }
+
+    audit
+        .record("end_rally", json!({ "winner": winner }))
+        .await?;
+    Ok(Json(table_state))
+}
+
+#[derive(Deserialize)]
+struct SidePath {
+    side: Side,
+}
+
+/// Second partners only sit on doubles sides
+#[derive(Deserialize)]
+struct KickQuery {
+    #[serde(default)]
+    partner: Partner,
+}
+
+async fn kick(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Path(SidePath { side }): Path<SidePath>,
+    Query(KickQuery { partner }): Query<KickQuery>,
+) -> AdminResult<Json<TableState>> {
+    actor.require(Scope::Write)?;
+    let (audit, table_state) = Audit::start(&state, &actor.name, &uid, &table_state);
+
+    let Some(player) = table_state.kick(side, partner).await? else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("{side} side is not claimed by a {partner} partner"),
+        ));
+    };
+
+    audit
+        .record(
+            "kick",
+            json!({ "side": side, "partner": partner, "player": player }),
+        )
+        .await?;
+    Ok(Json(table_state))
+}
@@ -0,0 +1,170 @@
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use jiff::Timestamp;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{
+    auth::{Actor, Scope},
+    database::{AuditEntry, AuditFilter, DbError, NewAuditEntry, TableUid, get_audit_entries},
+    models::{
+        application::AppState,
+        game::{AuditedStates, TableState},
+    },
+};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Manual change, recorded with the game states the table's actor saw right before and
+/// after it
+pub struct Audit {
+    pool: PgPool,
+    actor: String,
+    uid: Option<TableUid>,
+    states: Option<Arc<Mutex<AuditedStates>>>,
+    /// `false` once the match is gone, e.g. archived
+    kept: bool,
+}
+
+impl Audit {
+    /// Changes have to go through the returned handle to be audited
+    pub fn start(
+        state: &AppState,
+        actor: &str,
+        uid: &TableUid,
+        table_state: &TableState,
+    ) -> (Self, TableState) {
+        let (table_state, states) = table_state.audited();
+        let audit = Self {
+            pool: state.db_pool.clone(),
+            actor: actor.to_string(),
+            uid: Some(uid.clone()),
+            states: Some(states),
+            kept: true,
+        };
+        (audit, table_state)
+    }
+
+    /// For changes that aren't about a single match, e.g. a records rebuild
+    pub fn without_match(state: &AppState, actor: &str) -> Self {
+        Self {
+            pool: state.db_pool.clone(),
+            actor: actor.to_string(),
+            uid: None,
+            states: None,
+            kept: false,
+        }
+    }
+
+    /// No game state after the change, the match is gone
+    pub fn match_gone(self) -> Self {
+        Self {
+            kept: false,
+            ..self
+        }
+    }
+
+    /// Waits for the entry to be saved, so entries of consecutive requests keep their order.
+    /// The change is applied already, but the request fails if it can't be recorded.
+    pub async fn record(
+        self,
+        action: &'static str,
+        details: Value,
+    ) -> Result<(), (StatusCode, String)> {
+        info!(
+            target: "audit",
+            actor = %self.actor,
+            action,
+            match_id = self.uid.as_ref().map(TableUid::as_str),
+            %details,
+            "Manual change"
+        );
+
+        let states = self.states.map(|states| {
+            std::mem::take(&mut *states.lock().expect("audited states lock was poisoned"))
+        });
+        let (before, after) = states.map_or((None, None), |states| (states.before, states.after));
+        let entry = NewAuditEntry {
+            actor: self.actor,
+            action,
+            match_uid: self.uid,
+            details,
+            game_state_before: before,
+            game_state_after: after.filter(|_| self.kept),
+        };
+        insert_audit_entry(&self.pool, entry).await.map_err(|e| {
+            // the log line above is all that's left of it
+            error!(error = %e, "Failed to save audit entry");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to save audit entry".to_string(),
+            )
+        })
+    }
+}
+
+/// Unit tests have no database, their entries are kept in memory instead
+async fn insert_audit_entry(pool: &PgPool, entry: NewAuditEntry) -> Result<(), DbError> {
+    #[cfg(test)]
+    {
+        let _ = pool;
+        crate::tests::utils::mock_audit_log::insert(entry);
+        Ok(())
+    }
+    #[cfg(not(test))]
+    {
+        crate::database::insert_audit_entry(pool, &entry).await
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    match_id: Option<String>,
+    actor: Option<String>,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+pub async fn list_entries(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditLog>, (StatusCode, String)> {
+    actor.require(Scope::Read)?;
+
+    let filter = AuditFilter {
+        match_uid: query
+            .match_id
+            .map(TableUid::parse)
+            .transpose()
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
+        actor: query.actor,
+        from: query.from,
+        to: query.to,
+        limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+    };
+
+    let entries = get_audit_entries(&state.db_pool, &filter)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Failed to read audit log");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+
+    Ok(Json(AuditLog { entries }))
+}
@@ -0,0 +1,202 @@
+use std::{fmt, str::FromStr};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::models::application::AppState;
+
+/// What an admin API key is allowed to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Scope {
+    /// view all matches, including archived ones
+    Read,
+    /// correct the score of any match - undo points. Implied by `write`.
+    Referee,
+    /// change matches - score, server, rallies, claims, archiving
+    Write,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Referee => write!(f, "referee"),
+            Scope::Write => write!(f, "write"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "referee" => Ok(Scope::Referee),
+            "write" => Ok(Scope::Write),
+            other => Err(format!("unknown scope '{other}'")),
+        }
+    }
+}
+
+/// Configured as `actor:key:scope,scope`, so neither actor nor key can contain `:` or `;`
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminApiKey {
+    pub actor: String,
+    pub key: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl FromStr for AdminApiKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(actor), Some(key), Some(scopes)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err("expected 'actor:key:scope,scope'".to_string());
+        };
+        if actor.is_empty() || key.is_empty() {
+            return Err("actor and key can't be empty".to_string());
+        }
+
+        Ok(Self {
+            actor: actor.to_string(),
+            key: key.to_string(),
+            scopes: scopes
+                .split(',')
+                .map(str::trim)
+                .filter(|scope| !scope.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Authenticated admin, available as request extension behind `require_admin`
+#[derive(Clone, Debug)]
+pub struct Actor {
+    pub name: String,
+    scopes: Vec<Scope>,
+}
+
+impl Actor {
+    pub fn require(&self, scope: Scope) -> Result<(), (StatusCode, String)> {
+        let implied = scope == Scope::Referee && self.scopes.contains(&Scope::Write);
+        if implied || self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                format!("API key is missing '{scope}' scope"),
+            ))
+        }
+    }
+}
+
+/// Comparison time doesn't depend on how much of the key was guessed right
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+pub fn authenticate(state: &AppState, headers: &HeaderMap) -> Option<Actor> {
+    let token = bearer_token(headers)?;
+    state
+        .config
+        .admin
+        .api_keys
+        .iter()
+        .find(|api_key| constant_time_eq(api_key.key.as_bytes(), token.as_bytes()))
+        .map(|api_key| Actor {
+            name: api_key.actor.clone(),
+            scopes: api_key.scopes.clone(),
+        })
+}
+
+pub async fn require_admin(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match authenticate(&state, request.headers()) {
+        Some(actor) => {
+            request.extensions_mut().insert(actor);
+            next.run(request).await
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Valid admin API key required",
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_api_key() {
+        let key: AdminApiKey = "alice:s3cret:read, write".parse().unwrap();
+        assert_eq!(key.actor, "alice");
+        assert_eq!(key.key, "s3cret");
+        assert_eq!(key.scopes, vec![Scope::Read, Scope::Write]);
+
+        let key: AdminApiKey = "bob:key:read".parse().unwrap();
+        assert_eq!(key.scopes, vec![Scope::Read]);
+
+        let key: AdminApiKey = "ref:key:referee".parse().unwrap();
+        assert_eq!(key.scopes, vec![Scope::Referee]);
+
+        let key: AdminApiKey = "nobody:key:".parse().unwrap();
+        assert!(key.scopes.is_empty());
+    }
+
+    #[test]
+    fn invalid_api_keys() {
+        for invalid in [
+            "alice",
+            "alice:key",
+            ":key:read",
+            "alice::read",
+            "a:k:admin",
+        ] {
+            assert!(invalid.parse::<AdminApiKey>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn write_implies_referee() {
+        let actor = |scopes| Actor {
+            name: "alice".to_string(),
+            scopes,
+        };
+        assert!(actor(vec![Scope::Write]).require(Scope::Referee).is_ok());
+        assert!(actor(vec![Scope::Referee]).require(Scope::Referee).is_ok());
+        assert!(actor(vec![Scope::Referee]).require(Scope::Write).is_err());
+        assert!(actor(vec![Scope::Read]).require(Scope::Referee).is_err());
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"s"));
+    }
+}
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderName, StatusCode},
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::Deserialize;
+use tokio::time::timeout;
+use tracing::{Instrument, debug, info, info_span};
+
+use crate::{
+    corrections::{player_token, referee},
+    database::TableUid,
+    game_table::PLAYER_TOKEN_HEADER,
+    models::{
+        application::AppState,
+        bot::{BotRefused, BotSkill},
+        game::{Side, TableState, WeakTableState},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct BotPath {
+    side: Side,
+}
+
+/// Ball the bot has to play - it's a new one when any of these change
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Turn {
+    hit_count: usize,
+    rally_started_at: Option<jiff::Timestamp>,
+}
+
+fn turn_of(side: Side, table: &mut WeakTableState) -> Option<Turn> {
+    table.read_rally_state(|rally_state| {
+        (rally_state.side == side && !rally_state.paused).then_some(Turn {
+            hit_count: rally_state.hit_count,
+            rally_started_at: rally_state.first_hit_at,
+        })
+    })
+}
+
+/// Waits out the reaction time, `false` if the ball was paused, undone, let or ended
+/// by an admin in the meantime
+async fn react(
+    side: Side,
+    table: &mut WeakTableState,
+    turn: Turn,
+    reaction_time: Duration,
+) -> bool {
+    let turn_taken_away = async {
+        while table.changed().await {
+            if turn_of(side, table) != Some(turn) {
+                return;
+            }
+        }
+    };
+    timeout(reaction_time, turn_taken_away).await.is_err()
+}
+
+/// Plays `side` until aborted or the table is gone, through the same hit path as
+/// players do
+async fn play(side: Side, mut table: WeakTableState, skill: BotSkill) {
+    // thread_rng isn't Send
+    let mut rng = StdRng::from_os_rng();
+    let mut played: Option<Turn> = None;
+    loop {
+        let Some(turn) = turn_of(side, &mut table) else {
+            played = None;
+            if !table.changed().await {
+                return;
+            }
+            continue;
+        };
+        if played == Some(turn) {
+            // ball is dropping after a miss
+            if !table.changed().await {
+                return;
+            }
+            continue;
+        }
+        played = Some(turn);
+
+        let reaction_time = skill
+            .reaction_time(turn.hit_count, &mut rng)
+            .max(table.min_reaction_time());
+        if !react(side, &mut table, turn, reaction_time).await {
+            continue;
+        }
+        if turn.hit_count > 0 && rng.random_bool(skill.miss_probability) {
+            debug!(hit_count = turn.hit_count, "Bot lets the ball drop");
+            continue;
+        }
+        // bot plays for both partners in doubles
+        if table.hit(side, None).await.is_none() {
+            return;
+        }
+    }
+}
+
+/// What keeps others from attaching or detaching a bot
+fn refusal(side: Side, refused: BotRefused) -> (StatusCode, String) {
+    match refused {
+        BotRefused::Claimed(player) => (
+            StatusCode::CONFLICT,
+            format!("{side} side is claimed by {player}"),
+        ),
+        BotRefused::AlreadyPlayed => (
+            StatusCode::CONFLICT,
+            format!("{side} side is already played by a bot"),
+        ),
+        BotRefused::NoBot => (StatusCode::NOT_FOUND, format!("No bot on {side} side")),
+        BotRefused::NotKeeper => (
+            StatusCode::FORBIDDEN,
+            "Only whoever attached the bot or a referee can do that".to_string(),
+        ),
+    }
+}
+
+/// Free sides can get a bot from anyone. Claimed seats need the token of their player, or a
+/// referee. The bot's token, needed to detach it, comes back in the player token header.
+pub async fn attach_bot(
+    State(state): State<AppState>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Path(BotPath { side }): Path<BotPath>,
+    headers: HeaderMap,
+    Json(skill): Json<BotSkill>,
+) -> Result<(StatusCode, [(HeaderName, String); 1], Json<TableState>), (StatusCode, String)> {
+    if let Err(e) = skill.validate() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, e));
+    }
+    if table_state.is_wall(side) {
+        return Err((StatusCode::CONFLICT, format!("{side} side is a wall")));
+    }
+    let referee = referee(&state, &headers)?.is_some();
+    let token = player_token(&headers).map(str::to_string);
+
+    let span = info_span!("bot", match_id = %uid, %side);
+    let table = table_state.downgrade();
+    let bot_skill = skill.clone();
+    let start = move || tokio::spawn(play(side, table, bot_skill).instrument(span));
+    let token = table_state
+        .attach_bot(side, skill.clone(), token, referee, start)
+        .await?
+        .map_err(|refused| refusal(side, refused))?;
+    info!(%side, ?skill, "Bot attached");
+
+    Ok((
+        StatusCode::CREATED,
+        [(PLAYER_TOKEN_HEADER, token)],
+        Json(table_state),
+    ))
+}
+
+pub async fn detach_bot(
+    State(state): State<AppState>,
+    Extension(table_state): Extension<TableState>,
+    Path(BotPath { side }): Path<BotPath>,
+    headers: HeaderMap,
+) -> Result<Json<TableState>, (StatusCode, String)> {
+    let referee = referee(&state, &headers)?.is_some();
+    let token = player_token(&headers).map(str::to_string);
+    table_state
+        .detach_bot(side, token, referee)
+        .await?
+        .map_err(|refused| refusal(side, refused))?;
+
+    info!(%side, "Bot detached");
+    Ok(Json(table_state))
+}
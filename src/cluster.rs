@@ -0,0 +1,284 @@
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
+
+use axum::{
+    body::{self, Body},
+    extract::{ConnectInfo, OriginalUri, Request},
+    http::{HeaderName, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use reqwest::Client;
+use sqlx::postgres::PgListener;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::ClusterConfig,
+    database::{
+        DbError, MatchLease, OWNER_CHANNEL, Ownership, TableUid, acquire_match, renew_leases,
+    },
+    models::{application::AppState, game::TableState},
+};
+
+/// Set on requests forwarded to the instance serving the match, they are never forwarded again
+pub const FORWARDED_BY_HEADER: HeaderName = HeaderName::from_static("x-forwarded-by-instance");
+
+/// Gameplay and admin bodies are tiny
+const MAX_FORWARDED_BODY: usize = 1024 * 1024;
+
+/// Only meaningful for a single connection, or set again by our own layers
+const NOT_FORWARDED_HEADERS: [HeaderName; 6] = [
+    header::HOST,
+    header::CONNECTION,
+    header::TRANSFER_ENCODING,
+    header::CONTENT_LENGTH,
+    header::VARY,
+    header::UPGRADE,
+];
+
+/// This instance as one of several serving matches from the same database
+#[derive(Clone)]
+pub struct Cluster {
+    pub lease: MatchLease,
+    client: Client,
+}
+
+impl Cluster {
+    /// `None` unless `INSTANCE_URL` is set. Comes with the matches whose writes were refused,
+    /// for `follow_leases`.
+    pub fn new(config: &ClusterConfig) -> Option<(Self, mpsc::UnboundedReceiver<TableUid>)> {
+        let instance_url = config.instance_url.clone()?;
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(2))
+            .build()
+            .expect("Failed to create forwarding HTTP client");
+        let (lost, lost_leases) = mpsc::unbounded_channel();
+        let cluster = Self {
+            lease: MatchLease {
+                instance_url,
+                duration: config.lease,
+                lost,
+            },
+            client,
+        };
+        Some((cluster, lost_leases))
+    }
+}
+
+pub enum Located {
+    Local(TableState),
+    /// served by the instance at that URL
+    Remote(String),
+    Archived,
+    /// doesn't exist yet. Without a cluster also when it's archived.
+    Missing,
+}
+
+/// Finds the table of a match, taking it on if no other instance serves it
+pub async fn locate(state: &AppState, uid: &TableUid) -> Result<Located, DbError> {
+    if let Some(table_state) = state.game_tables.get(uid) {
+        return Ok(Located::Local(table_state));
+    }
+    let Some(cluster) = &state.cluster else {
+        return Ok(Located::Missing);
+    };
+
+    Ok(
+        match acquire_match(
+            &state.db_pool,
+            uid,
+            &cluster.lease,
+            &state.config.match_defaults,
+        )
+        .await?
+        {
+            Ownership::Acquired(table_state) => {
+                info!(match_id = %uid, "Match taken on");
+                Located::Local(state.game_tables.get_or_insert(uid, table_state))
+            }
+            Ownership::OwnedBy(url) => Located::Remote(url),
+            Ownership::Archived => Located::Archived,
+            Ownership::Missing => Located::Missing,
+        },
+    )
+}
+
+/// Stops serving the match here, without touching what's stored
+async fn drop_table(state: &AppState, uid: &TableUid) {
+    let removed = state.game_tables.remove(uid);
+    if let Some(table_state) = removed {
+        // a stopped table has no bots or rally left
+        let _ = table_state.remove_bots().await;
+        let _ = table_state.cancel_rally().await;
+    }
+}
+
+/// Passes the request on to the instance serving the match, and its answer back
+pub async fn forward(state: &AppState, owner_url: &str, request: Request) -> Response {
+    let Some(cluster) = &state.cluster else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let retry_after = cluster.lease.duration.as_secs().max(1);
+    let unavailable = |message: &str| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            message.to_string(),
+        )
+            .into_response()
+    };
+    if request.headers().contains_key(FORWARDED_BY_HEADER) {
+        // the match changed hands on the way, or the owner restarted and doesn't know it yet
+        return unavailable("Match is moving between instances");
+    }
+
+    // nested routers only see the rest of the path
+    let uri = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.clone(),
+        None => request.uri().clone(),
+    };
+    let path = uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = body::to_bytes(body, MAX_FORWARDED_BODY).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large").into_response();
+    };
+    let mut headers = parts.headers;
+    for name in NOT_FORWARDED_HEADERS {
+        headers.remove(name);
+    }
+    // behind trusted proxies the owner finds the client where this instance did,
+    // one more entry would make it count this instance's peer instead
+    if let Some(ip) = client_ip
+        && state.config.rate_limits.trusted_proxies == 0
+    {
+        let forwarded_for = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+            Some(earlier) => format!("{earlier}, {ip}"),
+            None => ip.to_string(),
+        };
+        if let Ok(value) = HeaderValue::try_from(forwarded_for) {
+            headers.insert("x-forwarded-for", value);
+        }
+    }
+    if let Ok(value) = HeaderValue::try_from(&cluster.lease.instance_url) {
+        headers.insert(FORWARDED_BY_HEADER, value);
+    }
+
+    let answer = cluster
+        .client
+        .request(parts.method, format!("{owner_url}{path}"))
+        .headers(headers)
+        .body(body)
+        .send()
+        .await;
+    let answer = match answer {
+        Ok(answer) => answer,
+        Err(e) => {
+            // taken over by someone else once the lease runs out
+            warn!(owner = owner_url, error = %e, "Instance serving the match is unreachable");
+            return unavailable("Instance serving the match is unavailable");
+        }
+    };
+
+    let status = answer.status();
+    let mut headers = answer.headers().clone();
+    for name in NOT_FORWARDED_HEADERS {
+        headers.remove(name);
+    }
+    let cors: Vec<HeaderName> = headers
+        .keys()
+        .filter(|name| name.as_str().starts_with("access-control-"))
+        .cloned()
+        .collect();
+    for name in cors {
+        headers.remove(name);
+    }
+    match answer.bytes().await {
+        Ok(body) => (status, headers, Body::from(body)).into_response(),
+        Err(e) => {
+            warn!(owner = owner_url, error = %e, "Answer of the instance serving the match was cut off");
+            unavailable("Instance serving the match is unavailable")
+        }
+    }
+}
+
+/// Keeps the leases of matches served here, and stops serving the ones taken over by someone
+/// else - announced right away, or noticed when renewing or writing
+pub async fn follow_leases(
+    state: &AppState,
+    lost_leases: Option<mpsc::UnboundedReceiver<TableUid>>,
+) -> Result<(), DbError> {
+    let (Some(cluster), Some(mut lost_leases)) = (state.cluster.clone(), lost_leases) else {
+        return Ok(());
+    };
+
+    let mut listener = PgListener::connect_with(&state.db_pool).await?;
+    listener.listen(OWNER_CHANNEL).await?;
+    let listening = state.clone();
+    let instance_url = cluster.lease.instance_url.clone();
+    tokio::spawn(async move {
+        loop {
+            let notification = match listener.recv().await {
+                Ok(notification) => notification,
+                Err(e) => {
+                    // reconnects on the next call, renewals catch whatever is missed meanwhile
+                    error!(error = %e, "Error while listening for match owners");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            let Some((uid, owner)) = notification.payload().split_once(' ') else {
+                continue;
+            };
+            if owner == instance_url {
+                continue;
+            }
+            if let Ok(uid) = TableUid::parse(uid)
+                && listening.game_tables.contains(&uid)
+            {
+                warn!(match_id = %uid, owner, "Match taken over by another instance");
+                drop_table(&listening, &uid).await;
+            }
+        }
+    });
+
+    let losing = state.clone();
+    tokio::spawn(async move {
+        while let Some(uid) = lost_leases.recv().await {
+            warn!(match_id = %uid, "Match lease lost while writing");
+            drop_table(&losing, &uid).await;
+        }
+    });
+
+    let renewing = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(cluster.lease.duration / 3);
+        loop {
+            interval.tick().await;
+            let served = renewing.game_tables.uids();
+            if served.is_empty() {
+                continue;
+            }
+            let renewed: HashSet<TableUid> =
+                match renew_leases(&renewing.db_pool, &cluster.lease, &served).await {
+                    Ok(renewed) => renewed.into_iter().collect(),
+                    Err(e) => {
+                        error!(error = %e, "Error while renewing match leases");
+                        continue;
+                    }
+                };
+            for uid in served.iter().filter(|uid| !renewed.contains(uid)) {
+                // archived ones are gone from memory already, unless that's racing this
+                warn!(match_id = %uid, "Match lease lost");
+                drop_table(&renewing, uid).await;
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use std::{env, fmt::Display, net::IpAddr, str::FromStr, time::Duration};
+
+use axum::http::{HeaderName, HeaderValue, Method, header};
+
+use crate::{auth::AdminApiKey, models::settings::MatchSettings};
+
+/// Runtime configuration, read once from environment variables on startup.
+///
+/// `Default` is the most permissive setup, which is also what you get with no variables set.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub rate_limits: RateLimitConfig,
+    pub cors: CorsConfig,
+    pub admin: AdminConfig,
+    /// Settings of matches that don't have their own
+    pub match_defaults: MatchSettings,
+    pub lobby: LobbyConfig,
+    pub records: RecordsConfig,
+    pub webhooks: WebhooksConfig,
+    pub cluster: ClusterConfig,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            rate_limits: RateLimitConfig::from_env(),
+            cors: CorsConfig::from_env(),
+            admin: AdminConfig::from_env(),
+            match_defaults: match_defaults_from_env(),
+            lobby: LobbyConfig::from_env(),
+            records: RecordsConfig::from_env(),
+            webhooks: WebhooksConfig::from_env(),
+            cluster: ClusterConfig::from_env(),
+        }
+    }
+}
+
+/// `None` disables particular limit
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    pub hits_per_second_per_ip: Option<u32>,
+    pub hits_per_second_per_match: Option<u32>,
+    pub match_creations_per_minute_per_ip: Option<u32>,
+    /// Long-polls a client can have open at once
+    pub open_waits_per_ip: Option<u32>,
+    /// Clients from these addresses are never throttled
+    pub trusted_ips: Vec<IpAddr>,
+    /// Clients sending one of these in `X-Api-Key` header are never throttled
+    pub api_keys: Vec<String>,
+    /// Proxies in front of the server, each appending the address it got the request from
+    /// to `X-Forwarded-For`. The client is that many entries from the right, as anything further
+    /// left was sent by the client itself. `0` ignores the header.
+    pub trusted_proxies: usize,
+}
+
+impl RateLimitConfig {
+    fn from_env() -> Self {
+        Self {
+            hits_per_second_per_ip: optional_var("RATE_LIMIT_HITS_PER_SECOND_PER_IP"),
+            hits_per_second_per_match: optional_var("RATE_LIMIT_HITS_PER_SECOND_PER_MATCH"),
+            match_creations_per_minute_per_ip: optional_var(
+                "RATE_LIMIT_MATCH_CREATIONS_PER_MINUTE_PER_IP",
+            ),
+            open_waits_per_ip: optional_var("RATE_LIMIT_OPEN_WAITS_PER_IP"),
+            trusted_ips: list_var("RATE_LIMIT_TRUSTED_IPS"),
+            api_keys: list_var("RATE_LIMIT_API_KEYS"),
+            trusted_proxies: optional_var("TRUSTED_PROXIES").unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LobbyConfig {
+    /// How long a player waits for an opponent before their ticket expires
+    pub ticket_ttl: Duration,
+    /// Longest a long-poll for a ticket is held open
+    pub max_wait: Duration,
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        Self {
+            ticket_ttl: Duration::from_secs(300),
+            max_wait: Duration::from_secs(30),
+        }
+    }
+}
+
+impl LobbyConfig {
+    fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            ticket_ttl: optional_var("LOBBY_TICKET_TTL_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.ticket_ttl),
+            max_wait: optional_var("LOBBY_MAX_WAIT_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.max_wait),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordsConfig {
+    /// How often new match events are checked for records
+    pub refresh: Duration,
+}
+
+impl Default for RecordsConfig {
+    fn default() -> Self {
+        Self {
+            refresh: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RecordsConfig {
+    fn from_env() -> Self {
+        Self {
+            refresh: optional_var("RECORDS_REFRESH_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(Self::default().refresh),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhooksConfig {
+    /// How often new match events and due deliveries are checked
+    pub poll: Duration,
+    /// Longest a receiver can take to answer
+    pub timeout: Duration,
+    /// Deliveries are given up after this many failed attempts
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled with each one after it
+    pub retry_base: Duration,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            poll: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+            max_attempts: 5,
+            retry_base: Duration::from_secs(2),
+        }
+    }
+}
+
+impl WebhooksConfig {
+    fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            poll: optional_var("WEBHOOK_POLL_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.poll),
+            timeout: optional_var("WEBHOOK_TIMEOUT_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            max_attempts: optional_var("WEBHOOK_MAX_ATTEMPTS").unwrap_or(defaults.max_attempts),
+            retry_base: optional_var("WEBHOOK_RETRY_BASE_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.retry_base),
+        }
+    }
+}
+
+/// Running several instances against one database. Off unless `instance_url` is set.
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    /// Base URL other instances reach this one at, e.g. `http://10.0.0.5:3000`.
+    /// Also tells instances apart, so it has to be unique and stay the same across restarts.
+    pub instance_url: Option<String>,
+    /// How long a match stays with an instance that stopped renewing it
+    pub lease: Duration,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            instance_url: None,
+            lease: Duration::from_secs(15),
+        }
+    }
+}
+
+impl ClusterConfig {
+    fn from_env() -> Self {
+        Self {
+            instance_url: optional_var::<String>("INSTANCE_URL")
+                .map(|url| url.trim_end_matches('/').to_string()),
+            lease: optional_var("MATCH_LEASE_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(Self::default().lease),
+        }
+    }
+}
+
+/// Either everything (`*` in env) or only listed values
+#[derive(Clone, Debug, PartialEq)]
+pub enum AllowList<T> {
+    Any,
+    Only(Vec<T>),
+}
+
+impl<T: FromStr> AllowList<T>
+where
+    T::Err: Display,
+{
+    fn from_env(name: &str, default: Self) -> Self {
+        match env::var(name) {
+            Err(_) => default,
+            Ok(value) if value.trim() == "*" => AllowList::Any,
+            Ok(_) => AllowList::Only(list_var(name)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CorsPolicy {
+    pub origins: AllowList<HeaderValue>,
+    pub methods: AllowList<Method>,
+    pub headers: AllowList<HeaderName>,
+    /// How long browsers may cache preflight responses
+    pub max_age: Option<Duration>,
+}
+
+impl CorsPolicy {
+    /// Gameplay is meant to be open for any page to embed
+    fn public() -> Self {
+        Self {
+            origins: AllowList::Any,
+            methods: AllowList::Any,
+            headers: AllowList::Any,
+            max_age: None,
+        }
+    }
+
+    /// No cross-origin access unless explicitly configured
+    fn admin() -> Self {
+        Self {
+            origins: AllowList::Only(vec![]),
+            methods: AllowList::Only(vec![Method::GET, Method::POST, Method::PUT, Method::DELETE]),
+            headers: AllowList::Only(vec![header::AUTHORIZATION, header::CONTENT_TYPE]),
+            max_age: None,
+        }
+    }
+
+    fn from_env(prefix: &str, default: Self) -> Self {
+        Self {
+            origins: AllowList::from_env(&format!("{prefix}_ALLOWED_ORIGINS"), default.origins),
+            methods: AllowList::from_env(&format!("{prefix}_ALLOWED_METHODS"), default.methods),
+            headers: AllowList::from_env(&format!("{prefix}_ALLOWED_HEADERS"), default.headers),
+            max_age: optional_var(&format!("{prefix}_MAX_AGE_SECONDS"))
+                .map(Duration::from_secs)
+                .or(default.max_age),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CorsConfig {
+    /// Gameplay, match listing and operational endpoints
+    pub public: CorsPolicy,
+    /// `/admin` endpoints
+    pub admin: CorsPolicy,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            public: CorsPolicy::public(),
+            admin: CorsPolicy::admin(),
+        }
+    }
+}
+
+impl CorsConfig {
+    fn from_env() -> Self {
+        Self {
+            public: CorsPolicy::from_env("CORS", CorsPolicy::public()),
+            admin: CorsPolicy::from_env("CORS_ADMIN", CorsPolicy::admin()),
+        }
+    }
+}
+
+/// No keys - no admin access
+#[derive(Clone, Debug, Default)]
+pub struct AdminConfig {
+    pub api_keys: Vec<AdminApiKey>,
+}
+
+impl AdminConfig {
+    fn from_env() -> Self {
+        Self {
+            // scopes are comma separated already
+            api_keys: separated_list_var("ADMIN_API_KEYS", ';'),
+        }
+    }
+}
+
+fn match_defaults_from_env() -> MatchSettings {
+    let defaults = MatchSettings::default();
+    let settings = MatchSettings {
+        min_reaction_time_ms: optional_var("MIN_REACTION_TIME_MS")
+            .unwrap_or(defaults.min_reaction_time_ms),
+        too_early: optional_var("TOO_EARLY_HITS").unwrap_or(defaults.too_early),
+        points_to_win: optional_var("POINTS_TO_WIN").or(defaults.points_to_win),
+        air_time_seconds: optional_var("AIR_TIME_SECONDS").or(defaults.air_time_seconds),
+    };
+    if let Err(e) = settings.validate() {
+        panic!("Match defaults are invalid: {e}");
+    }
+    settings
+}
+
+/// Misconfiguration should stop the server right away, not surface later
+fn optional_var<T: FromStr>(name: &str) -> Option<T>
+where
+    T::Err: Display,
+{
+    let value = env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(e) => panic!("{name} has invalid value '{value}': {e}"),
+    }
+}
+
+/// Comma separated values, empty if not set
+fn list_var<T: FromStr>(name: &str) -> Vec<T>
+where
+    T::Err: Display,
+{
+    separated_list_var(name, ',')
+}
+
+fn separated_list_var<T: FromStr>(name: &str, separator: char) -> Vec<T>
+where
+    T::Err: Display,
+{
+    env::var(name)
+        .unwrap_or_default()
+        .split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.parse() {
+            Ok(parsed) => parsed,
+            Err(e) => panic!("{name} has invalid item '{item}': {e}"),
+        })
+        .collect()
+}
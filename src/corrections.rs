@@ -0,0 +1,189 @@
+use axum::{
+    Extension, Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+use crate::{
+    audit::Audit,
+    auth::{Actor, Scope, authenticate},
+    database::TableUid,
+    game_table::PLAYER_TOKEN_HEADER,
+    models::{
+        application::AppState,
+        game::{Side, TableState},
+        player::PlayerName,
+    },
+};
+
+/// Who is allowed to correct the score of a match
+pub enum Corrector {
+    /// admin API key with `referee` scope
+    Referee(Actor),
+    /// first player who claimed a side, identified by their claim token
+    Owner(PlayerName),
+}
+
+impl Corrector {
+    /// as it's recorded in history and audit log
+    pub fn name(&self) -> String {
+        match self {
+            Corrector::Referee(actor) => actor.name.clone(),
+            Corrector::Owner(player) => format!("player:{player}"),
+        }
+    }
+}
+
+/// Actor of the referee key sent, `None` if there's no key. Keys that are unknown or
+/// lack the scope are refused rather than ignored.
+pub fn referee(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<Actor>, (StatusCode, String)> {
+    if !headers.contains_key(header::AUTHORIZATION) {
+        return Ok(None);
+    }
+    let actor = authenticate(state, headers).ok_or((
+        StatusCode::UNAUTHORIZED,
+        "Valid admin API key required".to_string(),
+    ))?;
+    actor.require(Scope::Referee)?;
+    Ok(Some(actor))
+}
+
+/// Referee key takes precedence, as it's checked no matter whose match it is
+pub fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+    table_state: &TableState,
+) -> Result<Corrector, (StatusCode, String)> {
+    if let Some(actor) = referee(state, headers)? {
+        return Ok(Corrector::Referee(actor));
+    }
+
+    let claims = table_state.claims();
+    match (
+        player_token(headers).and_then(|token| claims.side_with_token(token)),
+        &claims.owner,
+    ) {
+        (Some((_, player)), Some(owner)) if player == owner => Ok(Corrector::Owner(player.clone())),
+        _ => Err((
+            StatusCode::FORBIDDEN,
+            "Only the match owner or a referee can do that".to_string(),
+        )),
+    }
+}
+
+pub fn player_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(PLAYER_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+pub async fn undo(
+    State(state): State<AppState>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> Result<Json<TableState>, (StatusCode, String)> {
+    let corrector = authorize(&state, &headers, &table_state)?;
+    let (audit, table_state) = Audit::start(&state, &corrector.name(), &uid, &table_state);
+
+    if table_state.undo_point(corrector.name()).await?.is_none() {
+        return Err((StatusCode::CONFLICT, "Nothing to undo".to_string()));
+    }
+
+    audit.record("undo", json!({})).await?;
+    Ok(Json(table_state))
+}
+
+/// Owner or referee can call it right away, other players need both sides to ask
+pub async fn call_let(
+    State(state): State<AppState>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let no_rally = || (StatusCode::CONFLICT, "No rally in progress".to_string());
+    if !table_state.rally_in_progress() {
+        return Err(no_rally());
+    }
+
+    let by = match authorize(&state, &headers, &table_state) {
+        Ok(corrector) => corrector.name(),
+        Err(forbidden) => {
+            let claimed_side = player_token(&headers).and_then(|token| {
+                table_state
+                    .claims()
+                    .side_with_token(token)
+                    .map(|(side, player)| (side, player.clone()))
+            });
+            let Some((side, player)) = claimed_side else {
+                return Err(forbidden);
+            };
+            if !table_state.request_let(side).await? {
+                return Ok((
+                    StatusCode::ACCEPTED,
+                    format!(
+                        "Let requested by {player}, waiting for {} side to agree",
+                        side.flip()
+                    ),
+                )
+                    .into_response());
+            }
+            let claims = table_state.claims();
+            let name = |side| {
+                claims
+                    .get(side)
+                    .map(|claim| claim.player.to_string())
+                    .unwrap_or_default()
+            };
+            format!("players:{}+{}", name(Side::Ping), name(Side::Pong))
+        }
+    };
+
+    let (audit, table_state) = Audit::start(&state, &by, &uid, &table_state);
+    if !table_state.call_let(by).await? {
+        return Err(no_rally());
+    }
+
+    audit.record("let", json!({})).await?;
+    Ok(Json(table_state).into_response())
+}
+
+/// Hits are rejected until the match is resumed, the ball waits in the air
+pub async fn pause(
+    State(state): State<AppState>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> Result<Json<TableState>, (StatusCode, String)> {
+    let corrector = authorize(&state, &headers, &table_state)?;
+    let (audit, table_state) = Audit::start(&state, &corrector.name(), &uid, &table_state);
+
+    if !table_state.pause().await? {
+        return Err((StatusCode::CONFLICT, "Match is already paused".to_string()));
+    }
+
+    audit.record("pause", json!({})).await?;
+    Ok(Json(table_state))
+}
+
+pub async fn resume(
+    State(state): State<AppState>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> Result<Json<TableState>, (StatusCode, String)> {
+    let corrector = authorize(&state, &headers, &table_state)?;
+    let (audit, table_state) = Audit::start(&state, &corrector.name(), &uid, &table_state);
+
+    if !table_state.resume().await? {
+        return Err((StatusCode::CONFLICT, "Match is not paused".to_string()));
+    }
+
+    audit.record("resume", json!({})).await?;
+    Ok(Json(table_state))
+}
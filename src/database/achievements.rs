@@ -0,0 +1,115 @@
+use jiff::Timestamp;
+use sqlx::PgPool;
+use tracing::{info, instrument};
+
+use crate::models::{event::MatchEvent, player::PlayerName};
+
+use super::{DbError, TableUid};
+
+pub struct UnlockedAchievement {
+    pub achievement: String,
+    pub match_uid: TableUid,
+    pub unlocked_at: Timestamp,
+}
+
+/// Saves the first unlock together with its match event, later ones change nothing.
+/// With an `instance_url`, only while that instance serves the match.
+#[instrument(skip(pool), fields(%player))]
+pub async fn unlock_achievement(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    player: &PlayerName,
+    achievement: &str,
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    let unlocked = sqlx::query!(
+        "INSERT INTO player_achievement (player, achievement, match_uid)
+         SELECT $2, $3, uid FROM match
+         WHERE game_state_id = $1 AND ($4::text IS NULL OR instance_url = $4)
+         ON CONFLICT (player, achievement) DO NOTHING",
+        game_state_id,
+        player.as_str(),
+        achievement,
+        instance_url
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if unlocked {
+        info!(achievement, "Achievement unlocked");
+        let event = MatchEvent::Achievement {
+            player: player.clone(),
+            achievement: achievement.to_string(),
+        };
+        sqlx::query!(
+            "INSERT INTO match_event (game_state_id, kind, data) VALUES ($1, $2, $3)",
+            game_state_id,
+            event.kind(),
+            serde_json::to_value(&event)?
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Deletes the unlock if it came with the latest win of the match, which is undone.
+/// Unlocks from before that win stay.
+#[instrument(skip(pool), fields(%player))]
+pub async fn revoke_achievement(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    player: &PlayerName,
+    achievement: &str,
+) -> Result<(), DbError> {
+    let revoked = sqlx::query!(
+        "DELETE FROM player_achievement a USING match m
+         WHERE m.game_state_id = $1 AND ($4::text IS NULL OR m.instance_url = $4)
+           AND a.player = $2 AND a.achievement = $3 AND a.match_uid = m.uid
+           AND a.unlocked_at >= (
+               SELECT max(occurred_at) FROM match_event
+               WHERE game_state_id = $1 AND kind = 'won')",
+        game_state_id,
+        player.as_str(),
+        achievement,
+        instance_url
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0;
+    if revoked {
+        info!(achievement, "Achievement revoked");
+    }
+    Ok(())
+}
+
+/// Oldest first
+#[instrument(skip(pool))]
+pub async fn get_achievements(
+    pool: &PgPool,
+    player: &PlayerName,
+) -> Result<Vec<UnlockedAchievement>, DbError> {
+    sqlx::query!(
+        r#"SELECT achievement, match_uid, to_jsonb(unlocked_at) as "unlocked_at!"
+         FROM player_achievement WHERE player = $1
+         ORDER BY unlocked_at, achievement"#,
+        player.as_str()
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(UnlockedAchievement {
+            achievement: row.achievement,
+            match_uid: TableUid::parse(&row.match_uid)
+                .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", &row.match_uid)),
+            unlocked_at: serde_json::from_value(row.unlocked_at)?,
+        })
+    })
+    .collect()
+}
@@ -0,0 +1,113 @@
+use jiff::Timestamp;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use crate::models::game::GameState;
+
+use super::{DbError, TableUid};
+
+pub struct NewAuditEntry {
+    pub actor: String,
+    pub action: &'static str,
+    pub match_uid: Option<TableUid>,
+    pub details: Value,
+    pub game_state_before: Option<GameState>,
+    pub game_state_after: Option<GameState>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    pub occurred_at: Timestamp,
+    pub actor: String,
+    pub action: String,
+    pub match_id: Option<String>,
+    pub details: Value,
+    pub game_state_before: Option<GameState>,
+    pub game_state_after: Option<GameState>,
+}
+
+/// `None` matches everything
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub match_uid: Option<TableUid>,
+    pub actor: Option<String>,
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub limit: i64,
+}
+
+#[instrument(skip_all, fields(actor = %entry.actor, action = entry.action, match_id = entry.match_uid.as_ref().map(TableUid::as_str)))]
+pub async fn insert_audit_entry(pool: &PgPool, entry: &NewAuditEntry) -> Result<(), DbError> {
+    sqlx::query!(
+        "INSERT INTO audit_log (actor, action, match_uid, details, game_state_before, game_state_after)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+        entry.actor,
+        entry.action,
+        entry.match_uid.as_ref().map(TableUid::as_str),
+        entry.details,
+        entry
+            .game_state_before
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?,
+        entry
+            .game_state_after
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// newest first
+#[instrument(skip(pool))]
+pub async fn get_audit_entries(
+    pool: &PgPool,
+    filter: &AuditFilter,
+) -> Result<Vec<AuditEntry>, DbError> {
+    sqlx::query!(
+        r#"SELECT id, to_jsonb(occurred_at) as "occurred_at!", actor, action, match_uid,
+            details, game_state_before, game_state_after
+         FROM audit_log
+         WHERE ($1::text IS NULL OR match_uid = $1)
+           AND ($2::text IS NULL OR actor = $2)
+           AND ($3::text IS NULL OR occurred_at >= $3::text::timestamptz)
+           AND ($4::text IS NULL OR occurred_at < $4::text::timestamptz)
+         ORDER BY id DESC
+         LIMIT $5"#,
+        filter.match_uid.as_ref().map(TableUid::as_str),
+        filter.actor,
+        // jiff types aren't supported by sqlx, postgres parses RFC 3339 just fine
+        filter.from.map(|from| from.to_string()),
+        filter.to.map(|to| to.to_string()),
+        filter.limit,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(AuditEntry {
+            id: row.id,
+            occurred_at: serde_json::from_value(row.occurred_at)?,
+            actor: row.actor,
+            action: row.action,
+            match_id: row.match_uid,
+            details: row.details,
+            game_state_before: row
+                .game_state_before
+                .map(serde_json::from_value)
+                .transpose()?,
+            game_state_after: row
+                .game_state_after
+                .map(serde_json::from_value)
+                .transpose()?,
+        })
+    })
+    .collect()
+}
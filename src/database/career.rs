@@ -0,0 +1,95 @@
+use jiff::Timestamp;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use crate::models::{career::MatchResult, player::PlayerName};
+
+use super::{DbError, TableUid};
+
+pub struct PlayedMatch {
+    pub match_uid: TableUid,
+    pub finished_at: Timestamp,
+    pub result: MatchResult,
+}
+
+/// `None` matches everything
+#[derive(Debug, Default)]
+pub struct MatchFilter {
+    pub opponent: Option<PlayerName>,
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Won matches of `player`, newest first. Wins taken back by an undo don't count.
+#[instrument(skip(pool))]
+pub async fn get_player_matches(
+    pool: &PgPool,
+    player: &PlayerName,
+    filter: &MatchFilter,
+) -> Result<Vec<PlayedMatch>, DbError> {
+    sqlx::query!(
+        r#"SELECT m.uid, to_jsonb(e.occurred_at) as "occurred_at!", e.data
+         FROM match_event e JOIN match m ON m.game_state_id = e.game_state_id
+         WHERE e.kind = 'won'
+           AND NOT won_undone(e.id)
+           AND (($1 IN (e.data->'ping'->>'player', e.data->'ping'->>'partner')
+                 AND ($2::text IS NULL
+                   OR $2 IN (e.data->'pong'->>'player', e.data->'pong'->>'partner')))
+             OR ($1 IN (e.data->'pong'->>'player', e.data->'pong'->>'partner')
+                 AND ($2::text IS NULL
+                   OR $2 IN (e.data->'ping'->>'player', e.data->'ping'->>'partner'))))
+           AND ($3::text IS NULL OR e.occurred_at >= $3::text::timestamptz)
+           AND ($4::text IS NULL OR e.occurred_at < $4::text::timestamptz)
+         ORDER BY e.id DESC
+         LIMIT $5 OFFSET $6"#,
+        player.as_str(),
+        filter.opponent.as_ref().map(PlayerName::as_str),
+        // jiff types aren't supported by sqlx, postgres parses RFC 3339 just fine
+        filter.from.map(|from| from.to_string()),
+        filter.to.map(|to| to.to_string()),
+        filter.limit,
+        filter.offset,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(PlayedMatch {
+            match_uid: TableUid::parse(&row.uid)
+                .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", &row.uid)),
+            finished_at: serde_json::from_value(row.occurred_at)?,
+            result: played(row.data)?,
+        })
+    })
+    .collect()
+}
+
+/// All won matches of `player`, oldest first, without the undone ones
+#[instrument(skip(pool))]
+pub async fn get_career_results(
+    pool: &PgPool,
+    player: &PlayerName,
+) -> Result<Vec<MatchResult>, DbError> {
+    sqlx::query_scalar!(
+        "SELECT e.data FROM match_event e
+         WHERE e.kind = 'won'
+           AND NOT won_undone(e.id)
+           AND $1 IN (e.data->'ping'->>'player', e.data->'ping'->>'partner',
+                      e.data->'pong'->>'player', e.data->'pong'->>'partner')
+         ORDER BY e.id",
+        player.as_str()
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(played)
+    .collect()
+}
+
+/// Won events found by player always have both sides
+fn played(data: serde_json::Value) -> Result<MatchResult, DbError> {
+    Ok(MatchResult::from_event(serde_json::from_value(data)?)
+        .expect("won event with a player has both sides"))
+}
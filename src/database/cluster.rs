@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+use crate::models::{game::TableState, settings::MatchSettings};
+
+use super::{DbError, StoredTable, TableUid, get_claims};
+
+/// Channel instances announce matches they took on, as `<match uid> <instance url>`
+pub const OWNER_CHANNEL: &str = "match_owner";
+
+/// Serving matches as one of several instances
+#[derive(Clone, Debug)]
+pub struct MatchLease {
+    /// base URL the instance is reachable at, also what tells instances apart
+    pub instance_url: String,
+    /// how long a match stays with the instance after the last renewal
+    pub duration: Duration,
+    /// matches whose writes found them served by another instance
+    pub lost: mpsc::UnboundedSender<TableUid>,
+}
+
+pub enum Ownership {
+    /// served by the instance asking from now on
+    Acquired(TableState),
+    /// served by the instance at that URL
+    OwnedBy(String),
+    Archived,
+    Missing,
+}
+
+/// Leases the match unless another instance serves it, loading it if it does
+#[instrument(skip(pool, lease, default_settings), fields(%uid))]
+pub async fn acquire_match(
+    pool: &PgPool,
+    uid: &TableUid,
+    lease: &MatchLease,
+    default_settings: &MatchSettings,
+) -> Result<Ownership, DbError> {
+    let mut tx = pool.begin().await?;
+    let acquired = sqlx::query_as!(
+        StoredTable,
+        "UPDATE match
+         SET instance_url = $2, lease_expires_at = now() + make_interval(secs => $3)
+         FROM game_state
+         WHERE game_state.id = match.game_state_id AND uid = $1 AND archived_at IS NULL
+             AND (instance_url IS NULL OR instance_url = $2 OR lease_expires_at < now())
+         RETURNING uid, game_state_id, owner, settings, queue, data_dump as game_state",
+        uid.as_str(),
+        lease.instance_url,
+        lease.duration.as_secs_f64()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(stored) = acquired else {
+        let found = sqlx::query!(
+            r#"SELECT instance_url, archived_at IS NOT NULL as "archived!" FROM match WHERE uid = $1"#,
+            uid.as_str()
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        return Ok(match found {
+            None => Ownership::Missing,
+            Some(found) if found.archived => Ownership::Archived,
+            Some(found) => match found.instance_url {
+                Some(url) => Ownership::OwnedBy(url),
+                // created without an owner in the meantime, e.g. by a single instance setup
+                None => Ownership::Missing,
+            },
+        });
+    };
+
+    announce_owner(&mut tx, uid.as_str(), lease).await?;
+    tx.commit().await?;
+    let claims = get_claims(pool, Some(stored.game_state_id))
+        .await?
+        .remove(&stored.game_state_id)
+        .unwrap_or_default();
+    Ok(Ownership::Acquired(stored.into_table_state(
+        claims,
+        default_settings,
+        pool,
+        Some((uid, lease)),
+    )?))
+}
+
+/// Leases every live match nobody serves, returns their uids
+pub(super) async fn lease_free_matches(
+    pool: &PgPool,
+    lease: &MatchLease,
+) -> Result<Vec<String>, DbError> {
+    let mut tx = pool.begin().await?;
+    let uids = sqlx::query_scalar!(
+        "UPDATE match
+         SET instance_url = $1, lease_expires_at = now() + make_interval(secs => $2)
+         WHERE archived_at IS NULL
+             AND (instance_url IS NULL OR instance_url = $1 OR lease_expires_at < now())
+         RETURNING uid",
+        lease.instance_url,
+        lease.duration.as_secs_f64()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for uid in &uids {
+        announce_owner(&mut tx, uid, lease).await?;
+    }
+    tx.commit().await?;
+    Ok(uids)
+}
+
+/// Sent once the transaction commits. Whoever served the match before drops it.
+#[instrument(skip(tx, lease), fields(instance_url = %lease.instance_url))]
+async fn announce_owner(
+    tx: &mut Transaction<'_, Postgres>,
+    uid: &str,
+    lease: &MatchLease,
+) -> Result<(), DbError> {
+    // unchecked, pg_notify returns void which has no Rust type
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(OWNER_CHANNEL)
+        .bind(format!("{uid} {}", lease.instance_url))
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Extends leases of `uids`, returns the ones that are still this instance's
+#[instrument(skip_all, fields(matches = uids.len()))]
+pub async fn renew_leases(
+    pool: &PgPool,
+    lease: &MatchLease,
+    uids: &[TableUid],
+) -> Result<Vec<TableUid>, DbError> {
+    let uids: Vec<&str> = uids.iter().map(TableUid::as_str).collect();
+    let renewed = sqlx::query_scalar!(
+        "UPDATE match SET lease_expires_at = now() + make_interval(secs => $2)
+         WHERE instance_url = $1 AND uid = ANY($3) AND archived_at IS NULL
+         RETURNING uid",
+        lease.instance_url,
+        lease.duration.as_secs_f64(),
+        &uids as &[&str]
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(renewed
+        .iter()
+        .filter_map(|uid| TableUid::parse(uid).ok())
+        .collect())
+}
+
+/// Live matches, whichever instance serves them
+#[instrument(skip(pool))]
+pub async fn get_open_match_uids(pool: &PgPool) -> Result<Vec<TableUid>, DbError> {
+    let uids = sqlx::query_scalar!("SELECT uid FROM match WHERE archived_at IS NULL ORDER BY uid")
+        .fetch_all(pool)
+        .await?;
+    Ok(uids
+        .iter()
+        .filter_map(|uid| TableUid::parse(uid).ok())
+        .collect())
+}
@@ -9,6 +9,10 @@ pub enum DbError {
     Migration(MigrateError),
     Decoding(serde_json::Error),
     RowNotFound,
+    /// unique constraint violated, e.g. match id taken by an archived match
+    AlreadyExists,
+    /// nothing written, another instance serves the match now
+    LeaseLost,
 }
 
 impl From<VarError> for DbError {
@@ -19,7 +23,10 @@ impl From<VarError> for DbError {
 
 impl From<sqlx::Error> for DbError {
     fn from(value: sqlx::Error) -> Self {
-        DbError::Connection(value)
+        match value.as_database_error() {
+            Some(e) if e.is_unique_violation() => DbError::AlreadyExists,
+            _ => DbError::Connection(value),
+        }
     }
 }
 
@@ -43,6 +50,8 @@ impl fmt::Display for DbError {
             DbError::Migration(e) => write!(f, "Database migration failed: {}", e),
             DbError::Decoding(e) => write!(f, "Value decoding failed: {}", e),
             DbError::RowNotFound => write!(f, "Row not found"),
+            DbError::AlreadyExists => write!(f, "Row already exists"),
+            DbError::LeaseLost => write!(f, "Match is served by another instance"),
         }
     }
 }
@@ -54,7 +63,7 @@ impl Error for DbError {
             DbError::Connection(e) => Some(e),
             DbError::Migration(e) => Some(e),
             DbError::Decoding(e) => Some(e),
-            DbError::RowNotFound => None,
+            DbError::RowNotFound | DbError::AlreadyExists | DbError::LeaseLost => None,
         }
     }
 }
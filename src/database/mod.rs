@@ -1,33 +1,414 @@
+mod achievements;
+mod audit;
+mod career;
+mod cluster;
 mod db_error;
+mod rating;
+mod records;
 mod table_uid;
-use crate::models::{
-    application::GameTables,
-    game::{GameState, TableState},
+mod tournament;
+mod webhooks;
+use crate::{
+    metrics::METRICS,
+    models::{
+        application::GameTables,
+        event::MatchEvent,
+        game::{GameState, Partner, Side, TableState},
+        player::{Claim, Claims, PlayerName},
+        settings::MatchSettings,
+    },
+};
+pub use achievements::{UnlockedAchievement, get_achievements};
+use achievements::{revoke_achievement, unlock_achievement};
+pub use audit::{AuditEntry, AuditFilter, NewAuditEntry, get_audit_entries, insert_audit_entry};
+pub use career::{MatchFilter, PlayedMatch, get_career_results, get_player_matches};
+pub use cluster::{
+    MatchLease, OWNER_CHANNEL, Ownership, acquire_match, get_open_match_uids, renew_leases,
 };
 pub use db_error::DbError;
-use log::info;
-use sqlx::{PgPool, postgres::PgPoolOptions};
-use std::env;
+use jiff::Timestamp;
+pub use rating::{
+    Rating, create_rated_match, get_rating, get_unrated_matches, rate_match, register_rated_player,
+    unrate_match,
+};
+pub use records::{Record, get_records, rebuild_records, update_records};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    PgPool,
+    migrate::{Migrate, Migrator},
+    postgres::{PgPoolOptions, PgQueryResult},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    time::Instant,
+};
 pub use table_uid::{TableUid, TableUidError};
+use tokio::sync::mpsc;
+pub use tournament::{create_tournament, get_tournaments, save_tournament_matches};
+use tracing::{Instrument, Span, error, info, instrument};
+pub use webhooks::{
+    Attempt, Delivery, DueDelivery, NewWebhook, Webhook, claim_due_deliveries, create_webhook,
+    delete_webhook, enqueue_deliveries, get_deliveries, get_webhooks, record_attempt,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
 
+/// Changes of a single table, applied in the order they were made
+enum DbWrite {
+    /// boxed, it outgrew the other writes by far
+    GameState(Box<GameState>),
+    Event(MatchEvent),
+    Owner(PlayerName),
+    Settings(MatchSettings),
+    Claim(Side, Partner, Claim),
+    DeleteClaim(Side, Partner, Claim),
+    Queue(VecDeque<Claim>),
+    Achievement(PlayerName, &'static str),
+    RevokeAchievement(PlayerName, &'static str),
+}
+
+impl DbWrite {
+    /// Label of the write in metrics
+    fn kind(&self) -> &'static str {
+        match self {
+            DbWrite::GameState(_) => "game_state",
+            DbWrite::Event(_) => "event",
+            DbWrite::Owner(_) => "owner",
+            DbWrite::Settings(_) => "settings",
+            DbWrite::Claim(..) => "claim",
+            DbWrite::DeleteClaim(..) => "delete_claim",
+            DbWrite::Queue(_) => "queue",
+            DbWrite::Achievement(..) => "achievement",
+            DbWrite::RevokeAchievement(..) => "revoke_achievement",
+        }
+    }
+}
+
+/// Queues writes for a background task, so a slow write doesn't get overtaken by
+/// a later one - e.g. an undo landing before the point it undoes
 #[derive(Clone)]
 pub struct TableDbSyncHandle {
-    game_state_id: i64,
-    pool: PgPool,
+    writes: mpsc::UnboundedSender<(DbWrite, Span)>,
 }
 impl TableDbSyncHandle {
-    pub fn new(game_state_id: i64, pool: &PgPool) -> Self {
-        TableDbSyncHandle {
-            game_state_id,
-            pool: pool.clone(),
-        }
+    /// With a `lease`, writes only go through while this instance serves the match `uid`
+    pub fn new(game_state_id: i64, pool: &PgPool, lease: Option<(&TableUid, &MatchLease)>) -> Self {
+        let (writes, receiver) = mpsc::unbounded_channel();
+        let lease = lease.map(|(uid, lease)| WriteLease {
+            uid: uid.clone(),
+            instance_url: lease.instance_url.clone(),
+            lost: lease.lost.clone(),
+        });
+        tokio::spawn(write_in_order(game_state_id, pool.clone(), lease, receiver));
+        TableDbSyncHandle { writes }
+    }
+
+    pub fn update_game_state(&self, game_state: GameState) {
+        self.send(DbWrite::GameState(Box::new(game_state)));
+    }
+
+    pub fn save_event(&self, event: MatchEvent) {
+        self.send(DbWrite::Event(event));
+    }
+
+    pub fn save_owner(&self, owner: PlayerName) {
+        self.send(DbWrite::Owner(owner));
     }
 
-    pub async fn update_game_state(&self, game_state: GameState) -> Result<(), DbError> {
-        update_game_state(&self.pool, self.game_state_id, game_state).await
+    pub fn save_settings(&self, settings: MatchSettings) {
+        self.send(DbWrite::Settings(settings));
     }
+
+    pub fn save_claim(&self, side: Side, partner: Partner, claim: Claim) {
+        self.send(DbWrite::Claim(side, partner, claim));
+    }
+
+    /// Only deletes that exact claim, in case the side was claimed again in the meantime
+    pub fn delete_claim(&self, side: Side, partner: Partner, claim: Claim) {
+        self.send(DbWrite::DeleteClaim(side, partner, claim));
+    }
+
+    pub fn save_queue(&self, queue: VecDeque<Claim>) {
+        self.send(DbWrite::Queue(queue));
+    }
+
+    /// Announced in match history unless the player has it already
+    pub fn unlock_achievement(&self, player: PlayerName, achievement: &'static str) {
+        self.send(DbWrite::Achievement(player, achievement));
+    }
+
+    /// Takes back an achievement unlocked by the latest win, once that win is undone
+    pub fn revoke_achievement(&self, player: PlayerName, achievement: &'static str) {
+        self.send(DbWrite::RevokeAchievement(player, achievement));
+    }
+
+    fn send(&self, write: DbWrite) {
+        // writer only stops when every handle is gone, or once the lease is lost
+        let _ = self.writes.send((write, Span::current()));
+    }
+}
+
+/// Match a clustered table's writes are guarded with
+struct WriteLease {
+    uid: TableUid,
+    instance_url: String,
+    lost: mpsc::UnboundedSender<TableUid>,
+}
+
+/// Nothing written while clustered means the match isn't ours anymore
+fn check_lease(result: &PgQueryResult, instance_url: Option<&str>) -> Result<(), DbError> {
+    if instance_url.is_some() && result.rows_affected() == 0 {
+        return Err(DbError::LeaseLost);
+    }
+    Ok(())
 }
 
+async fn write_in_order(
+    game_state_id: i64,
+    pool: PgPool,
+    lease: Option<WriteLease>,
+    mut receiver: mpsc::UnboundedReceiver<(DbWrite, Span)>,
+) {
+    let instance_url = lease.as_ref().map(|lease| lease.instance_url.as_str());
+    while let Some((write, span)) = receiver.recv().await {
+        let kind = write.kind();
+        let started = Instant::now();
+        let result = async {
+            match write {
+                DbWrite::GameState(game_state) => {
+                    update_game_state(&pool, game_state_id, instance_url, *game_state)
+                        .await
+                        .inspect_err(|e| {
+                            error!(error = %e, "Error while updating game state in database")
+                        })
+                }
+                DbWrite::Event(event) => save_event(&pool, game_state_id, instance_url, &event)
+                    .await
+                    .inspect_err(|e| {
+                        error!(error = %e, kind = event.kind(), "Error while saving match event in database")
+                    }),
+                DbWrite::Owner(owner) => save_owner(&pool, game_state_id, instance_url, &owner)
+                    .await
+                    .inspect_err(|e| error!(error = %e, "Error while saving match owner in database")),
+                DbWrite::Settings(settings) => {
+                    save_settings(&pool, game_state_id, instance_url, &settings)
+                        .await
+                        .inspect_err(|e| {
+                            error!(error = %e, "Error while saving match settings in database")
+                        })
+                }
+                DbWrite::Claim(side, partner, claim) => {
+                    save_claim(&pool, game_state_id, instance_url, side, partner, &claim)
+                        .await
+                        .inspect_err(|e| error!(error = %e, "Error while saving claim in database"))
+                }
+                DbWrite::DeleteClaim(side, partner, claim) => {
+                    delete_claim(&pool, game_state_id, instance_url, side, partner, &claim)
+                        .await
+                        .inspect_err(|e| {
+                            error!(error = %e, "Error while deleting claim from database")
+                        })
+                }
+                DbWrite::Queue(queue) => save_queue(&pool, game_state_id, instance_url, &queue)
+                    .await
+                    .inspect_err(|e| error!(error = %e, "Error while saving queue in database")),
+                DbWrite::Achievement(player, achievement) => unlock_achievement(
+                    &pool,
+                    game_state_id,
+                    instance_url,
+                    &player,
+                    achievement,
+                )
+                .await
+                .inspect_err(|e| error!(error = %e, "Error while saving achievement in database")),
+                DbWrite::RevokeAchievement(player, achievement) => revoke_achievement(
+                    &pool,
+                    game_state_id,
+                    instance_url,
+                    &player,
+                    achievement,
+                )
+                .await
+                .inspect_err(|e| {
+                    error!(error = %e, "Error while revoking achievement in database")
+                }),
+            }
+        }
+        .instrument(span)
+        .await;
+        METRICS.record_db_write(kind, started, result.is_ok());
+
+        if let (Err(DbError::LeaseLost), Some(lease)) = (result, &lease) {
+            // the rest would be refused as well, whoever serves the match now has its state
+            let _ = lease.lost.send(lease.uid.clone());
+            return;
+        }
+    }
+}
+
+#[instrument(skip(pool, claim), fields(player = %claim.player))]
+async fn save_claim(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    side: Side,
+    partner: Partner,
+    claim: &Claim,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "INSERT INTO match_claim (game_state_id, side, partner, player, token)
+         SELECT $1, $2, $3, $4, $5 FROM match
+         WHERE game_state_id = $1 AND ($6::text IS NULL OR instance_url = $6)
+         ON CONFLICT (game_state_id, side, partner)
+         DO UPDATE SET player = EXCLUDED.player, token = EXCLUDED.token, claimed_at = now()",
+        game_state_id,
+        side.to_string(),
+        partner.to_string(),
+        claim.player.as_str(),
+        claim.token,
+        instance_url
+    )
+    .execute(pool)
+    .await?;
+    check_lease(&result, instance_url)
+}
+
+#[instrument(skip(pool, owner), fields(%owner))]
+async fn save_owner(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    owner: &PlayerName,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "UPDATE match SET owner = $2
+         WHERE game_state_id = $1 AND ($3::text IS NULL OR instance_url = $3)",
+        game_state_id,
+        owner.as_str(),
+        instance_url
+    )
+    .execute(pool)
+    .await?;
+    check_lease(&result, instance_url)
+}
+
+#[instrument(skip(pool, settings))]
+async fn save_settings(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    settings: &MatchSettings,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "UPDATE match SET settings = $2
+         WHERE game_state_id = $1 AND ($3::text IS NULL OR instance_url = $3)",
+        game_state_id,
+        serde_json::to_value(settings)?,
+        instance_url
+    )
+    .execute(pool)
+    .await?;
+    check_lease(&result, instance_url)
+}
+
+/// Queue is stored with tokens, unlike how `Claim` is serialized for players
+#[derive(Serialize, Deserialize)]
+struct QueuedClaim {
+    player: PlayerName,
+    token: String,
+}
+
+#[instrument(skip(pool, queue), fields(waiting = queue.len()))]
+async fn save_queue(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    queue: &VecDeque<Claim>,
+) -> Result<(), DbError> {
+    let queue: Vec<QueuedClaim> = queue
+        .iter()
+        .map(|claim| QueuedClaim {
+            player: claim.player.clone(),
+            token: claim.token.clone(),
+        })
+        .collect();
+    let result = sqlx::query!(
+        "UPDATE match SET queue = $2
+         WHERE game_state_id = $1 AND ($3::text IS NULL OR instance_url = $3)",
+        game_state_id,
+        serde_json::to_value(queue)?,
+        instance_url
+    )
+    .execute(pool)
+    .await?;
+    check_lease(&result, instance_url)
+}
+
+fn parse_queue(queue: serde_json::Value) -> Result<VecDeque<Claim>, DbError> {
+    let queue: Vec<QueuedClaim> = serde_json::from_value(queue)?;
+    Ok(queue
+        .into_iter()
+        .map(|QueuedClaim { player, token }| Claim { player, token })
+        .collect())
+}
+
+#[instrument(skip(pool, event), fields(kind = event.kind()))]
+async fn save_event(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    event: &MatchEvent,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "INSERT INTO match_event (game_state_id, kind, data)
+         SELECT $1, $2, $3 FROM match
+         WHERE game_state_id = $1 AND ($4::text IS NULL OR instance_url = $4)",
+        game_state_id,
+        event.kind(),
+        serde_json::to_value(event)?,
+        instance_url
+    )
+    .execute(pool)
+    .await?;
+    check_lease(&result, instance_url)
+}
+
+/// Deleting nothing is fine, the claim may be gone already - losing the lease shows on other writes
+#[instrument(skip(pool, claim), fields(player = %claim.player))]
+async fn delete_claim(
+    pool: &PgPool,
+    game_state_id: i64,
+    instance_url: Option<&str>,
+    side: Side,
+    partner: Partner,
+    claim: &Claim,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM match_claim USING match
+         WHERE match_claim.game_state_id = $1 AND match.game_state_id = $1
+             AND side = $2 AND partner = $3 AND token = $4
+             AND ($5::text IS NULL OR instance_url = $5)",
+        game_state_id,
+        side.to_string(),
+        partner.to_string(),
+        claim.token,
+        instance_url
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Match as stored in the database, archived or not
+pub struct StoredMatch {
+    pub uid: TableUid,
+    pub archived_at: Option<Timestamp>,
+    pub game_state: GameState,
+    pub claims: Claims,
+}
+
+#[instrument]
 pub async fn init_db() -> Result<PgPool, DbError> {
     info!("Starting database initialization");
     info!("Getting DATABASE_URL env variable");
@@ -40,40 +421,163 @@ pub async fn init_db() -> Result<PgPool, DbError> {
         .await?;
 
     info!("Running migrations");
-    sqlx::migrate!().run(&pool).await?;
+    MIGRATOR.run(&pool).await?;
 
     info!("Database initialized");
     Ok(pool)
 }
 
-/// gets and initializes game tables with sync handles
-pub async fn get_game_tables(pool: &PgPool) -> Result<GameTables, DbError> {
-    sqlx::query!(
-        "SELECT uid, game_state_id, data_dump as game_state
-     FROM match JOIN game_state ON match.game_state_id = game_state.id"
+/// cheapest possible roundtrip, just to know the database is there
+#[instrument(skip_all)]
+pub async fn check_connection(pool: &PgPool) -> Result<(), DbError> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+/// number of migrations known to this binary that are not applied to the database
+#[instrument(skip_all)]
+pub async fn count_pending_migrations(pool: &PgPool) -> Result<usize, DbError> {
+    let mut conn = pool.acquire().await?;
+    let applied: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .count())
+}
+
+/// names are validated before they are saved, so invalid ones need fixing by hand
+fn parse_player_name(name: String) -> PlayerName {
+    PlayerName::try_from(name).unwrap_or_else(|e| panic!("Invalid player name in database: {e}"))
+}
+
+/// claims of all matches, or just of `game_state_id`, by game state id
+#[instrument(skip(pool))]
+async fn get_claims(
+    pool: &PgPool,
+    game_state_id: Option<i64>,
+) -> Result<HashMap<i64, Claims>, DbError> {
+    let mut claims: HashMap<i64, Claims> = HashMap::new();
+    for row in sqlx::query!(
+        "SELECT game_state_id, side, partner, player, token FROM match_claim
+         WHERE $1::bigint IS NULL OR game_state_id = $1",
+        game_state_id
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        let side: Side = row
+            .side
+            .parse()
+            .expect("side is constrained in the database");
+        let partner: Partner = row
+            .partner
+            .parse()
+            .expect("partner is constrained in the database");
+        let player = parse_player_name(row.player);
+        *claims
+            .entry(row.game_state_id)
+            .or_default()
+            .seat_slot(side, partner) = Some(Claim {
+            player,
+            token: row.token,
+        });
+    }
+    Ok(claims)
+}
+
+/// Live match as stored, ready to be turned into a table
+struct StoredTable {
+    uid: String,
+    game_state_id: i64,
+    owner: Option<String>,
+    settings: Option<serde_json::Value>,
+    queue: serde_json::Value,
+    game_state: serde_json::Value,
+}
+
+impl StoredTable {
+    /// Starts syncing the table back. Matches without their own settings get `default_settings`
+    fn into_table_state(
+        self,
+        claims: Claims,
+        default_settings: &MatchSettings,
+        pool: &PgPool,
+        lease: Option<(&TableUid, &MatchLease)>,
+    ) -> Result<TableState, DbError> {
+        Ok(TableState::new(
+            serde_json::from_value(self.game_state)?,
+            Claims {
+                owner: self.owner.map(parse_player_name),
+                queue: parse_queue(self.queue)?,
+                ..claims
+            },
+            match self.settings {
+                Some(settings) => serde_json::from_value(settings)?,
+                None => default_settings.clone(),
+            },
+            TableDbSyncHandle::new(self.game_state_id, pool, lease),
+        ))
+    }
+}
+
+/// gets and initializes game tables with sync handles, archived matches are skipped
+/// Matches without their own settings get `default_settings`.
+/// With a `lease`, only matches nobody else serves are taken, and they are leased first.
+#[instrument(skip_all)]
+pub async fn get_game_tables(
+    pool: &PgPool,
+    default_settings: &MatchSettings,
+    lease: Option<&MatchLease>,
+) -> Result<GameTables, DbError> {
+    let leased = match lease {
+        Some(lease) => Some(cluster::lease_free_matches(pool, lease).await?),
+        None => None,
+    };
+    let mut claims = get_claims(pool, None).await?;
+    sqlx::query_as!(
+        StoredTable,
+        "SELECT uid, game_state_id, owner, settings, queue, data_dump as game_state
+     FROM match JOIN game_state ON match.game_state_id = game_state.id
+     WHERE archived_at IS NULL AND ($1::text[] IS NULL OR uid = ANY($1))",
+        leased.as_deref()
     )
     .fetch_all(pool)
     .await?
     .into_iter()
     .map(|row| {
+        let game_state_id = row.game_state_id;
+        // If database has invalid UIDs we want to fail fast and fix
+        let uid = TableUid::parse(&row.uid)
+            .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", row.uid));
         // TODO: One bad record destroys everything. Think if we want that or filter
-        let table_state = TableState::new(
-            serde_json::from_value(row.game_state)?,
-            TableDbSyncHandle::new(row.game_state_id, pool),
-        );
-        Ok((
-            // If database has invalid UIDs we want to fail fast and fix
-            TableUid::parse(&row.uid)
-                .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", &row.uid)),
-            table_state,
-        ))
+        let table_state = row.into_table_state(
+            claims.remove(&game_state_id).unwrap_or_default(),
+            default_settings,
+            pool,
+            lease.map(|lease| (&uid, lease)),
+        )?;
+        Ok((uid, table_state))
     })
     .collect()
 }
 
-pub async fn create_new_match(pool: &PgPool, uid: &TableUid) -> Result<TableState, DbError> {
-    let initial_game_state = GameState::default();
-
+/// `settings` are not stored, so the match follows the defaults from config.
+/// With a `lease`, the match is served by the instance creating it.
+#[instrument(skip(pool, initial_game_state, settings, lease), fields(%uid))]
+pub async fn create_new_match(
+    pool: &PgPool,
+    uid: &TableUid,
+    initial_game_state: GameState,
+    settings: MatchSettings,
+    lease: Option<&MatchLease>,
+) -> Result<TableState, DbError> {
     let mut tx = pool.begin().await?;
 
     let game_state_id = sqlx::query!(
@@ -85,9 +589,13 @@ pub async fn create_new_match(pool: &PgPool, uid: &TableUid) -> Result<TableStat
     .id;
 
     sqlx::query!(
-        "INSERT INTO match (uid, game_state_id) VALUES ($1, $2)",
+        "INSERT INTO match (uid, game_state_id, instance_url, lease_expires_at)
+         VALUES ($1, $2, $3, now() + make_interval(secs => $4))",
         uid.as_str(),
-        game_state_id
+        game_state_id,
+        lease.map(|lease| lease.instance_url.as_str()),
+        // NULL interval leaves the lease NULL
+        lease.map(|lease| lease.duration.as_secs_f64())
     )
     .execute(&mut *tx)
     .await?;
@@ -96,26 +604,80 @@ pub async fn create_new_match(pool: &PgPool, uid: &TableUid) -> Result<TableStat
 
     Ok(TableState::new(
         initial_game_state,
-        TableDbSyncHandle::new(game_state_id, pool),
+        Claims::default(),
+        settings,
+        TableDbSyncHandle::new(game_state_id, pool, lease.map(|lease| (uid, lease))),
     ))
 }
 
+/// all matches including archived ones, as last saved
+#[instrument(skip_all)]
+pub async fn get_all_matches(pool: &PgPool) -> Result<Vec<StoredMatch>, DbError> {
+    let mut claims = get_claims(pool, None).await?;
+    sqlx::query!(
+        r#"SELECT uid, game_state_id, owner, queue, to_jsonb(archived_at) as "archived_at", data_dump as game_state
+     FROM match JOIN game_state ON match.game_state_id = game_state.id
+     ORDER BY uid"#
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(StoredMatch {
+            uid: TableUid::parse(&row.uid)
+                .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", &row.uid)),
+            archived_at: row.archived_at.map(serde_json::from_value).transpose()?,
+            game_state: serde_json::from_value(row.game_state)?,
+            claims: Claims {
+                owner: row.owner.map(parse_player_name),
+                queue: parse_queue(row.queue)?,
+                ..claims.remove(&row.game_state_id).unwrap_or_default()
+            },
+        })
+    })
+    .collect()
+}
+
+/// `RowNotFound` if there is no such match or it's archived already
+#[instrument(skip(pool), fields(%uid))]
+pub async fn archive_match(pool: &PgPool, uid: &TableUid) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "UPDATE match SET archived_at = now() WHERE uid = $1 AND archived_at IS NULL",
+        uid.as_str()
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(DbError::RowNotFound);
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(pool, game_state))]
 async fn update_game_state(
     pool: &PgPool,
     table_id: i64,
+    instance_url: Option<&str>,
     game_state: GameState,
 ) -> Result<(), DbError> {
     let data_dump =
         serde_json::to_value(game_state).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
 
     let result = sqlx::query!(
-        "UPDATE game_state SET data_dump = $2 WHERE id = $1",
+        "UPDATE game_state SET data_dump = $2
+         FROM match
+         WHERE game_state.id = $1 AND match.game_state_id = game_state.id
+             AND ($3::text IS NULL OR instance_url = $3)",
         table_id,
-        data_dump
+        data_dump,
+        instance_url
     )
     .execute(pool)
     .await?;
 
+    check_lease(&result, instance_url)?;
     if result.rows_affected() == 0 {
         return Err(DbError::RowNotFound);
     }
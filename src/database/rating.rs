@@ -0,0 +1,207 @@
+use sqlx::PgPool;
+use tracing::instrument;
+
+use crate::models::{
+    lobby::{DEFAULT_RATING, rate},
+    player::PlayerName,
+};
+
+use super::{DbError, TableUid, parse_player_name};
+
+pub struct Rating {
+    pub rating: i32,
+    pub rated_matches: i32,
+}
+
+/// Players without rated matches have the default rating
+#[instrument(skip(pool))]
+pub async fn get_rating(pool: &PgPool, player: &PlayerName) -> Result<Rating, DbError> {
+    let row = sqlx::query!(
+        "SELECT rating, rated_matches FROM player_rating WHERE player = $1",
+        player.as_str()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match row {
+        Some(row) => Rating {
+            rating: row.rating,
+            rated_matches: row.rated_matches,
+        },
+        None => Rating {
+            rating: DEFAULT_RATING,
+            rated_matches: 0,
+        },
+    })
+}
+
+/// Registers the token on the first rated match of the player, returns the token they
+/// registered with
+#[instrument(skip(pool, token))]
+pub async fn register_rated_player(
+    pool: &PgPool,
+    player: &PlayerName,
+    token: &str,
+) -> Result<String, DbError> {
+    Ok(sqlx::query_scalar!(
+        "INSERT INTO rated_player (player, token) VALUES ($1, $2)
+         ON CONFLICT (player) DO UPDATE SET player = EXCLUDED.player
+         RETURNING token",
+        player.as_str(),
+        token
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+#[instrument(skip(pool), fields(%uid))]
+pub async fn create_rated_match(
+    pool: &PgPool,
+    uid: &TableUid,
+    ping: &PlayerName,
+    pong: &PlayerName,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        "INSERT INTO rated_match (match_uid, ping_player, pong_player) VALUES ($1, $2, $3)",
+        uid.as_str(),
+        ping.as_str(),
+        pong.as_str()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Rated matches whose result hasn't changed the ratings yet, with ping and pong player
+#[instrument(skip_all)]
+pub async fn get_unrated_matches(
+    pool: &PgPool,
+) -> Result<Vec<(TableUid, PlayerName, PlayerName)>, DbError> {
+    Ok(sqlx::query!(
+        "SELECT match_uid, ping_player, pong_player FROM rated_match WHERE rated_at IS NULL"
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        (
+            TableUid::parse(&row.match_uid)
+                .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", &row.match_uid)),
+            parse_player_name(row.ping_player),
+            parse_player_name(row.pong_player),
+        )
+    })
+    .collect())
+}
+
+/// Applies the result once, returns new ratings of the winner and the loser.
+/// `None` if the match was rated already.
+#[instrument(skip(pool), fields(%uid))]
+pub async fn rate_match(
+    pool: &PgPool,
+    uid: &TableUid,
+    winner: &PlayerName,
+    loser: &PlayerName,
+) -> Result<Option<(i32, i32)>, DbError> {
+    let mut tx = pool.begin().await?;
+
+    let marked = sqlx::query!(
+        "UPDATE rated_match SET rated_at = now() WHERE match_uid = $1 AND rated_at IS NULL",
+        uid.as_str()
+    )
+    .execute(&mut *tx)
+    .await?;
+    if marked.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let mut current = [DEFAULT_RATING; 2];
+    for row in sqlx::query!(
+        "SELECT player, rating FROM player_rating WHERE player IN ($1, $2) FOR UPDATE",
+        winner.as_str(),
+        loser.as_str()
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    {
+        current[usize::from(row.player != winner.as_str())] = row.rating;
+    }
+
+    let (winner_rating, loser_rating) = rate(current[0], current[1]);
+    for (player, rating) in [(winner, winner_rating), (loser, loser_rating)] {
+        sqlx::query!(
+            "INSERT INTO player_rating (player, rating, rated_matches) VALUES ($1, $2, 1)
+             ON CONFLICT (player) DO UPDATE
+             SET rating = EXCLUDED.rating, rated_matches = player_rating.rated_matches + 1, updated_at = now()",
+            player.as_str(),
+            rating
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query!(
+        "UPDATE rated_match SET winner = $2, winner_change = $3, loser_change = $4
+         WHERE match_uid = $1",
+        uid.as_str(),
+        winner.as_str(),
+        winner_rating - current[0],
+        loser_rating - current[1]
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(Some((winner_rating, loser_rating)))
+}
+
+/// Takes back what the result changed, once its win is undone. `false` if the match
+/// wasn't rated.
+#[instrument(skip(pool), fields(%uid))]
+pub async fn unrate_match(pool: &PgPool, uid: &TableUid) -> Result<bool, DbError> {
+    let mut tx = pool.begin().await?;
+
+    let Some(rated) = sqlx::query!(
+        r#"SELECT ping_player, pong_player, winner as "winner!", winner_change as "winner_change!",
+                loser_change as "loser_change!"
+         FROM rated_match
+         WHERE match_uid = $1 AND rated_at IS NOT NULL AND winner IS NOT NULL
+         FOR UPDATE"#,
+        uid.as_str()
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(false);
+    };
+    sqlx::query!(
+        "UPDATE rated_match
+         SET rated_at = NULL, winner = NULL, winner_change = NULL, loser_change = NULL
+         WHERE match_uid = $1",
+        uid.as_str()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let loser = if rated.winner == rated.ping_player {
+        rated.pong_player
+    } else {
+        rated.ping_player
+    };
+    for (player, change) in [
+        (rated.winner, rated.winner_change),
+        (loser, rated.loser_change),
+    ] {
+        sqlx::query!(
+            "UPDATE player_rating
+             SET rating = rating - $2, rated_matches = rated_matches - 1, updated_at = now()
+             WHERE player = $1",
+            player,
+            change
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use jiff::Timestamp;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{info, instrument};
+
+use crate::models::{
+    event::MatchEvent,
+    player::PlayerName,
+    records::{RecordKind, event_candidates, game_candidates, record_holders},
+};
+
+use super::{DbError, TableUid, parse_player_name};
+
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub kind: RecordKind,
+    pub value: i64,
+    pub match_uid: TableUid,
+    pub ping_player: Option<PlayerName>,
+    pub pong_player: Option<PlayerName>,
+    pub ping_partner: Option<PlayerName>,
+    pub pong_partner: Option<PlayerName>,
+    pub set_at: Timestamp,
+    /// event that set it
+    pub event_id: i64,
+}
+
+#[instrument(skip(pool))]
+pub async fn get_records(pool: &PgPool) -> Result<Vec<Record>, DbError> {
+    sqlx::query!(
+        r#"SELECT kind, value, match_uid, ping_player, pong_player, ping_partner, pong_partner,
+                to_jsonb(set_at) as "set_at!", event_id
+         FROM record"#
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    // kinds dropped from the code are left alone
+    .filter_map(|row| Some((RecordKind::parse(&row.kind)?, row)))
+    .map(|(kind, row)| {
+        Ok(Record {
+            kind,
+            value: row.value,
+            match_uid: TableUid::parse(&row.match_uid)
+                .unwrap_or_else(|_| panic!("Invalid Table UID in database: {}", &row.match_uid)),
+            ping_player: row.ping_player.map(parse_player_name),
+            pong_player: row.pong_player.map(parse_player_name),
+            ping_partner: row.ping_partner.map(parse_player_name),
+            pong_partner: row.pong_partner.map(parse_player_name),
+            set_at: serde_json::from_value(row.set_at)?,
+            event_id: row.event_id,
+        })
+    })
+    .collect()
+}
+
+/// Goes through up to `batch` events not seen yet and keeps the records they beat.
+/// Returns how many events were seen.
+///
+/// Events are only taken once they are a second old, so one saved a moment later but
+/// under a lower id isn't skipped for good. Undone wins set no records, once one that set
+/// a record is undone, they are all built again.
+#[instrument(skip(pool))]
+pub async fn update_records(pool: &PgPool, batch: i64) -> Result<usize, DbError> {
+    let mut tx = pool.begin().await?;
+    // instances take turns, the cursor row is the lock
+    let cursor = sqlx::query!(
+        "SELECT last_event_id, announced_after_event_id FROM record_cursor FOR UPDATE"
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let events = sqlx::query!(
+        r#"SELECT e.id, e.game_state_id, to_jsonb(e.occurred_at) as "occurred_at!", e.data, m.uid,
+                EXISTS (
+                    -- an admin set the score earlier in the same game
+                    SELECT 1 FROM match_event s
+                    WHERE s.game_state_id = e.game_state_id AND s.id < e.id AND s.kind = 'scoreSet'
+                      AND s.id > COALESCE(
+                          (SELECT max(id) FROM match_event b
+                           WHERE b.game_state_id = e.game_state_id AND b.id < e.id
+                             AND (b.kind = 'reset' OR (b.kind = 'won' AND NOT won_undone(b.id)))),
+                          0)
+                ) as "score_set!",
+                e.kind = 'won' AND won_undone(e.id) as "undone!",
+                EXISTS (
+                    -- the undo takes back a win that set a record
+                    SELECT 1 FROM record r JOIN match_event w ON w.id = r.event_id
+                    WHERE e.kind = 'undo' AND w.kind = 'won'
+                      AND w.game_state_id = e.game_state_id AND w.id < e.id
+                      AND NOT EXISTS (
+                          SELECT 1 FROM match_event between_them
+                          WHERE between_them.game_state_id = e.game_state_id
+                            AND between_them.id > w.id AND between_them.id < e.id
+                            AND between_them.kind IN ('point', 'undo', 'reset', 'scoreSet'))
+                ) as "retracts_record!"
+         FROM match_event e JOIN match m ON m.game_state_id = e.game_state_id
+         WHERE e.id > $1 AND e.occurred_at < now() - interval '1 second'
+         ORDER BY e.id
+         LIMIT $2"#,
+        cursor.last_event_id,
+        batch
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let Some(last_event_id) = events.last().map(|row| row.id) else {
+        return Ok(0);
+    };
+
+    let mut best: HashMap<RecordKind, i64> = sqlx::query!("SELECT kind, value FROM record")
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .filter_map(|row| Some((RecordKind::parse(&row.kind)?, row.value)))
+        .collect();
+
+    // with the match to announce it in, if it's to be announced
+    let mut beaten: HashMap<RecordKind, (Record, Option<i64>)> = HashMap::new();
+    for row in &events {
+        if row.retracts_record {
+            // built again without the undone win, events not seen yet are still announced
+            info!(match_id = %row.uid, "Record set by an undone win, rebuilding records");
+            sqlx::query!("DELETE FROM record").execute(&mut *tx).await?;
+            sqlx::query!(
+                "UPDATE record_cursor
+                 SET last_event_id = 0,
+                     announced_after_event_id = GREATEST(announced_after_event_id, $1)",
+                cursor.last_event_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(events.len());
+        }
+        if row.undone {
+            continue;
+        }
+        let event: MatchEvent = serde_json::from_value(row.data.clone())?;
+        let occurred_at: Timestamp = serde_json::from_value(row.occurred_at.clone())?;
+        let holders = record_holders(&event);
+        let mut candidates = event_candidates(&event, row.score_set);
+        if let MatchEvent::Won { winner, .. } = event {
+            let game = game_events(&mut tx, row.game_state_id, row.id).await?;
+            candidates.extend(game_candidates(&game, occurred_at, winner));
+        }
+
+        for (kind, value) in candidates {
+            if best
+                .get(&kind)
+                .is_some_and(|&current| !kind.beats(value, current))
+            {
+                continue;
+            }
+            best.insert(kind, value);
+            let announce_in =
+                (row.id > cursor.announced_after_event_id).then_some(row.game_state_id);
+            beaten.insert(
+                kind,
+                (
+                    Record {
+                        kind,
+                        value,
+                        match_uid: TableUid::parse(&row.uid).unwrap_or_else(|_| {
+                            panic!("Invalid Table UID in database: {}", &row.uid)
+                        }),
+                        ping_player: holders.ping.clone(),
+                        pong_player: holders.pong.clone(),
+                        ping_partner: holders.ping_partner.clone(),
+                        pong_partner: holders.pong_partner.clone(),
+                        set_at: occurred_at,
+                        event_id: row.id,
+                    },
+                    announce_in,
+                ),
+            );
+        }
+    }
+
+    for (kind, (record, announce_in)) in beaten {
+        info!(%kind, value = record.value, match_id = %record.match_uid, "New record");
+        if let Some(game_state_id) = announce_in {
+            let event = MatchEvent::Record {
+                record: kind.to_string(),
+                value: record.value,
+            };
+            sqlx::query!(
+                "INSERT INTO match_event (game_state_id, kind, data) VALUES ($1, $2, $3)",
+                game_state_id,
+                event.kind(),
+                serde_json::to_value(&event)?
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query!(
+            "INSERT INTO record
+                 (kind, value, match_uid, ping_player, pong_player, ping_partner, pong_partner, set_at,
+                  event_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8::text::timestamptz, $9)
+             ON CONFLICT (kind) DO UPDATE
+             SET value = EXCLUDED.value, match_uid = EXCLUDED.match_uid, ping_player = EXCLUDED.ping_player,
+                 pong_player = EXCLUDED.pong_player, ping_partner = EXCLUDED.ping_partner,
+                 pong_partner = EXCLUDED.pong_partner, set_at = EXCLUDED.set_at,
+                 event_id = EXCLUDED.event_id",
+            kind.to_string(),
+            record.value,
+            record.match_uid.as_str(),
+            record.ping_player.as_ref().map(PlayerName::as_str),
+            record.pong_player.as_ref().map(PlayerName::as_str),
+            record.ping_partner.as_ref().map(PlayerName::as_str),
+            record.pong_partner.as_ref().map(PlayerName::as_str),
+            // jiff types aren't supported by sqlx, postgres parses RFC 3339 just fine
+            record.set_at.to_string(),
+            record.event_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!("UPDATE record_cursor SET last_event_id = $1", last_event_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(events.len())
+}
+
+/// Events of the game that ended with event `won_id`, since the previous win or reset.
+/// Undone wins didn't end their game.
+#[instrument(skip(tx))]
+async fn game_events(
+    tx: &mut Transaction<'_, Postgres>,
+    game_state_id: i64,
+    won_id: i64,
+) -> Result<Vec<(Timestamp, MatchEvent)>, DbError> {
+    sqlx::query!(
+        r#"SELECT to_jsonb(occurred_at) as "occurred_at!", data FROM match_event
+         WHERE game_state_id = $1 AND id < $2
+           AND id > COALESCE(
+               (SELECT max(id) FROM match_event
+                WHERE game_state_id = $1 AND id < $2
+                  AND (kind = 'reset' OR (kind = 'won' AND NOT won_undone(id)))),
+               0)
+         ORDER BY id"#,
+        game_state_id,
+        won_id
+    )
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok((
+            serde_json::from_value(row.occurred_at)?,
+            serde_json::from_value(row.data)?,
+        ))
+    })
+    .collect()
+}
+
+/// Forgets all records, the next update builds them again from the whole history.
+/// Records set by events seen before aren't announced again.
+#[instrument(skip(pool))]
+pub async fn rebuild_records(pool: &PgPool) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SELECT last_event_id FROM record_cursor FOR UPDATE")
+        .fetch_one(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM record").execute(&mut *tx).await?;
+    sqlx::query!(
+        "UPDATE record_cursor
+         SET last_event_id = 0,
+             announced_after_event_id = (SELECT COALESCE(max(id), 0) FROM match_event)"
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
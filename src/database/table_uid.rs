@@ -1,5 +1,7 @@
 use std::fmt;
 
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use sqlx::Type;
 
 // IMPORTANT: Remember to keep this in sync with the database!
@@ -14,8 +16,9 @@ fn is_valid_uid(uid: &str) -> bool {
             .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
 }
 
-#[derive(Clone, Hash, PartialEq, Eq, Type, Debug)]
+#[derive(Clone, Hash, PartialEq, Eq, Type, Serialize, Deserialize, Debug)]
 #[sqlx(transparent)]
+#[serde(try_from = "String")]
 pub struct TableUid(String);
 
 impl TableUid {
@@ -28,11 +31,29 @@ impl TableUid {
         Ok(Self(uid))
     }
 
+    /// `prefix` followed by random characters, for matches nobody picked a name for
+    pub fn random(prefix: char) -> Self {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let mut rng = rand::rng();
+        let uid: String = std::iter::once(prefix)
+            .chain((1..UID_MAX_LENGTH).map(|_| CHARS[rng.random_range(0..CHARS.len())] as char))
+            .collect();
+        Self::parse(uid).expect("prefix has to be a valid uid character")
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
 }
 
+impl TryFrom<String> for TableUid {
+    type Error = TableUidError;
+
+    fn try_from(uid: String) -> Result<Self, Self::Error> {
+        Self::parse(uid)
+    }
+}
+
 impl fmt::Display for TableUid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.as_str())
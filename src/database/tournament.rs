@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use sqlx::PgPool;
+use tracing::instrument;
+
+use crate::models::tournament::{Tournament, TournamentMatch};
+
+use super::{DbError, parse_player_name};
+
+/// Stores a new tournament, `tournament.id` is ignored and the new one returned.
+/// `tokens` belong to the players in the same order.
+#[instrument(skip_all, fields(name = %tournament.name))]
+pub async fn create_tournament(
+    pool: &PgPool,
+    tournament: &Tournament,
+    tokens: &[String],
+) -> Result<i64, DbError> {
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query!(
+        "INSERT INTO tournament (name, format, points_to_win, matches) VALUES ($1, $2, $3, $4)
+         RETURNING id",
+        tournament.name,
+        tournament.format.to_string(),
+        tournament.points_to_win as i32,
+        serde_json::to_value(&tournament.matches)?
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    for (seed, (player, token)) in tournament.players.iter().zip(tokens).enumerate() {
+        sqlx::query!(
+            "INSERT INTO tournament_player (tournament_id, seed, player, token) VALUES ($1, $2, $3, $4)",
+            id,
+            seed as i32,
+            player.as_str(),
+            token
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+#[instrument(skip(pool, matches))]
+pub async fn save_tournament_matches(
+    pool: &PgPool,
+    id: i64,
+    matches: &[TournamentMatch],
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "UPDATE tournament SET matches = $2 WHERE id = $1",
+        id,
+        serde_json::to_value(matches)?
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(DbError::RowNotFound);
+    }
+
+    Ok(())
+}
+
+/// All tournaments with the tokens of their players, by seed
+#[instrument(skip_all)]
+pub async fn get_tournaments(pool: &PgPool) -> Result<Vec<(Tournament, Vec<String>)>, DbError> {
+    let mut entrants: HashMap<i64, Vec<_>> = HashMap::new();
+    for row in sqlx::query!(
+        "SELECT tournament_id, player, token FROM tournament_player ORDER BY tournament_id, seed"
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        entrants
+            .entry(row.tournament_id)
+            .or_default()
+            .push((parse_player_name(row.player), row.token));
+    }
+
+    sqlx::query!("SELECT id, name, format, points_to_win, matches FROM tournament ORDER BY id")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let (players, tokens) = entrants
+                .remove(&row.id)
+                .unwrap_or_default()
+                .into_iter()
+                .unzip();
+            let tournament = Tournament {
+                id: row.id,
+                name: row.name,
+                format: row
+                    .format
+                    .parse()
+                    .expect("format is constrained in the database"),
+                players,
+                points_to_win: row.points_to_win as usize,
+                matches: serde_json::from_value(row.matches)?,
+            };
+            Ok((tournament, tokens))
+        })
+        .collect()
+}
@@ -0,0 +1,298 @@
+use std::time::Duration;
+
+use jiff::Timestamp;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use crate::models::webhooks::WebhookEvent;
+
+use super::{DbError, TableUid};
+
+pub struct NewWebhook {
+    pub url: String,
+    pub secret: String,
+    /// `None` for all matches
+    pub match_uid: Option<TableUid>,
+    pub events: Vec<WebhookEvent>,
+    pub created_by: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub match_id: Option<String>,
+    pub events: Vec<WebhookEvent>,
+    pub created_by: String,
+    pub created_at: Timestamp,
+}
+
+/// Delivery claimed for an attempt
+pub struct DueDelivery {
+    pub id: i64,
+    pub event: String,
+    pub payload: Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: i64,
+    pub event: String,
+    pub payload: Value,
+    pub created_at: Timestamp,
+    pub attempts: i32,
+    /// `None` once delivered or given up
+    pub next_attempt_at: Option<Timestamp>,
+    pub delivered_at: Option<Timestamp>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+}
+
+/// How an attempt went, `retry_in` is `None` when it succeeded or was the last one
+pub struct Attempt {
+    pub delivered: bool,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub retry_in: Option<Duration>,
+}
+
+#[instrument(skip_all, fields(url = %webhook.url))]
+pub async fn create_webhook(pool: &PgPool, webhook: &NewWebhook) -> Result<Webhook, DbError> {
+    let events: Vec<String> = webhook.events.iter().map(ToString::to_string).collect();
+    let row = sqlx::query!(
+        r#"INSERT INTO webhook (url, secret, match_uid, events, created_by)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, to_jsonb(created_at) as "created_at!""#,
+        webhook.url,
+        webhook.secret,
+        webhook.match_uid.as_ref().map(TableUid::as_str),
+        &events,
+        webhook.created_by
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(Webhook {
+        id: row.id,
+        url: webhook.url.clone(),
+        match_id: webhook.match_uid.as_ref().map(ToString::to_string),
+        events: webhook.events.clone(),
+        created_by: webhook.created_by.clone(),
+        created_at: serde_json::from_value(row.created_at)?,
+    })
+}
+
+#[instrument(skip(pool))]
+pub async fn get_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, DbError> {
+    sqlx::query!(
+        r#"SELECT id, url, match_uid, events, created_by, to_jsonb(created_at) as "created_at!"
+         FROM webhook ORDER BY id"#
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(Webhook {
+            id: row.id,
+            url: row.url,
+            match_id: row.match_uid,
+            // kinds dropped from the code are left alone
+            events: row
+                .events
+                .iter()
+                .filter_map(|event| WebhookEvent::parse(event))
+                .collect(),
+            created_by: row.created_by,
+            created_at: serde_json::from_value(row.created_at)?,
+        })
+    })
+    .collect()
+}
+
+/// `RowNotFound` if there is no such webhook. Its delivery log goes with it.
+#[instrument(skip(pool))]
+pub async fn delete_webhook(pool: &PgPool, id: i64) -> Result<(), DbError> {
+    let deleted = sqlx::query!("DELETE FROM webhook WHERE id = $1", id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(DbError::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Newest first, `RowNotFound` if there is no such webhook
+#[instrument(skip(pool))]
+pub async fn get_deliveries(
+    pool: &PgPool,
+    webhook_id: i64,
+    limit: i64,
+) -> Result<Vec<Delivery>, DbError> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM webhook WHERE id = $1) as "exists!""#,
+        webhook_id
+    )
+    .fetch_one(pool)
+    .await?;
+    if !exists {
+        return Err(DbError::RowNotFound);
+    }
+
+    sqlx::query!(
+        r#"SELECT id, event, payload, to_jsonb(created_at) as "created_at!", attempts,
+            to_jsonb(next_attempt_at) as next_attempt_at, to_jsonb(delivered_at) as delivered_at,
+            last_status_code, last_error
+         FROM webhook_delivery WHERE webhook_id = $1
+         ORDER BY id DESC
+         LIMIT $2"#,
+        webhook_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(Delivery {
+            id: row.id,
+            event: row.event,
+            payload: row.payload,
+            created_at: serde_json::from_value(row.created_at)?,
+            attempts: row.attempts,
+            next_attempt_at: row
+                .next_attempt_at
+                .map(serde_json::from_value)
+                .transpose()?,
+            delivered_at: row.delivered_at.map(serde_json::from_value).transpose()?,
+            last_status_code: row.last_status_code,
+            last_error: row.last_error,
+        })
+    })
+    .collect()
+}
+
+/// Turns up to `batch` match events not seen yet into deliveries for webhooks that want them.
+/// Returns how many events were seen.
+///
+/// Events are only taken once they are a second old, so one saved a moment later but
+/// under a lower id isn't skipped for good.
+#[instrument(skip(pool))]
+pub async fn enqueue_deliveries(pool: &PgPool, batch: i64) -> Result<usize, DbError> {
+    let mut tx = pool.begin().await?;
+    // instances take turns, the cursor row is the lock
+    let cursor = sqlx::query_scalar!("SELECT last_event_id FROM webhook_cursor FOR UPDATE")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let seen = sqlx::query!(
+        r#"SELECT count(*) as "count!", max(id) as last_event_id FROM (
+             SELECT id FROM match_event
+             WHERE id > $1 AND occurred_at < now() - interval '1 second'
+             ORDER BY id
+             LIMIT $2
+         ) batch"#,
+        cursor,
+        batch
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let Some(last_event_id) = seen.last_event_id else {
+        return Ok(0);
+    };
+
+    sqlx::query!(
+        "INSERT INTO webhook_delivery (webhook_id, event, payload)
+         SELECT w.id, e.kind, jsonb_build_object(
+             'eventId', e.id,
+             'event', e.kind,
+             'matchId', m.uid,
+             'occurredAt', e.occurred_at,
+             'data', e.data)
+         FROM match_event e
+         JOIN match m ON m.game_state_id = e.game_state_id
+         JOIN webhook w ON e.kind = ANY (w.events) AND (w.match_uid IS NULL OR w.match_uid = m.uid)
+         WHERE e.id > $1 AND e.id <= $2
+         ORDER BY e.id, w.id",
+        cursor,
+        last_event_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE webhook_cursor SET last_event_id = $1",
+        last_event_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(seen.count as usize)
+}
+
+/// Takes up to `limit` deliveries that are due, oldest first. Nobody else takes them for
+/// `lease`, so one that is never finished, e.g. when the server stops, is attempted again.
+#[instrument(skip(pool))]
+pub async fn claim_due_deliveries(
+    pool: &PgPool,
+    limit: i64,
+    lease: Duration,
+) -> Result<Vec<DueDelivery>, DbError> {
+    let rows = sqlx::query!(
+        "UPDATE webhook_delivery d
+         SET next_attempt_at = now() + make_interval(secs => $2)
+         FROM webhook w
+         WHERE w.id = d.webhook_id AND d.id IN (
+             SELECT id FROM webhook_delivery
+             WHERE next_attempt_at <= now()
+             ORDER BY next_attempt_at, id
+             LIMIT $1
+             FOR UPDATE SKIP LOCKED)
+         RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret",
+        limit,
+        lease.as_secs_f64()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut due: Vec<_> = rows
+        .into_iter()
+        .map(|row| DueDelivery {
+            id: row.id,
+            event: row.event,
+            payload: row.payload,
+            attempts: row.attempts,
+            url: row.url,
+            secret: row.secret,
+        })
+        .collect();
+    due.sort_by_key(|delivery| delivery.id);
+    Ok(due)
+}
+
+#[instrument(skip(pool, attempt), fields(delivered = attempt.delivered))]
+pub async fn record_attempt(pool: &PgPool, id: i64, attempt: &Attempt) -> Result<(), DbError> {
+    sqlx::query!(
+        "UPDATE webhook_delivery
+         SET attempts = attempts + 1,
+             delivered_at = CASE WHEN $2 THEN now() END,
+             next_attempt_at = now() + make_interval(secs => $3),
+             last_status_code = $4,
+             last_error = $5
+         WHERE id = $1",
+        id,
+        attempt.delivered,
+        // NULL interval leaves the next attempt NULL
+        attempt.retry_in.map(|retry_in| retry_in.as_secs_f64()),
+        attempt.status_code.map(i32::from),
+        attempt.error
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
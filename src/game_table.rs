@@ -1,36 +1,150 @@
-use std::time::Duration;
-
 use axum::{
     Extension, Json, Router,
-    extract::{Path, Request, State},
-    http::StatusCode,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderName, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
 };
-use log::error;
-use tokio::time::sleep;
+use serde::{Deserialize, Serialize};
+use tracing::{Span, debug, error};
 
 use crate::{
-    BALL_AIR_TIME_SECONDS, clock,
-    database::{TableUid, create_new_match},
+    bot::{attach_bot, detach_bot},
+    cluster::{Located, forward, locate},
+    corrections::{call_let, pause, resume, undo},
+    database::{DbError, TableUid, archive_match, create_new_match},
     models::{
         application::AppState,
-        game::{Side, TableState},
+        game::{GameMode, GameState, HitOutcome, Partner, Side, TableState, TableStopped},
+        player::{Claim, PlayerName},
+        settings::MatchSettings,
     },
+    queue::{join_queue, leave_queue, view_queue},
+    rate_limit::{limit_hits, too_many_requests},
+    stats::match_stats,
 };
 
+/// Required for hits on a claimed side
+pub const PLAYER_TOKEN_HEADER: HeaderName = HeaderName::from_static("x-player-token");
+
+/// Table was dropped while the request was on its way to it
+impl From<TableStopped> for (StatusCode, String) {
+    fn from(_: TableStopped) -> Self {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Match is no longer served here".to_string(),
+        )
+    }
+}
+
 pub fn match_routes(state: AppState) -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_state))
+    let hit_routes = Router::new()
         .route("/ping", get(ping))
         .route("/pong", get(pong))
+        .route_layer(middleware::from_fn_with_state(state.clone(), limit_hits));
+
+    Router::new()
+        .route("/", get(get_state))
+        .route("/stats", get(match_stats))
+        .route("/claims/{side}", post(claim_side))
+        .route("/undo", post(undo))
+        .route("/let", post(call_let))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/bots/{side}", post(attach_bot).delete(detach_bot))
+        .route(
+            "/queue",
+            get(view_queue).post(join_queue).delete(leave_queue),
+        )
+        .merge(hit_routes)
         .route_layer(middleware::from_fn_with_state(state, get_or_create_match))
 }
 
+/// Random uids are rarely taken already, but it happens
+const RANDOM_UID_ATTEMPTS: usize = 5;
+
+/// New classic match with its own settings and both sides claimed, under a random uid
+/// starting with `prefix`. Fails if it can't be saved or its actor stops before seating.
+pub(crate) async fn open_table(
+    state: &AppState,
+    prefix: char,
+    settings: MatchSettings,
+    ping: Claim,
+    pong: Claim,
+) -> Result<(TableUid, TableState), String> {
+    for _ in 0..RANDOM_UID_ATTEMPTS {
+        let uid = TableUid::random(prefix);
+        let table_state = match create_new_match(
+            &state.db_pool,
+            &uid,
+            GameState::new(GameMode::Classic),
+            settings.clone(),
+            state.cluster.as_ref().map(|cluster| &cluster.lease),
+        )
+        .await
+        {
+            Ok(table_state) => table_state,
+            Err(DbError::AlreadyExists) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        // unlike the config defaults, these have to survive a restart
+        table_state
+            .set_settings(settings)
+            .await
+            .map_err(|e| e.to_string())?;
+        for (side, Claim { player, token }) in [(Side::Ping, ping), (Side::Pong, pong)] {
+            table_state
+                .claim_with_token(side, player, token)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        state.game_tables.insert(uid.clone(), table_state.clone());
+        return Ok((uid, table_state));
+    }
+    Err(DbError::AlreadyExists.to_string())
+}
+
+/// Archives the match and stops serving it, bots and the rally in the air go with it
+pub(crate) async fn archive_table(
+    state: &AppState,
+    uid: &TableUid,
+    table_state: &TableState,
+) -> Result<(), DbError> {
+    match archive_match(&state.db_pool, uid).await {
+        // archived by someone else in the meantime - still has to go from memory
+        Ok(()) | Err(DbError::RowNotFound) => {}
+        Err(e) => return Err(e),
+    }
+    // a stopped table has no bots or rally left
+    let _ = table_state.remove_bots().await;
+    let _ = table_state.cancel_rally().await;
+    state.game_tables.remove(uid);
+    Ok(())
+}
+
+/// Nested routes can have more path params than just the match id
+#[derive(Deserialize)]
+pub(crate) struct MatchPath {
+    pub(crate) id: String,
+}
+
+#[derive(Deserialize)]
+struct SidePath {
+    side: Side,
+}
+
+/// Only matters when the match gets created, existing matches have to match it
+#[derive(Deserialize)]
+struct ModeQuery {
+    mode: Option<GameMode>,
+}
+
 async fn get_or_create_match(
     State(state): State<AppState>,
-    Path(uid): Path<String>,
+    Path(MatchPath { id: uid }): Path<MatchPath>,
+    Query(ModeQuery { mode }): Query<ModeQuery>,
     mut request: Request,
     next: Next,
 ) -> Response {
@@ -40,39 +154,83 @@ async fn get_or_create_match(
             return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
         }
     };
+    Span::current().record("match_id", uid.as_str());
 
-    let table_state = state
-        .game_tables
-        .read()
-        .expect("game_tables read lock was poisoned")
-        .get(&uid)
-        .cloned();
+    let located = match locate(&state, &uid).await {
+        Ok(located) => located,
+        Err(e) => {
+            error!(match_id = %uid, error = %e, "Failed to find match");
+            return (StatusCode::INTERNAL_SERVER_ERROR).into_response();
+        }
+    };
 
-    let table_state = match table_state {
-        Some(table_state) => table_state,
-        None => {
+    let table_state = match located {
+        Located::Local(table_state) => table_state,
+        Located::Remote(owner) => return forward(&state, &owner, request).await,
+        Located::Archived => {
+            return (
+                StatusCode::CONFLICT,
+                format!("Match {uid} is not available"),
+            )
+                .into_response();
+        }
+        Located::Missing => {
             // TODO: create new match IF THE CONFIG IS SET TO ALLOW IT.
-            // And yeah, btw add config.
             // config should also have `DEBUG` which would be used to add
             // info about having to allow that in config to the response.
-            match create_new_match(&state.db_pool, &uid).await {
-                Ok(table_state) => {
-                    state
-                        .game_tables
-                        .write()
-                        .expect("game_tables write lock was poisoned")
-                        .insert(uid, table_state.clone());
-
-                    table_state
+            if let Err(retry_after) = state.rate_limits.check_match_creation(&request) {
+                debug!(match_id = %uid, "Match creation throttled");
+                return too_many_requests(retry_after);
+            }
+            // concurrent first requests get the table the first one creates
+            let created = state
+                .game_tables
+                .get_or_try_insert_with(&uid, || {
+                    create_new_match(
+                        &state.db_pool,
+                        &uid,
+                        GameState::new(mode.unwrap_or_default()),
+                        state.config.match_defaults.clone(),
+                        state.cluster.as_ref().map(|cluster| &cluster.lease),
+                    )
+                })
+                .await;
+            match created {
+                Ok(table_state) => table_state,
+                Err(DbError::AlreadyExists) => {
+                    // not loaded, but in the database - archived, or created by someone else
+                    match locate(&state, &uid).await {
+                        Ok(Located::Local(table_state)) => table_state,
+                        Ok(Located::Remote(owner)) => {
+                            return forward(&state, &owner, request).await;
+                        }
+                        _ => {
+                            return (
+                                StatusCode::CONFLICT,
+                                format!("Match {uid} is not available"),
+                            )
+                                .into_response();
+                        }
+                    }
                 }
                 Err(e) => {
-                    error!("Failed to create new match with id {uid}: {e}");
+                    error!(match_id = %uid, error = %e, "Failed to create new match");
                     return (StatusCode::INTERNAL_SERVER_ERROR).into_response();
                 }
             }
         }
     };
+    if let Some(mode) = mode
+        && mode != table_state.mode()
+    {
+        return (
+            StatusCode::CONFLICT,
+            format!("Match {uid} is a {} match", table_state.mode()),
+        )
+            .into_response();
+    }
     request.extensions_mut().insert(table_state.clone());
+    request.extensions_mut().insert(uid);
 
     Response::from(next.run(request).await)
 }
@@ -83,52 +241,92 @@ async fn get_state(
     (StatusCode::OK, Json(table_state))
 }
 
-async fn try_hit(side: Side, state: TableState) -> bool {
-    let did_hit = {
-        let mut rally_state = state
-            .rally_state
-            .write()
-            .expect("rally_state write lock was poisoned");
-
-        if let Some(hit_timeout_task) = rally_state.hit_timeout_task.take() {
-            hit_timeout_task.abort();
-        }
-
-        if side == rally_state.side {
-            rally_state.side = side.flip();
-            rally_state.hit_count += 1;
-            rally_state.hit_timeout =
-                Some(clock::now() + Duration::from_secs(BALL_AIR_TIME_SECONDS));
-            rally_state.first_hit_at.get_or_insert_with(clock::now);
-
-            let state_clone = state.clone();
-            rally_state.hit_timeout_task = Some(tokio::spawn(async move {
-                sleep(Duration::from_secs(BALL_AIR_TIME_SECONDS)).await;
-                state_clone.lose_point(side.flip()).await;
-            }));
-
-            true
-        } else {
-            false
+async fn get_hit_response(
+    side: Side,
+    state: TableState,
+    headers: HeaderMap,
+) -> (StatusCode, String) {
+    let token = headers
+        .get(PLAYER_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let hitter = match state.check_token(side, token) {
+        Ok(hitter) => hitter,
+        Err(owner) => {
+            return (
+                StatusCode::FORBIDDEN,
+                format!("{side} side is claimed by {owner}"),
+            );
         }
     };
-    if !did_hit {
-        state.lose_point(side).await;
-    };
+    if state.has_bot(side) {
+        return (
+            StatusCode::FORBIDDEN,
+            format!("{side} side is played by a bot"),
+        );
+    }
+    if state.is_wall(side) {
+        return (StatusCode::FORBIDDEN, format!("{side} side is a wall"));
+    }
 
-    did_hit
+    match state.hit(side, hitter).await {
+        Ok(HitOutcome::Returned(next)) => (StatusCode::OK, next.to_string()),
+        Ok(HitOutcome::Missed) => (StatusCode::CONFLICT, "MISS".to_string()),
+        Ok(HitOutcome::Paused) => (StatusCode::LOCKED, "Match is paused".to_string()),
+        Ok(HitOutcome::TooEarly) => (StatusCode::TOO_EARLY, "Too early".to_string()),
+        Ok(HitOutcome::Over) => (StatusCode::CONFLICT, "Match is over".to_string()),
+        Err(stopped) => stopped.into(),
+    }
 }
 
-async fn get_hit_response(side: Side, state: TableState) -> (StatusCode, String) {
-    match try_hit(side, state).await {
-        true => (StatusCode::OK, side.flip().to_string()),
-        false => (StatusCode::CONFLICT, "MISS".to_string()),
-    }
+async fn ping(
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> (StatusCode, String) {
+    get_hit_response(Side::Ping, table_state, headers).await
+}
+async fn pong(
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> (StatusCode, String) {
+    get_hit_response(Side::Pong, table_state, headers).await
 }
 
-async fn ping(Extension(table_state): Extension<TableState>) -> (StatusCode, String) {
-    get_hit_response(Side::Ping, table_state).await
+#[derive(Deserialize)]
+struct ClaimRequest {
+    player: PlayerName,
 }
-async fn pong(Extension(table_state): Extension<TableState>) -> (StatusCode, String) {
-    get_hit_response(Side::Pong, table_state).await
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClaimResponse {
+    side: Side,
+    /// always first, unless it's a doubles match
+    partner: Partner,
+    player: PlayerName,
+    token: String,
+}
+
+async fn claim_side(
+    Extension(table_state): Extension<TableState>,
+    Path(SidePath { side }): Path<SidePath>,
+    Json(ClaimRequest { player }): Json<ClaimRequest>,
+) -> Result<(StatusCode, Json<ClaimResponse>), (StatusCode, String)> {
+    if table_state.is_wall(side) {
+        return Err((StatusCode::CONFLICT, format!("{side} side is a wall")));
+    }
+    let Some((partner, token)) = table_state.claim(side, player.clone()).await? else {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{side} side is already claimed"),
+        ));
+    };
+    Ok((
+        StatusCode::CREATED,
+        Json(ClaimResponse {
+            side,
+            partner,
+            player,
+            token,
+        }),
+    ))
 }
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use serde::Serialize;
+use tokio::time::timeout;
+
+use crate::{
+    database::{DbError, check_connection, count_pending_migrations},
+    models::application::AppState,
+};
+
+/// Readiness probes should answer fast, even if the database is hanging
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn health_routes() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+}
+
+/// Liveness - if we can answer at all, the process is alive
+async fn healthz() -> (StatusCode, &'static str) {
+    (StatusCode::OK, "OK")
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckResult {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl CheckResult {
+    fn passed() -> Self {
+        Self {
+            ok: true,
+            error: None,
+        }
+    }
+
+    fn failed(error: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadinessReport {
+    ready: bool,
+    shutting_down: bool,
+    database: CheckResult,
+    migrations: CheckResult,
+    game_tables: CheckResult,
+}
+
+async fn with_timeout<T>(check: impl Future<Output = Result<T, DbError>>) -> Result<T, String> {
+    match timeout(DB_CHECK_TIMEOUT, check).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("Timed out after {}s", DB_CHECK_TIMEOUT.as_secs())),
+    }
+}
+
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<ReadinessReport>) {
+    let database = match with_timeout(check_connection(&state.db_pool)).await {
+        Ok(()) => CheckResult::passed(),
+        Err(e) => CheckResult::failed(e),
+    };
+
+    // no point in asking about migrations if there is no connection
+    let migrations = if database.ok {
+        match with_timeout(count_pending_migrations(&state.db_pool)).await {
+            Ok(0) => CheckResult::passed(),
+            Ok(pending) => CheckResult::failed(format!("{pending} migration(s) not applied")),
+            Err(e) => CheckResult::failed(e),
+        }
+    } else {
+        CheckResult::failed("Database unreachable")
+    };
+
+    let game_tables = if state.readiness.tables_loaded() {
+        CheckResult::passed()
+    } else {
+        CheckResult::failed("Game tables not loaded yet")
+    };
+
+    let shutting_down = state.readiness.shutting_down();
+    let ready = !shutting_down && database.ok && migrations.ok && game_tables.ok;
+
+    (
+        if ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        },
+        Json(ReadinessReport {
+            ready,
+            shutting_down,
+            database,
+            migrations,
+            game_tables,
+        }),
+    )
+}
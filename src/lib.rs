@@ -1,57 +1,178 @@
-use std::{
-    process::exit,
-    sync::{Arc, RwLock},
-};
+use std::{process::exit, sync::Arc};
 
-use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
-use log::error;
+use axum::{
+    Json, Router,
+    extract::{MatchedPath, Request, State},
+    http::{HeaderName, StatusCode},
+    middleware,
+    routing::get,
+};
 use serde::Serialize;
 use sqlx::PgPool;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::{
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{Span, error, field::Empty, info_span};
 
+mod admin;
+mod audit;
+pub mod auth;
+mod bot;
 pub mod clock;
+mod cluster;
+pub mod config;
+mod corrections;
 pub mod database;
 mod game_table;
+mod health;
+mod lobby;
+pub mod metrics;
 pub mod models;
+mod players;
+mod queue;
+pub mod rate_limit;
+mod records;
+mod stats;
+mod tournament;
+mod webhooks;
 
 #[cfg(test)]
 pub mod tests;
 
-use crate::{database::get_game_tables, game_table::match_routes, models::application::AppState};
+use crate::{
+    admin::admin_routes,
+    cluster::{Cluster, follow_leases},
+    config::{AllowList, Config, CorsPolicy},
+    database::{TableUid, get_game_tables, get_open_match_uids},
+    game_table::{PLAYER_TOKEN_HEADER, match_routes},
+    health::health_routes,
+    lobby::{lobby_routes, resume_rated_matches},
+    metrics::{metrics_routes, track_http_requests},
+    models::application::{AppState, Readiness},
+    players::player_routes,
+    rate_limit::RateLimits,
+    records::{follow_records, list_records},
+    tournament::{resume_tournaments, tournament_routes},
+    webhooks::follow_webhooks,
+};
 
 pub const BALL_AIR_TIME_SECONDS: u64 = 30;
 
-async fn init_state(pool: &PgPool) -> Result<AppState, database::DbError> {
-    let game_tables = get_game_tables(pool).await?;
+/// Taken from the incoming request if present, generated otherwise. Always echoed back.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
 
-    Ok(AppState {
-        game_tables: Arc::new(RwLock::new(game_tables)),
+async fn init_state(
+    pool: &PgPool,
+    config: Config,
+    readiness: Readiness,
+) -> Result<AppState, database::DbError> {
+    let (cluster, lost_leases) = Cluster::new(&config.cluster).unzip();
+    let lease = cluster.as_ref().map(|cluster| &cluster.lease);
+    let game_tables = get_game_tables(pool, &config.match_defaults, lease).await?;
+
+    let state = AppState {
+        game_tables: Arc::new(game_tables),
         db_pool: pool.clone(),
-    })
+        readiness,
+        rate_limits: Arc::new(RateLimits::new(&config.rate_limits)),
+        config: Arc::new(config),
+        tournaments: Default::default(),
+        lobby: Default::default(),
+        cluster,
+    };
+    // before anything else can go wrong, matches taken on have to be kept
+    follow_leases(&state, lost_leases).await?;
+    // tournaments open their tables, so they have to follow the loaded ones
+    resume_tournaments(&state).await?;
+    resume_rated_matches(&state).await?;
+    state.readiness.mark_tables_loaded();
+    follow_records(state.db_pool.clone(), state.config.records.refresh);
+    follow_webhooks(state.db_pool.clone(), state.config.webhooks.clone());
+
+    Ok(state)
 }
 
-pub async fn create_app(pool: PgPool) -> Router {
-    match init_state(&pool).await {
+/// `readiness` is shared with the caller so it can report shutdown before the server stops
+pub async fn create_app(pool: PgPool, config: Config, readiness: Readiness) -> Router {
+    match init_state(&pool, config, readiness).await {
         Ok(state) => create_app_from_state(state),
         Err(e) => {
-            error!("Failed to initialize app state from database: {e}");
+            error!(error = %e, "Failed to initialize app state from database");
             exit(1)
         }
     }
 }
 
+fn cors_layer(policy: &CorsPolicy) -> CorsLayer {
+    let layer = CorsLayer::new()
+        .allow_origin(match &policy.origins {
+            AllowList::Any => AllowOrigin::any(),
+            AllowList::Only(origins) => AllowOrigin::list(origins.iter().cloned()),
+        })
+        .allow_methods(match &policy.methods {
+            AllowList::Any => AllowMethods::any(),
+            AllowList::Only(methods) => AllowMethods::list(methods.iter().cloned()),
+        })
+        .allow_headers(match &policy.headers {
+            AllowList::Any => AllowHeaders::any(),
+            AllowList::Only(headers) => AllowHeaders::list(headers.iter().cloned()),
+        })
+        // tokens of bots come back in a header
+        .expose_headers([REQUEST_ID_HEADER, PLAYER_TOKEN_HEADER]);
+
+    match policy.max_age {
+        Some(max_age) => layer.max_age(max_age),
+        None => layer,
+    }
+}
+
 pub fn create_app_from_state(state: AppState) -> Router {
-    // TODO: Consider restricting CORS in the future
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let public_cors = cors_layer(&state.config.cors.public);
 
     Router::new()
         .route("/matches", get(open_matches))
+        .route("/records", get(list_records))
         .nest("/matches/{id}", match_routes(state.clone()))
+        .nest("/tournaments", tournament_routes(state.clone()))
+        .nest("/lobby", lobby_routes(state.clone()))
+        .nest("/players", player_routes())
+        .merge(health_routes())
+        .merge(metrics_routes())
+        // layers only wrap routes added before them - routes with other CORS policy go below
+        .layer(public_cors)
+        .nest(
+            "/admin",
+            admin_routes(state.clone()).layer(cors_layer(&state.config.cors.admin)),
+        )
         .with_state(state)
-        .layer(cors)
+        .layer(middleware::from_fn(track_http_requests))
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
+}
+
+/// `match_id` is filled in once the match is resolved
+fn request_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| request.uri().path());
+
+    info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        route,
+        match_id = Empty,
+    )
 }
 
 #[derive(Serialize)]
@@ -60,16 +181,23 @@ struct MatchList {
     open_matches: Vec<String>,
 }
 
-async fn open_matches(State(state): State<AppState>) -> (StatusCode, Json<MatchList>) {
-    let game_tables = state
-        .game_tables
-        .read()
-        .expect("game_tables read lock was poisoned");
-
-    (
-        StatusCode::OK,
-        Json(MatchList {
-            open_matches: game_tables.keys().map(|uid| uid.to_string()).collect(),
-        }),
-    )
+async fn open_matches(
+    State(state): State<AppState>,
+) -> Result<Json<MatchList>, (StatusCode, String)> {
+    // other instances serve matches too, only the database knows them all
+    let uids: Vec<TableUid> = if state.cluster.is_some() {
+        get_open_match_uids(&state.db_pool).await.map_err(|e| {
+            error!(error = %e, "Failed to list open matches");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list open matches".to_string(),
+            )
+        })?
+    } else {
+        state.game_tables.uids()
+    };
+
+    Ok(Json(MatchList {
+        open_matches: uids.iter().map(ToString::to_string).collect(),
+    }))
 }
@@ -0,0 +1,515 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::timeout};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    auth::constant_time_eq,
+    clock,
+    database::{
+        DbError, TableUid, create_rated_match, get_rating, get_unrated_matches, rate_match,
+        register_rated_player, unrate_match,
+    },
+    game_table::{PLAYER_TOKEN_HEADER, open_table},
+    models::{
+        application::AppState,
+        game::Side,
+        lobby::{DEFAULT_RATING, Lobby, LobbyEntry, Preferences, TicketStatus},
+        player::{Claim, PlayerName},
+        settings::MatchSettings,
+    },
+    rate_limit::{limit_match_creations, limit_open_waits},
+};
+
+/// Waiting players and what became of their tickets
+#[derive(Clone, Default)]
+pub struct LobbyHandle {
+    waiting: Arc<Mutex<Lobby>>,
+    tickets: Arc<RwLock<HashMap<String, watch::Sender<TicketStatus>>>>,
+    /// rated matches until their result changed the ratings
+    rated: Arc<RwLock<HashSet<TableUid>>>,
+}
+
+impl LobbyHandle {
+    /// Rated matches can't hand the table over to challengers, the result would get lost
+    pub fn is_rated_pending(&self, uid: &TableUid) -> bool {
+        self.rated
+            .read()
+            .expect("rated read lock was poisoned")
+            .contains(uid)
+    }
+
+    fn status(&self, ticket: &str) -> Option<watch::Receiver<TicketStatus>> {
+        self.tickets
+            .read()
+            .expect("tickets read lock was poisoned")
+            .get(ticket)
+            .map(watch::Sender::subscribe)
+    }
+
+    fn set_status(&self, ticket: &str, status: TicketStatus) {
+        if let Some(sender) = self
+            .tickets
+            .read()
+            .expect("tickets read lock was poisoned")
+            .get(ticket)
+        {
+            sender.send_replace(status);
+        }
+    }
+}
+
+pub fn lobby_routes(state: AppState) -> Router<AppState> {
+    let joining =
+        Router::new()
+            .route("/join", post(join))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                limit_match_creations,
+            ));
+
+    Router::new()
+        .route(
+            "/tickets/{ticket}",
+            get(ticket_status)
+                .layer(middleware::from_fn_with_state(state, limit_open_waits))
+                .delete(leave),
+        )
+        .route("/ratings/{player}", get(rating))
+        .merge(joining)
+}
+
+/// Keeps following rated matches whose result hasn't been rated yet
+#[instrument(skip_all)]
+pub async fn resume_rated_matches(state: &AppState) -> Result<(), DbError> {
+    for (uid, ping, pong) in get_unrated_matches(&state.db_pool).await? {
+        let wins = state
+            .game_tables
+            .get(&uid)
+            .map(|table_state| table_state.wins());
+        match wins {
+            Some(wins) => watch_rated_match(state.clone(), uid, [ping, pong], wins),
+            None => warn!(match_id = %uid, "Rated match is archived"),
+        }
+    }
+    Ok(())
+}
+
+/// Rates the match once it's won, and takes the rating back if that win is undone.
+/// Ends on its own once the table is gone.
+fn watch_rated_match(
+    state: AppState,
+    uid: TableUid,
+    [ping, pong]: [PlayerName; 2],
+    mut wins: watch::Receiver<Vec<Option<Side>>>,
+) {
+    state
+        .lobby
+        .rated
+        .write()
+        .expect("rated write lock was poisoned")
+        .insert(uid.clone());
+    tokio::spawn(async move {
+        // the win that was rated, by its number
+        let mut rated: Option<usize> = None;
+        loop {
+            let Ok(standing) = wins
+                .wait_for(|wins| match rated {
+                    None => wins.iter().any(Option::is_some),
+                    Some(rated) => wins[rated].is_none(),
+                })
+                .await
+                .map(|wins| {
+                    // the first win still standing
+                    wins.iter()
+                        .enumerate()
+                        .find_map(|(won, winner)| winner.map(|winner| (won, winner)))
+                })
+            else {
+                return;
+            };
+
+            if rated.take().is_some() {
+                match unrate_match(&state.db_pool, &uid).await {
+                    Ok(true) => info!(match_id = %uid, "Win undone, rating taken back"),
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!(match_id = %uid, error = %e, "Error while taking back rating")
+                    }
+                }
+                state
+                    .lobby
+                    .rated
+                    .write()
+                    .expect("rated write lock was poisoned")
+                    .insert(uid.clone());
+                // the next win counts, it may be there already
+                continue;
+            }
+            let Some((won, winner)) = standing else {
+                continue;
+            };
+            let (winner, loser) = match winner {
+                Side::Ping => (&ping, &pong),
+                Side::Pong => (&pong, &ping),
+            };
+            match rate_match(&state.db_pool, &uid, winner, loser).await {
+                Ok(Some((winner_rating, loser_rating))) => {
+                    info!(match_id = %uid, %winner, winner_rating, %loser, loser_rating, "Match rated");
+                }
+                Ok(None) => {}
+                // stays unrated until the next restart tries again
+                Err(e) => error!(match_id = %uid, error = %e, "Error while rating match"),
+            }
+            rated = Some(won);
+            state
+                .lobby
+                .rated
+                .write()
+                .expect("rated write lock was poisoned")
+                .remove(&uid);
+        }
+    });
+}
+
+/// Final statuses stay around for as long as a ticket could have waited
+fn forget_later(state: &AppState, tickets: Vec<String>) {
+    let lobby = state.lobby.clone();
+    let ttl = state.config.lobby.ticket_ttl;
+    tokio::spawn(async move {
+        tokio::time::sleep(ttl).await;
+        let mut known = lobby
+            .tickets
+            .write()
+            .expect("tickets write lock was poisoned");
+        for ticket in tickets {
+            known.remove(&ticket);
+        }
+    });
+}
+
+fn expire_later(state: &AppState, ticket: String, after: Duration) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(after).await;
+        let expired = state
+            .lobby
+            .waiting
+            .lock()
+            .expect("lobby lock was poisoned")
+            .remove(&ticket);
+        if let Some(entry) = expired {
+            info!(player = %entry.player, "Lobby ticket expired");
+            state.lobby.set_status(&ticket, TicketStatus::Expired);
+            forget_later(&state, vec![ticket]);
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct JoinRequest {
+    player: PlayerName,
+    #[serde(flatten)]
+    preferences: Preferences,
+}
+
+#[derive(Serialize)]
+struct JoinResponse {
+    ticket: String,
+    /// needed to hit once matched
+    token: String,
+    #[serde(flatten)]
+    status: TicketStatus,
+}
+
+async fn join(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(JoinRequest {
+        player,
+        preferences,
+    }): Json<JoinRequest>,
+) -> Result<(StatusCode, Json<JoinResponse>), (StatusCode, String)> {
+    preferences
+        .validate()
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+
+    // rated names are held by the token they played their first rated match with
+    let token = headers
+        .get(PLAYER_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|token| !token.is_empty())
+        .map_or_else(|| Uuid::new_v4().simple().to_string(), str::to_string);
+    if preferences.rated {
+        let registered = register_rated_player(&state.db_pool, &player, &token)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Error while registering rated player in database");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to register rated player".to_string(),
+                )
+            })?;
+        if !constant_time_eq(registered.as_bytes(), token.as_bytes()) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("{player} plays rated matches with another token"),
+            ));
+        }
+    }
+
+    let rating = if preferences.needs_rating() {
+        get_rating(&state.db_pool, &player)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Error while getting rating from database");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to get rating".to_string(),
+                )
+            })?
+            .rating
+    } else {
+        DEFAULT_RATING
+    };
+    let entry = LobbyEntry {
+        ticket: Uuid::new_v4().simple().to_string(),
+        player,
+        token,
+        preferences,
+        rating,
+        joined_at: clock::now(),
+    };
+
+    let opponent = {
+        let mut lobby = state.lobby.waiting.lock().expect("lobby lock was poisoned");
+        if lobby.is_waiting(&entry.player) {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("{} is already waiting in the lobby", entry.player),
+            ));
+        }
+        let opponent = lobby.take_opponent(&entry);
+        if opponent.is_none() {
+            // registered before anyone else can be paired with the entry
+            state
+                .lobby
+                .tickets
+                .write()
+                .expect("tickets write lock was poisoned")
+                .insert(
+                    entry.ticket.clone(),
+                    watch::Sender::new(TicketStatus::Waiting),
+                );
+            lobby.push(entry.clone());
+        }
+        opponent
+    };
+
+    let Some(opponent) = opponent else {
+        info!(player = %entry.player, "Player is waiting in the lobby");
+        expire_later(&state, entry.ticket.clone(), state.config.lobby.ticket_ttl);
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(JoinResponse {
+                ticket: entry.ticket,
+                token: entry.token,
+                status: TicketStatus::Waiting,
+            }),
+        ));
+    };
+
+    let status = match pair(&state, &opponent, &entry).await {
+        Ok(status) => status,
+        Err(()) => {
+            // the opponent keeps their place, their ticket still expires on its first schedule
+            let waited = clock::now().duration_since(opponent.joined_at);
+            let left = state
+                .config
+                .lobby
+                .ticket_ttl
+                .saturating_sub(waited.try_into().unwrap_or_default());
+            if left.is_zero() {
+                info!(player = %opponent.player, "Lobby ticket expired");
+                state
+                    .lobby
+                    .set_status(&opponent.ticket, TicketStatus::Expired);
+                forget_later(&state, vec![opponent.ticket]);
+            } else {
+                state
+                    .lobby
+                    .waiting
+                    .lock()
+                    .expect("lobby lock was poisoned")
+                    .push_front(opponent);
+            }
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to open match".to_string(),
+            ));
+        }
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(JoinResponse {
+            ticket: entry.ticket,
+            token: entry.token,
+            status,
+        }),
+    ))
+}
+
+/// Seats the longer waiting player on ping. Returns the status of the newcomer.
+async fn pair(
+    state: &AppState,
+    waiting: &LobbyEntry,
+    newcomer: &LobbyEntry,
+) -> Result<TicketStatus, ()> {
+    let preferences = &newcomer.preferences;
+    let settings = MatchSettings {
+        points_to_win: Some(preferences.points_to_win),
+        air_time_seconds: preferences
+            .air_time_seconds
+            .or(state.config.match_defaults.air_time_seconds),
+        ..state.config.match_defaults.clone()
+    };
+    let [ping, pong] = [waiting, newcomer].map(|entry| Claim {
+        player: entry.player.clone(),
+        token: entry.token.clone(),
+    });
+
+    let (uid, table_state) = open_table(state, 'l', settings, ping, pong)
+        .await
+        .map_err(|e| error!(error = %e, "Failed to open lobby match"))?;
+    if preferences.rated {
+        if let Err(e) =
+            create_rated_match(&state.db_pool, &uid, &waiting.player, &newcomer.player).await
+        {
+            // the match is there already, it just won't count
+            error!(match_id = %uid, error = %e, "Error while saving rated match in database");
+        } else {
+            watch_rated_match(
+                state.clone(),
+                uid.clone(),
+                [waiting.player.clone(), newcomer.player.clone()],
+                table_state.wins(),
+            );
+        }
+    }
+    info!(match_id = %uid, ping = %waiting.player, pong = %newcomer.player, "Lobby match opened");
+
+    state.lobby.set_status(
+        &waiting.ticket,
+        TicketStatus::Matched {
+            match_id: uid.clone(),
+            side: Side::Ping,
+            opponent: newcomer.player.clone(),
+        },
+    );
+    let status = TicketStatus::Matched {
+        match_id: uid,
+        side: Side::Pong,
+        opponent: waiting.player.clone(),
+    };
+    state
+        .lobby
+        .tickets
+        .write()
+        .expect("tickets write lock was poisoned")
+        .insert(newcomer.ticket.clone(), watch::Sender::new(status.clone()));
+    forget_later(state, vec![waiting.ticket.clone(), newcomer.ticket.clone()]);
+    Ok(status)
+}
+
+#[derive(Deserialize)]
+struct WaitQuery {
+    /// seconds to hold the request while the ticket is waiting
+    #[serde(default)]
+    wait: u64,
+}
+
+async fn ticket_status(
+    State(state): State<AppState>,
+    Path(ticket): Path<String>,
+    Query(WaitQuery { wait }): Query<WaitQuery>,
+) -> Result<Json<TicketStatus>, (StatusCode, String)> {
+    let mut status = state
+        .lobby
+        .status(&ticket)
+        .ok_or((StatusCode::NOT_FOUND, "Ticket not found".to_string()))?;
+
+    let wait = Duration::from_secs(wait).min(state.config.lobby.max_wait);
+    // still waiting when the time is up is an answer too
+    let _ = timeout(
+        wait,
+        status.wait_for(|status| *status != TicketStatus::Waiting),
+    )
+    .await;
+    let current = status.borrow().clone();
+    Ok(Json(current))
+}
+
+async fn leave(
+    State(state): State<AppState>,
+    Path(ticket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let left = state
+        .lobby
+        .waiting
+        .lock()
+        .expect("lobby lock was poisoned")
+        .remove(&ticket);
+    match left {
+        Some(entry) => {
+            info!(player = %entry.player, "Player left the lobby");
+            state.lobby.set_status(&ticket, TicketStatus::Left);
+            forget_later(&state, vec![ticket]);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None if state.lobby.status(&ticket).is_some() => Err((
+            StatusCode::CONFLICT,
+            "Ticket is no longer waiting".to_string(),
+        )),
+        None => Err((StatusCode::NOT_FOUND, "Ticket not found".to_string())),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayerRating {
+    player: PlayerName,
+    rating: i32,
+    rated_matches: i32,
+}
+
+async fn rating(
+    State(state): State<AppState>,
+    Path(player): Path<PlayerName>,
+) -> Result<Json<PlayerRating>, (StatusCode, String)> {
+    let rating = get_rating(&state.db_pool, &player).await.map_err(|e| {
+        error!(error = %e, "Error while getting rating from database");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to get rating".to_string(),
+        )
+    })?;
+    Ok(Json(PlayerRating {
+        player,
+        rating: rating.rating,
+        rated_matches: rating.rated_matches,
+    }))
+}
@@ -1,11 +1,17 @@
 use std::env;
+use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
 use std::process::exit;
+use std::time::Duration;
 
-use log::{error, info};
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
 
 use tokio::signal;
 
+use ping_pong_api::config::Config;
 use ping_pong_api::create_app;
+use ping_pong_api::models::application::Readiness;
 
 use ping_pong_api::database::init_db;
 
@@ -15,33 +21,69 @@ use api_docs::create_api_docs;
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    init_tracing();
     let server_port: u16 = env::var("SERVER_PORT")
         .unwrap_or_else(|_| 3000.to_string())
         .parse()
         .expect("SERVER_PORT must be a valid port number");
+    let shutdown_grace_period: u64 = env::var("SHUTDOWN_GRACE_SECONDS")
+        .unwrap_or_else(|_| 0.to_string())
+        .parse()
+        .expect("SHUTDOWN_GRACE_SECONDS must be a number of seconds");
     let pool = init_db().await;
 
     match pool {
         Ok(p) => {
-            let app = create_app(p).await;
+            let readiness = Readiness::default();
+            let app = create_app(p, Config::from_env(), readiness.clone()).await;
             let api_docs = create_api_docs();
             let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{server_port}"))
                 .await
                 .unwrap();
-            axum::serve(listener, app.merge(api_docs))
-                .with_graceful_shutdown(shutdown_signal())
-                .await
-                .unwrap();
+            info!(port = server_port, "Listening");
+            // connect info is needed to tell clients apart for rate limiting
+            axum::serve(
+                listener,
+                app.merge(api_docs)
+                    .into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal(
+                readiness,
+                Duration::from_secs(shutdown_grace_period),
+            ))
+            .await
+            .unwrap();
         }
         Err(e) => {
-            error!("Database initialization failed with error: {e}");
+            error!(error = %e, "Database initialization failed");
             exit(1);
         }
     }
 }
 
-async fn shutdown_signal() {
+/// RUST_LOG controls verbosity, LOG_FORMAT=json switches to one JSON object per line
+fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // logs go to stderr, like they did with env_logger
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        // reporting a failed write goes to stderr too, and panics if that is what's broken
+        .log_internal_errors(false)
+        .with_ansi(io::stderr().is_terminal());
+
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+        Ok("text") | Err(_) => subscriber.init(),
+        Ok(other) => panic!("LOG_FORMAT must be either 'text' or 'json', got '{other}'"),
+    }
+}
+
+async fn shutdown_signal(readiness: Readiness, grace_period: Duration) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -60,5 +102,15 @@ async fn shutdown_signal() {
         _ = terminate => {},
     }
 
+    // report not ready first, so load balancer can stop sending traffic before we stop listening
+    readiness.begin_shutdown();
+    if !grace_period.is_zero() {
+        info!(
+            "Shutdown requested, waiting {}s before closing connections",
+            grace_period.as_secs()
+        );
+        tokio::time::sleep(grace_period).await;
+    }
+
     info!("Shutting down")
 }
@@ -0,0 +1,208 @@
+use std::{sync::LazyLock, time::Instant};
+
+use axum::{
+    Router,
+    extract::{MatchedPath, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use tracing::error;
+
+use crate::models::{
+    application::AppState,
+    game::{MissReason, Side},
+};
+
+/// Global, because table actors and DB handles have no access to `AppState`
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    hits: IntCounterVec,
+    misses: IntCounterVec,
+    points: IntCounterVec,
+    active_matches: IntGauge,
+    rally_length: Histogram,
+    hit_time_to_deadline: Histogram,
+    db_write_duration: HistogramVec,
+    db_write_failures: IntCounterVec,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("ping_pong".to_string()), None)
+            .expect("metrics registry prefix must be valid");
+
+        let metrics = Self {
+            hits: IntCounterVec::new(
+                Opts::new("hits_total", "Successful hits by side"),
+                &["side"],
+            )
+            .unwrap(),
+            misses: IntCounterVec::new(
+                Opts::new("misses_total", "Missed balls by side and reason"),
+                &["side", "reason"],
+            )
+            .unwrap(),
+            points: IntCounterVec::new(
+                Opts::new("points_total", "Points scored by side"),
+                &["side"],
+            )
+            .unwrap(),
+            active_matches: IntGauge::new("active_matches", "Matches loaded in memory").unwrap(),
+            rally_length: Histogram::with_opts(
+                HistogramOpts::new("rally_length_hits", "Hits made in a finished rally")
+                    .buckets(vec![1., 2., 3., 5., 8., 13., 21., 34., 55., 89., 144.]),
+            )
+            .unwrap(),
+            hit_time_to_deadline: Histogram::with_opts(
+                HistogramOpts::new(
+                    "hit_time_to_deadline_seconds",
+                    "Time left before the hit deadline when the ball was returned",
+                )
+                // fixed, air time differs between matches
+                .buckets(vec![
+                    0.1, 0.25, 0.5, 1., 2.5, 5., 10., 15., 20., 25., 30., 60., 300., 900., 3600.,
+                ]),
+            )
+            .unwrap(),
+            db_write_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "db_write_duration_seconds",
+                    "Duration of match writes to the database by what was written",
+                ),
+                &["write"],
+            )
+            .unwrap(),
+            db_write_failures: IntCounterVec::new(
+                Opts::new(
+                    "db_write_failures_total",
+                    "Failed match writes to the database by what was written",
+                ),
+                &["write"],
+            )
+            .unwrap(),
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests by route and status"),
+                &["method", "route", "status"],
+            )
+            .unwrap(),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "HTTP request duration by route",
+                ),
+                &["method", "route"],
+            )
+            .unwrap(),
+            registry,
+        };
+
+        for collector in [
+            Box::new(metrics.hits.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(metrics.misses.clone()),
+            Box::new(metrics.points.clone()),
+            Box::new(metrics.active_matches.clone()),
+            Box::new(metrics.rally_length.clone()),
+            Box::new(metrics.hit_time_to_deadline.clone()),
+            Box::new(metrics.db_write_duration.clone()),
+            Box::new(metrics.db_write_failures.clone()),
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_request_duration.clone()),
+        ] {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metric registered twice");
+        }
+
+        metrics
+    }
+
+    /// `time_to_deadline` is `None` for serves - there is no deadline for those
+    pub fn record_hit(&self, side: Side, time_to_deadline: Option<f64>) {
+        self.hits.with_label_values(&[side.to_string()]).inc();
+        if let Some(seconds) = time_to_deadline {
+            self.hit_time_to_deadline.observe(seconds);
+        }
+    }
+
+    pub fn record_point(&self, losing_side: Side, reason: MissReason, rally_length: usize) {
+        self.misses
+            .with_label_values(&[losing_side.to_string(), reason.to_string()])
+            .inc();
+        self.points
+            .with_label_values(&[losing_side.flip().to_string()])
+            .inc();
+        self.rally_length.observe(rally_length as f64);
+    }
+
+    pub fn record_db_write(&self, write: &str, started: Instant, succeeded: bool) {
+        self.db_write_duration
+            .with_label_values(&[write])
+            .observe(started.elapsed().as_secs_f64());
+        if !succeeded {
+            self.db_write_failures.with_label_values(&[write]).inc();
+        }
+    }
+
+    fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("prometheus text format is utf-8"))
+    }
+}
+
+pub fn metrics_routes() -> Router<AppState> {
+    Router::new().route("/metrics", get(metrics))
+}
+
+async fn metrics(State(state): State<AppState>) -> Response {
+    // cheaper to read on scrape than to track every insert
+    let active_matches = state.game_tables.len();
+    METRICS.active_matches.set(active_matches as i64);
+
+    match METRICS.render() {
+        Ok(body) => (
+            [(header::CONTENT_TYPE, TextEncoder::new().format_type())],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to render metrics");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Counts requests per matched route, so match ids don't explode label cardinality
+pub async fn track_http_requests(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    METRICS
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    METRICS
+        .http_request_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+
+    response
+}
@@ -0,0 +1,220 @@
+use super::{
+    event::MatchEvent,
+    game::{GameState, Side},
+};
+
+/// What has to happen to unlock an achievement
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Rule {
+    /// rally or wall streak of at least `hits`, for everyone in it
+    Rally { hits: usize },
+    /// win with `points` while the opponent has none
+    Shutout { points: usize },
+    /// win with the average reaction time below `ms`
+    QuickWin { average_reaction_ms: u64 },
+    /// save match points in a single game, opponent was a point away from winning
+    MatchPointsSaved { count: usize },
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Achievement {
+    /// stored per player, keep it once released
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub rule: Rule,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 4] = [
+    Achievement {
+        id: "centurion",
+        title: "Centurion",
+        description: "Play a rally of 100 hits",
+        rule: Rule::Rally { hits: 100 },
+    },
+    Achievement {
+        id: "flawless",
+        title: "Flawless",
+        description: "Win 11-0",
+        rule: Rule::Shutout { points: 11 },
+    },
+    Achievement {
+        id: "lightning",
+        title: "Lightning",
+        description: "Win reacting in under a second on average",
+        rule: Rule::QuickWin {
+            average_reaction_ms: 1000,
+        },
+    },
+    Achievement {
+        id: "escape_artist",
+        title: "Escape Artist",
+        description: "Save 5 match points in a game",
+        rule: Rule::MatchPointsSaved { count: 5 },
+    },
+];
+
+pub fn find(id: &str) -> Option<&'static Achievement> {
+    ACHIEVEMENTS.iter().find(|achievement| achievement.id == id)
+}
+
+impl Rule {
+    /// Sides that unlock it with `event`. `game_state` is the one right after the event.
+    fn unlocked_by(&self, event: &MatchEvent, game_state: &GameState) -> Vec<Side> {
+        match (*self, event) {
+            (Rule::Rally { hits }, MatchEvent::Point { rally_hits, .. }) if *rally_hits >= hits => {
+                vec![Side::Ping, Side::Pong]
+            }
+            // the wall has no player to unlock anything
+            (Rule::Rally { hits }, MatchEvent::Streak { returns, .. }) if *returns >= hits => {
+                vec![Side::Ping]
+            }
+            (Rule::Shutout { points }, MatchEvent::Won { winner, score, .. }) => {
+                let (won, lost) = match winner {
+                    Side::Ping => (score.ping, score.pong),
+                    Side::Pong => (score.pong, score.ping),
+                };
+                (won >= points && lost == 0)
+                    .then_some(*winner)
+                    .into_iter()
+                    .collect()
+            }
+            (
+                Rule::QuickWin {
+                    average_reaction_ms,
+                },
+                MatchEvent::Won { winner, .. },
+            ) => {
+                let (total, reactions) = game_state.stats.side(*winner).reaction_time_totals();
+                (reactions > 0 && total < average_reaction_ms * reactions as u64)
+                    .then_some(*winner)
+                    .into_iter()
+                    .collect()
+            }
+            // only once, when the count is reached
+            (
+                Rule::MatchPointsSaved { count },
+                MatchEvent::Point {
+                    loser,
+                    match_point_saved: true,
+                    ..
+                },
+            ) => {
+                let saver = loser.flip();
+                (game_state.stats.side(saver).match_points_saved == count)
+                    .then_some(saver)
+                    .into_iter()
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// Achievements unlocked by `event`, with the side that unlocked them
+pub fn unlocked(event: &MatchEvent, game_state: &GameState) -> Vec<(Side, &'static Achievement)> {
+    ACHIEVEMENTS
+        .iter()
+        .flat_map(|achievement| {
+            achievement
+                .rule
+                .unlocked_by(event, game_state)
+                .into_iter()
+                .map(move |side| (side, achievement))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::game::{GameMode, MissReason, Score};
+
+    fn point(loser: Side, rally_hits: usize, match_point_saved: bool) -> MatchEvent {
+        MatchEvent::Point {
+            loser,
+            reason: MissReason::Timeout,
+            rally_hits,
+            rally_duration: None,
+            score: Score::default(),
+            match_point_saved,
+            players: Default::default(),
+        }
+    }
+
+    fn won(winner: Side, ping: usize, pong: usize) -> MatchEvent {
+        MatchEvent::Won {
+            winner,
+            score: Score { ping, pong },
+            ping: None,
+            pong: None,
+            longest_rally: None,
+        }
+    }
+
+    fn ids(unlocked: Vec<(Side, &'static Achievement)>) -> Vec<(Side, &'static str)> {
+        unlocked
+            .into_iter()
+            .map(|(side, achievement)| (side, achievement.id))
+            .collect()
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        for achievement in &ACHIEVEMENTS {
+            assert_eq!(find(achievement.id), Some(achievement));
+        }
+        assert_eq!(find("unknown"), None);
+    }
+
+    #[test]
+    fn long_rallies_count_for_both_sides() {
+        let game_state = GameState::new(GameMode::Classic);
+        assert!(unlocked(&point(Side::Ping, 99, false), &game_state).is_empty());
+        assert_eq!(
+            ids(unlocked(&point(Side::Ping, 100, false), &game_state)),
+            vec![(Side::Ping, "centurion"), (Side::Pong, "centurion")]
+        );
+    }
+
+    #[test]
+    fn shutouts_need_the_full_score() {
+        let game_state = GameState::new(GameMode::Classic);
+        assert_eq!(
+            ids(unlocked(&won(Side::Pong, 0, 11), &game_state)),
+            vec![(Side::Pong, "flawless")]
+        );
+        assert!(unlocked(&won(Side::Pong, 1, 11), &game_state).is_empty());
+        assert!(unlocked(&won(Side::Pong, 0, 5), &game_state).is_empty());
+    }
+
+    #[test]
+    fn quick_wins_need_reactions_of_the_winner() {
+        let mut game_state = GameState::new(GameMode::Classic);
+        for ms in [400, 400, 400] {
+            game_state.stats.pong.reaction_times.record(ms);
+        }
+        for ms in [999, 1002] {
+            game_state.stats.ping.reaction_times.record(ms);
+        }
+        assert_eq!(
+            ids(unlocked(&won(Side::Pong, 3, 11), &game_state)),
+            vec![(Side::Pong, "lightning")]
+        );
+        assert!(unlocked(&won(Side::Ping, 11, 3), &game_state).is_empty());
+    }
+
+    #[test]
+    fn saved_match_points_unlock_once() {
+        let mut game_state = GameState::new(GameMode::Classic);
+        game_state.stats.ping.match_points_saved = 5;
+        assert_eq!(
+            ids(unlocked(&point(Side::Pong, 1, true), &game_state)),
+            vec![(Side::Ping, "escape_artist")]
+        );
+        // a point won after the fifth save isn't one more
+        assert!(unlocked(&point(Side::Pong, 1, false), &game_state).is_empty());
+        game_state.stats.ping.match_points_saved = 6;
+        assert!(unlocked(&point(Side::Pong, 1, true), &game_state).is_empty());
+    }
+}
@@ -1,19 +1,231 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
 };
 
+use dashmap::DashMap;
 use sqlx::PgPool;
+use tokio::sync::OnceCell;
 
-use crate::database::TableUid;
+use crate::{
+    cluster::Cluster, config::Config, database::TableUid, lobby::LobbyHandle,
+    rate_limit::RateLimits, tournament::Tournaments,
+};
 
 use super::game::TableState;
 
-// TODO: consider DashMap if contention becomes an issue
-pub type GameTables = HashMap<TableUid, TableState>;
+/// Tables of live matches served here. Sharded, so requests for different matches
+/// rarely wait for each other.
+#[derive(Default)]
+pub struct GameTables {
+    /// empty while the table is being created
+    tables: DashMap<TableUid, Arc<OnceCell<TableState>>>,
+}
+
+impl GameTables {
+    pub fn get(&self, uid: &TableUid) -> Option<TableState> {
+        self.tables.get(uid)?.get().cloned()
+    }
+
+    pub fn contains(&self, uid: &TableUid) -> bool {
+        self.get(uid).is_some()
+    }
+
+    pub fn insert(&self, uid: TableUid, table_state: TableState) {
+        self.tables
+            .insert(uid, Arc::new(OnceCell::new_with(Some(table_state))));
+    }
+
+    pub fn remove(&self, uid: &TableUid) -> Option<TableState> {
+        let (_, slot) = self.tables.remove(uid)?;
+        slot.get().cloned()
+    }
+
+    /// The table there already, or `table_state` if there was none
+    pub fn get_or_insert(&self, uid: &TableUid, table_state: TableState) -> TableState {
+        let slot = self.slot(uid);
+        let _ = slot.set(table_state);
+        slot.get().expect("slot was just filled").clone()
+    }
+
+    /// The table there already, or the one `create` makes. Concurrent callers for the same
+    /// match wait for a single `create`, and try their own only if it fails.
+    pub async fn get_or_try_insert_with<F, Fut, E>(
+        &self,
+        uid: &TableUid,
+        create: F,
+    ) -> Result<TableState, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<TableState, E>>,
+    {
+        let slot = self.slot(uid);
+        let result = slot.get_or_try_init(create).await.cloned();
+        if result.is_err() {
+            // nobody managed, an empty slot shouldn't outlive the callers
+            self.tables.remove_if(uid, |_, current| {
+                Arc::ptr_eq(current, &slot) && current.get().is_none()
+            });
+        }
+        result
+    }
+
+    /// Shard lock is only held until the slot is cloned, never across awaits
+    fn slot(&self, uid: &TableUid) -> Arc<OnceCell<TableState>> {
+        self.tables.entry(uid.clone()).or_default().clone()
+    }
+
+    pub fn uids(&self) -> Vec<TableUid> {
+        self.tables
+            .iter()
+            .filter(|slot| slot.get().is_some())
+            .map(|slot| slot.key().clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables
+            .iter()
+            .filter(|slot| slot.get().is_some())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl FromIterator<(TableUid, TableState)> for GameTables {
+    fn from_iter<I: IntoIterator<Item = (TableUid, TableState)>>(tables: I) -> Self {
+        let game_tables = GameTables::default();
+        for (uid, table_state) in tables {
+            game_tables.insert(uid, table_state);
+        }
+        game_tables
+    }
+}
 
 #[derive(Clone)]
 pub struct AppState {
-    pub game_tables: Arc<RwLock<GameTables>>,
+    pub game_tables: Arc<GameTables>,
     pub db_pool: PgPool,
+    pub readiness: Readiness,
+    pub config: Arc<Config>,
+    pub rate_limits: Arc<RateLimits>,
+    pub tournaments: Tournaments,
+    pub lobby: LobbyHandle,
+    /// `None` when this is the only instance
+    pub cluster: Option<Cluster>,
+}
+
+/// Process-level flags that are not checkable from the outside, reported by `/readyz`
+#[derive(Clone, Default)]
+pub struct Readiness {
+    tables_loaded: Arc<AtomicBool>,
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn mark_tables_loaded(&self) {
+        self.tables_loaded.store(true, Ordering::Release);
+    }
+
+    pub fn tables_loaded(&self) -> bool {
+        self.tables_loaded.load(Ordering::Acquire)
+    }
+
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Release);
+    }
+
+    pub fn shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{
+        database::TableDbSyncHandle,
+        models::{
+            game::{GameMode, GameState},
+            player::Claims,
+            settings::MatchSettings,
+        },
+    };
+
+    fn table(mode: GameMode) -> TableState {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        TableState::new(
+            GameState::new(mode),
+            Claims::default(),
+            MatchSettings::default(),
+            TableDbSyncHandle::new(0, &pool, None),
+        )
+    }
+
+    #[tokio::test]
+    async fn concurrent_first_requests_create_once() {
+        let game_tables = Arc::new(GameTables::default());
+        let uid = TableUid::parse("race").unwrap();
+        let created = Arc::new(AtomicUsize::new(0));
+
+        let requests: Vec<_> = (0..10)
+            .map(|_| {
+                let (game_tables, uid, created) =
+                    (game_tables.clone(), uid.clone(), created.clone());
+                tokio::spawn(async move {
+                    game_tables
+                        .get_or_try_insert_with(&uid, || async {
+                            created.fetch_add(1, Ordering::SeqCst);
+                            tokio::task::yield_now().await;
+                            Ok::<_, ()>(table(GameMode::Classic))
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for request in requests {
+            assert!(request.await.unwrap().is_ok());
+        }
+
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        assert_eq!(game_tables.uids(), vec![uid]);
+    }
+
+    #[tokio::test]
+    async fn failed_creation_leaves_nothing_behind() {
+        let game_tables = GameTables::default();
+        let uid = TableUid::parse("gone").unwrap();
+
+        let failed = game_tables
+            .get_or_try_insert_with(&uid, || async { Err("archived") })
+            .await;
+        assert!(failed.is_err());
+        assert!(game_tables.is_empty());
+        assert!(!game_tables.contains(&uid));
+
+        let created = game_tables
+            .get_or_try_insert_with(&uid, || async { Ok::<_, ()>(table(GameMode::Wall)) })
+            .await
+            .unwrap();
+        assert_eq!(created.mode(), GameMode::Wall);
+        assert_eq!(game_tables.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn first_table_is_kept() {
+        let game_tables = GameTables::default();
+        let uid = TableUid::parse("kept").unwrap();
+
+        game_tables.get_or_insert(&uid, table(GameMode::Wall));
+        let kept = game_tables.get_or_insert(&uid, table(GameMode::Classic));
+        assert_eq!(kept.mode(), GameMode::Wall);
+
+        assert_eq!(game_tables.remove(&uid).unwrap().mode(), GameMode::Wall);
+        assert!(game_tables.get(&uid).is_none());
+    }
 }
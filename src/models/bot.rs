@@ -0,0 +1,178 @@
+use std::{f64::consts::PI, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use super::game::Side;
+use super::player::PlayerName;
+
+/// How well a bot plays. Missing fields take the defaults.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BotSkill {
+    /// average time it takes to return the ball
+    pub reaction_time_ms: u64,
+    /// standard deviation of the reaction time
+    pub reaction_time_spread_ms: u64,
+    /// chance of letting a returnable ball drop, from 0 to 1. Serves always go in.
+    pub miss_probability: f64,
+    /// added to the reaction time for every hit in the rally, long rallies get harder
+    pub slowdown_per_hit_ms: u64,
+}
+
+impl Default for BotSkill {
+    fn default() -> Self {
+        Self {
+            reaction_time_ms: 500,
+            reaction_time_spread_ms: 150,
+            miss_probability: 0.05,
+            slowdown_per_hit_ms: 0,
+        }
+    }
+}
+
+impl BotSkill {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.miss_probability) {
+            return Err("missProbability must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Normally distributed around the average, never negative
+    pub fn reaction_time(&self, hit_count: usize, rng: &mut impl Rng) -> Duration {
+        // Box-Muller transform, `rand` alone has no normal distribution
+        let (u1, u2): (f64, f64) = (rng.random(), rng.random());
+        let z = (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * PI * u2).cos();
+
+        let millis = self.reaction_time_ms as f64
+            + z * self.reaction_time_spread_ms as f64
+            + self.slowdown_per_hit_ms as f64 * hit_count as f64;
+        Duration::from_secs_f64(millis.max(0.0) / 1000.0)
+    }
+}
+
+/// Bot playing one side of a match, stops when dropped
+pub struct Bot {
+    pub skill: BotSkill,
+    /// of whoever attached it, needed to detach it again
+    pub token: String,
+    pub task: JoinHandle<()>,
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+pub struct Bots {
+    pub ping: Option<Bot>,
+    pub pong: Option<Bot>,
+}
+
+impl Bots {
+    pub fn get(&self, side: Side) -> Option<&Bot> {
+        match side {
+            Side::Ping => self.ping.as_ref(),
+            Side::Pong => self.pong.as_ref(),
+        }
+    }
+
+    pub fn slot(&mut self, side: Side) -> &mut Option<Bot> {
+        match side {
+            Side::Ping => &mut self.ping,
+            Side::Pong => &mut self.pong,
+        }
+    }
+
+    pub fn skills(&self) -> BotSkills {
+        let skill = |bot: &Option<Bot>| bot.as_ref().map(|bot| bot.skill.clone());
+        BotSkills {
+            ping: skill(&self.ping),
+            pong: skill(&self.pong),
+        }
+    }
+}
+
+/// Only the skills of the bots are public
+#[derive(Clone, Default, Serialize, Debug)]
+pub struct BotSkills {
+    pub ping: Option<BotSkill>,
+    pub pong: Option<BotSkill>,
+}
+
+impl BotSkills {
+    pub fn get(&self, side: Side) -> Option<&BotSkill> {
+        match side {
+            Side::Ping => self.ping.as_ref(),
+            Side::Pong => self.pong.as_ref(),
+        }
+    }
+}
+
+/// Why a bot can't be attached to a side, or detached from it
+#[derive(Debug)]
+pub enum BotRefused {
+    /// by a player whose token wasn't sent
+    Claimed(PlayerName),
+    AlreadyPlayed,
+    NoBot,
+    /// neither the token of whoever attached the bot, nor a referee
+    NotKeeper,
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn reaction_time_follows_skill() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let steady = BotSkill {
+            reaction_time_ms: 300,
+            reaction_time_spread_ms: 0,
+            slowdown_per_hit_ms: 10,
+            ..BotSkill::default()
+        };
+        assert_eq!(
+            steady.reaction_time(0, &mut rng),
+            Duration::from_millis(300)
+        );
+        assert_eq!(
+            steady.reaction_time(5, &mut rng),
+            Duration::from_millis(350)
+        );
+
+        let shaky = BotSkill {
+            reaction_time_ms: 100,
+            reaction_time_spread_ms: 500,
+            ..BotSkill::default()
+        };
+        let samples: Vec<_> = (0..1000)
+            .map(|_| shaky.reaction_time(0, &mut rng))
+            .collect();
+        assert!(samples.contains(&Duration::ZERO));
+        assert!(
+            samples
+                .iter()
+                .any(|sample| *sample > Duration::from_millis(600))
+        );
+    }
+
+    #[test]
+    fn miss_probability_is_a_probability() {
+        for invalid in [-0.1, 1.5, f64::NAN] {
+            let skill = BotSkill {
+                miss_probability: invalid,
+                ..BotSkill::default()
+            };
+            assert!(skill.validate().is_err(), "{invalid}");
+        }
+        assert!(BotSkill::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::{
+    event::{MatchEvent, SideResult},
+    game::{Score, Side},
+    player::PlayerName,
+};
+
+/// A won match as recorded in its `Won` event
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchResult {
+    pub winner: Side,
+    pub score: Score,
+    pub ping: SideResult,
+    pub pong: SideResult,
+    pub longest_rally: Option<usize>,
+}
+
+impl MatchResult {
+    /// `None` for other events, and wins recorded before careers were kept
+    pub fn from_event(event: MatchEvent) -> Option<Self> {
+        match event {
+            MatchEvent::Won {
+                winner,
+                score,
+                ping: Some(ping),
+                pong: Some(pong),
+                longest_rally,
+            } => Some(MatchResult {
+                winner,
+                score,
+                ping,
+                pong,
+                longest_rally,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn side_of(&self, player: &PlayerName) -> Option<Side> {
+        [Side::Ping, Side::Pong]
+            .into_iter()
+            .find(|&side| self.side(side).players().any(|seated| seated == player))
+    }
+
+    pub fn side(&self, side: Side) -> &SideResult {
+        match side {
+            Side::Ping => &self.ping,
+            Side::Pong => &self.pong,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Won,
+    Lost,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Streak {
+    pub outcome: Outcome,
+    pub matches: usize,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct HeadToHead {
+    pub opponent: PlayerName,
+    pub wins: usize,
+    pub losses: usize,
+}
+
+/// Everything a player did in won matches, opponents that didn't claim their side
+/// only count towards the totals
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Career {
+    pub matches: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub head_to_head: Vec<HeadToHead>,
+    pub average_reaction_time_ms: Option<f64>,
+    /// hits of the longest rally of any match played
+    pub longest_rally: Option<usize>,
+    pub current_streak: Option<Streak>,
+}
+
+impl Career {
+    /// `results` oldest first, ones the player didn't play in are skipped
+    pub fn from_results<'a>(
+        player: &PlayerName,
+        results: impl IntoIterator<Item = &'a MatchResult>,
+    ) -> Self {
+        let mut career = Career {
+            matches: 0,
+            wins: 0,
+            losses: 0,
+            head_to_head: vec![],
+            average_reaction_time_ms: None,
+            longest_rally: None,
+            current_streak: None,
+        };
+        let mut opponents: BTreeMap<&PlayerName, (usize, usize)> = BTreeMap::new();
+        let (mut reaction_time_total_ms, mut reactions) = (0, 0);
+
+        for result in results {
+            let Some(side) = result.side_of(player) else {
+                continue;
+            };
+            let outcome = if result.winner == side {
+                Outcome::Won
+            } else {
+                Outcome::Lost
+            };
+            career.matches += 1;
+            match outcome {
+                Outcome::Won => career.wins += 1,
+                Outcome::Lost => career.losses += 1,
+            }
+            // both players of a doubles side were faced
+            for opponent in result.side(side.flip()).players() {
+                let (wins, losses) = opponents.entry(opponent).or_default();
+                match outcome {
+                    Outcome::Won => *wins += 1,
+                    Outcome::Lost => *losses += 1,
+                }
+            }
+
+            let own = result.side(side);
+            reaction_time_total_ms += own.reaction_time_total_ms;
+            reactions += own.reactions;
+            career.longest_rally = career.longest_rally.max(result.longest_rally);
+            match &mut career.current_streak {
+                Some(streak) if streak.outcome == outcome => streak.matches += 1,
+                streak => {
+                    *streak = Some(Streak {
+                        outcome,
+                        matches: 1,
+                    })
+                }
+            }
+        }
+
+        career.head_to_head = opponents
+            .into_iter()
+            .map(|(opponent, (wins, losses))| HeadToHead {
+                opponent: opponent.clone(),
+                wins,
+                losses,
+            })
+            .collect();
+        career.average_reaction_time_ms =
+            (reactions > 0).then(|| reaction_time_total_ms as f64 / reactions as f64);
+        career
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str) -> PlayerName {
+        PlayerName::try_from(name.to_string()).unwrap()
+    }
+
+    fn side(name: Option<&str>, reaction_time_total_ms: u64, reactions: usize) -> SideResult {
+        SideResult {
+            player: name.map(player),
+            partner: None,
+            hits: reactions,
+            reaction_time_total_ms,
+            reactions,
+        }
+    }
+
+    fn result(ping: SideResult, pong: SideResult, winner: Side, longest: usize) -> MatchResult {
+        MatchResult {
+            winner,
+            score: Score::default(),
+            ping,
+            pong,
+            longest_rally: Some(longest),
+        }
+    }
+
+    #[test]
+    fn results_without_sides_are_skipped() {
+        let won = MatchEvent::Won {
+            winner: Side::Ping,
+            score: Score::default(),
+            ping: None,
+            pong: None,
+            longest_rally: None,
+        };
+        assert_eq!(MatchResult::from_event(won), None);
+        assert_eq!(MatchResult::from_event(MatchEvent::Reset), None);
+    }
+
+    #[test]
+    fn career_sums_up_matches_from_either_side() {
+        let results = [
+            result(
+                side(Some("alice"), 1000, 4),
+                side(Some("bob"), 0, 0),
+                Side::Ping,
+                7,
+            ),
+            result(
+                side(Some("bob"), 0, 0),
+                side(Some("alice"), 600, 1),
+                Side::Ping,
+                12,
+            ),
+            result(
+                side(Some("carol"), 0, 0),
+                side(Some("alice"), 0, 0),
+                Side::Ping,
+                3,
+            ),
+            result(side(None, 0, 0), side(Some("bob"), 0, 0), Side::Ping, 20),
+            result(side(Some("alice"), 0, 0), side(None, 0, 0), Side::Pong, 5),
+        ];
+
+        let career = Career::from_results(&player("alice"), &results);
+        assert_eq!(career.matches, 4);
+        assert_eq!((career.wins, career.losses), (1, 3));
+        assert_eq!(
+            career.head_to_head,
+            vec![
+                HeadToHead {
+                    opponent: player("bob"),
+                    wins: 1,
+                    losses: 1
+                },
+                HeadToHead {
+                    opponent: player("carol"),
+                    wins: 0,
+                    losses: 1
+                },
+            ]
+        );
+        assert_eq!(career.average_reaction_time_ms, Some(320.));
+        // bob's 20 hit rally was in a match without alice
+        assert_eq!(career.longest_rally, Some(12));
+        assert_eq!(
+            career.current_streak,
+            Some(Streak {
+                outcome: Outcome::Lost,
+                matches: 3
+            })
+        );
+    }
+
+    #[test]
+    fn players_without_matches_have_an_empty_career() {
+        let career = Career::from_results(&player("dave"), &[]);
+        assert_eq!(career.matches, 0);
+        assert_eq!(career.average_reaction_time_ms, None);
+        assert_eq!(career.current_streak, None);
+    }
+}
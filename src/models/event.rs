@@ -0,0 +1,137 @@
+use jiff::SignedDuration;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    game::{MissReason, Score, Side},
+    player::PlayerName,
+    stats::SideStats,
+};
+
+/// Match history entry, stored as it happened
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum MatchEvent {
+    #[serde(rename_all = "camelCase")]
+    Point {
+        /// side that missed
+        loser: Side,
+        reason: MissReason,
+        rally_hits: usize,
+        rally_duration: Option<SignedDuration>,
+        score: Score,
+        /// the loser would have won the game with this point
+        #[serde(default)]
+        match_point_saved: bool,
+        /// who played the rally, missing from points recorded before records went to them
+        #[serde(default)]
+        players: SidePlayers,
+    },
+    /// rally of a wall match is over
+    #[serde(rename_all = "camelCase")]
+    Streak {
+        reason: MissReason,
+        /// player's returns in a row
+        returns: usize,
+        rally_duration: Option<SignedDuration>,
+        /// best streak of the match so far, this one included
+        best: usize,
+        /// who played the rally, missing from streaks recorded before records went to them
+        #[serde(default)]
+        players: SidePlayers,
+    },
+    /// last point of the match, recorded right after it.
+    /// Sides and longest rally are missing from events recorded before careers were kept.
+    #[serde(rename_all = "camelCase")]
+    Won {
+        winner: Side,
+        score: Score,
+        #[serde(default)]
+        ping: Option<SideResult>,
+        #[serde(default)]
+        pong: Option<SideResult>,
+        /// hits of the longest rally
+        #[serde(default)]
+        longest_rally: Option<usize>,
+    },
+    /// last point taken back
+    #[serde(rename_all = "camelCase")]
+    Undo { by: String, score: Score },
+    /// score set by an admin, it counts as played from then on
+    #[serde(rename_all = "camelCase")]
+    ScoreSet { by: String, score: Score },
+    /// rally replayed without a point
+    #[serde(rename_all = "camelCase")]
+    Let { by: String, rally_hits: usize },
+    /// match started over, by an admin or for the next challenger
+    Reset,
+    /// match set a new record, recorded once records caught up with it
+    Record { record: String, value: i64 },
+    /// player unlocked an achievement for the first time, right after the event that did it
+    Achievement {
+        player: PlayerName,
+        achievement: String,
+    },
+}
+
+/// Players of both sides, `None` for unclaimed ones
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SidePlayers {
+    pub ping: Option<PlayerName>,
+    pub pong: Option<PlayerName>,
+    /// second players of doubles sides
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ping_partner: Option<PlayerName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pong_partner: Option<PlayerName>,
+}
+
+/// What a side did in a won match, for the careers of its players. Partners share it.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SideResult {
+    /// first player of the side, `None` if it wasn't claimed
+    pub player: Option<PlayerName>,
+    /// second player of a doubles side, `None` if it wasn't claimed
+    #[serde(default)]
+    pub partner: Option<PlayerName>,
+    pub hits: usize,
+    /// sum of all reaction times, so averages can span matches
+    pub reaction_time_total_ms: u64,
+    pub reactions: usize,
+}
+
+impl SideResult {
+    pub fn new(player: Option<PlayerName>, partner: Option<PlayerName>, stats: &SideStats) -> Self {
+        let (reaction_time_total_ms, reactions) = stats.reaction_time_totals();
+        SideResult {
+            player,
+            partner,
+            hits: stats.hits,
+            reaction_time_total_ms,
+            reactions,
+        }
+    }
+
+    /// Claimed players of the side, first partner first
+    pub fn players(&self) -> impl Iterator<Item = &PlayerName> {
+        self.player.iter().chain(&self.partner)
+    }
+}
+
+impl MatchEvent {
+    /// same as serialized `kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MatchEvent::Point { .. } => "point",
+            MatchEvent::Streak { .. } => "streak",
+            MatchEvent::Won { .. } => "won",
+            MatchEvent::Undo { .. } => "undo",
+            MatchEvent::ScoreSet { .. } => "scoreSet",
+            MatchEvent::Let { .. } => "let",
+            MatchEvent::Reset => "reset",
+            MatchEvent::Record { .. } => "record",
+            MatchEvent::Achievement { .. } => "achievement",
+        }
+    }
+}
@@ -1,14 +1,26 @@
 use std::fmt::{self, Formatter};
-use std::sync::{Arc, RwLock};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use jiff::{SignedDuration, Timestamp};
-use log::error;
-use serde::{Deserialize, Serialize};
-use tokio::task::JoinHandle;
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+    time::Instant,
+};
+use tracing::{Span, instrument};
+use uuid::Uuid;
 
-use crate::clock;
 use crate::database::TableDbSyncHandle;
 
+use super::bot::{BotRefused, BotSkill};
+use super::player::{Claim, Claims, PlayerName};
+use super::settings::MatchSettings;
+use super::stats::{MatchStats, RallyHits};
+use super::table_actor::{BotKeeper, Command, LiveState, TableActor};
+
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Default, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Side {
@@ -35,6 +47,110 @@ impl fmt::Display for Side {
     }
 }
 
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ping" => Ok(Side::Ping),
+            "pong" => Ok(Side::Pong),
+            other => Err(format!("unknown side '{other}'")),
+        }
+    }
+}
+
+/// One of the two players of a doubles team
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Partner {
+    /// first player to claim the side
+    #[default]
+    First,
+    Second,
+}
+
+impl Partner {
+    pub fn flip(&self) -> Self {
+        match self {
+            Partner::First => Partner::Second,
+            Partner::Second => Partner::First,
+        }
+    }
+}
+
+impl fmt::Display for Partner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Partner::First => write!(f, "first"),
+            Partner::Second => write!(f, "second"),
+        }
+    }
+}
+
+impl FromStr for Partner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Partner::First),
+            "second" => Ok(Partner::Second),
+            other => Err(format!("unknown partner '{other}'")),
+        }
+    }
+}
+
+/// Partner of each doubles team who's up next
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Default, Debug)]
+pub struct Rotation {
+    pub ping: Partner,
+    pub pong: Partner,
+}
+
+impl Rotation {
+    pub fn get(&self, side: Side) -> Partner {
+        match side {
+            Side::Ping => self.ping,
+            Side::Pong => self.pong,
+        }
+    }
+
+    /// The other partner of `side` is up next
+    pub fn pass(&mut self, side: Side) {
+        match side {
+            Side::Ping => self.ping = self.ping.flip(),
+            Side::Pong => self.pong = self.pong.flip(),
+        }
+    }
+}
+
+/// Why the ball was not returned
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum MissReason {
+    /// hit attempted while the ball was on the other side
+    WrongSide,
+    /// ball was not returned before the deadline
+    Timeout,
+    /// rally ended by an admin
+    Forced,
+    /// ball returned sooner than the minimum reaction time allows
+    TooEarly,
+    /// doubles partner hit out of turn
+    WrongPartner,
+}
+
+impl fmt::Display for MissReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MissReason::WrongSide => write!(f, "wrong_side"),
+            MissReason::Timeout => write!(f, "timeout"),
+            MissReason::Forced => write!(f, "forced"),
+            MissReason::TooEarly => write!(f, "too_early"),
+            MissReason::WrongPartner => write!(f, "wrong_partner"),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
 pub struct Score {
     pub ping: usize,
@@ -48,9 +164,20 @@ impl Score {
             Side::Pong => self.ping += 1,
         }
     }
+
+    /// Side with at least `points_to_win` points and a two point lead
+    pub fn winner(&self, points_to_win: usize) -> Option<Side> {
+        if self.ping >= points_to_win && self.ping >= self.pong + 2 {
+            Some(Side::Ping)
+        } else if self.pong >= points_to_win && self.pong >= self.ping + 2 {
+            Some(Side::Pong)
+        } else {
+            None
+        }
+    }
 }
 
-#[derive(Serialize, Default)]
+#[derive(Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct RallyState {
     pub side: Side,
@@ -58,103 +185,532 @@ pub struct RallyState {
     pub hit_timeout: Option<Timestamp>,
     #[serde(rename = "serveTimestamp")]
     pub first_hit_at: Option<Timestamp>,
+    #[serde(rename = "lastHitTimestamp")]
+    pub last_hit_at: Option<Timestamp>,
     pub hit_count: usize,
+    /// doubles only - partner of each side who has to hit next
+    #[serde(rename = "nextPartners")]
+    pub partners: Option<Rotation>,
+    /// hits are rejected and the ball's countdown is stopped
+    pub paused: bool,
+    /// air time the ball had left when the match was paused
+    #[serde(skip)]
+    pub paused_air_time: Option<Duration>,
+    /// when the ball drops, on the runtime's clock
+    #[serde(skip)]
+    pub deadline: Option<Instant>,
     #[serde(skip)]
-    pub hit_timeout_task: Option<JoinHandle<()>>,
+    pub hits: RallyHits,
+}
+
+impl RallyState {
+    /// Drops the ongoing rally, server of `game_state` serves next. Pause stays.
+    pub fn restart(&mut self, game_state: &GameState) {
+        *self = RallyState {
+            side: game_state.server,
+            partners: game_state.rotation,
+            paused: self.paused,
+            ..RallyState::default()
+        };
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LongestRally {
-    hit_count: usize,
-    duration: SignedDuration,
+    pub hit_count: usize,
+    pub duration: SignedDuration,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    /// ping against pong, for points
+    #[default]
+    Classic,
+    /// ping alone against a wall on the pong side, for streaks
+    Wall,
+    /// two players per side, taking turns to hit
+    Doubles,
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GameMode::Classic => write!(f, "classic"),
+            GameMode::Wall => write!(f, "wall"),
+            GameMode::Doubles => write!(f, "doubles"),
+        }
+    }
+}
+
+/// Scoring of wall matches - nobody wins points, the player chases their best streak
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WallScore {
+    /// returns in a row in the latest finished rally
+    pub last_streak: usize,
+    pub best_streak: usize,
+    /// finished rallies
+    pub rallies: usize,
+}
+
+impl WallScore {
+    pub fn end_rally(&mut self, returns: usize) {
+        self.last_streak = returns;
+        self.best_streak = self.best_streak.max(returns);
+        self.rallies += 1;
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GameState {
     pub server: Side,
+    /// only counted in classic matches
     pub score: Score,
     pub longest_rally: Option<LongestRally>,
+    /// rallies replayed without a point
+    #[serde(default)]
+    pub lets: usize,
+    #[serde(default)]
+    pub mode: GameMode,
+    /// only present in wall matches
+    #[serde(default)]
+    pub wall: Option<WallScore>,
+    /// only present in doubles matches - who serves and who receives the next rally
+    #[serde(default)]
+    pub rotation: Option<Rotation>,
+    /// set once a side reaches the points to win, no more hits after that
+    #[serde(default)]
+    pub winner: Option<Side>,
+    #[serde(default)]
+    pub stats: MatchStats,
 }
 
-/// Updates longest rally - hit count based.
-///
-/// Duration only saved as a bonus - you can have more hits with shorter duration
-/// and it will overwrite previous, longer one.
-fn update_statistics(
-    game_state: &mut std::sync::RwLockWriteGuard<'_, GameState>,
-    rally_state: &std::sync::RwLockWriteGuard<'_, RallyState>,
-) {
-    if let Some(start) = rally_state.first_hit_at {
-        let current_rally_time = clock::now().duration_since(start);
-        match &mut game_state.longest_rally {
-            None => {
-                game_state.longest_rally = Some(LongestRally {
-                    hit_count: rally_state.hit_count,
-                    duration: current_rally_time,
-                })
-            }
-            Some(longest_rally) => {
-                if longest_rally.hit_count < rally_state.hit_count {
-                    longest_rally.duration = current_rally_time;
-                    longest_rally.hit_count = rally_state.hit_count
-                } else if longest_rally.hit_count == rally_state.hit_count
-                    && longest_rally.duration < current_rally_time
-                {
-                    longest_rally.duration = current_rally_time
-                }
-            }
-        }
-    }
-}
-
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
+impl GameState {
+    pub fn new(mode: GameMode) -> Self {
+        Self {
+            mode,
+            wall: (mode == GameMode::Wall).then(WallScore::default),
+            rotation: (mode == GameMode::Doubles).then(Rotation::default),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HitOutcome {
+    /// with the side that has to hit next
+    Returned(Side),
+    Missed,
+    /// not counted as a miss, the match is on hold
+    Paused,
+    /// not counted as a miss, the ball is still in play
+    TooEarly,
+    /// match has a winner already
+    Over,
+}
+
+/// Handle of a table. Its state belongs to the table's actor, changes go through it and
+/// reads see what it published last.
+#[derive(Clone)]
 pub struct TableState {
-    pub rally_state: Arc<RwLock<RallyState>>,
-    pub game_state: Arc<RwLock<GameState>>,
-    #[serde(skip)]
-    db_handle: TableDbSyncHandle,
+    live: watch::Receiver<LiveState>,
+    /// final game state, once the match is won
+    finished: watch::Receiver<Option<GameState>>,
+    wins: watch::Receiver<Vec<Option<Side>>>,
+    commands: mpsc::UnboundedSender<(Command, Span)>,
+    /// where the actor keeps the game states around commands of this handle, if audited
+    audited: Option<Arc<Mutex<AuditedStates>>>,
+}
+
+/// Game states the actor saw right before the first audited command and right after the last
+#[derive(Default)]
+pub struct AuditedStates {
+    pub before: Option<GameState>,
+    pub after: Option<GameState>,
+}
+
+/// The table's actor is gone, it can't take any more changes
+#[derive(Debug)]
+pub struct TableStopped;
+
+impl fmt::Display for TableStopped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "table actor has stopped")
+    }
+}
+
+/// Sends `command` to the table's actor, returns its answer
+async fn ask<T>(
+    commands: &mpsc::UnboundedSender<(Command, Span)>,
+    command: impl FnOnce(oneshot::Sender<T>) -> Command,
+) -> Result<T, TableStopped> {
+    let (reply, answer) = oneshot::channel();
+    commands
+        .send((command(reply), Span::current()))
+        .map_err(|_| TableStopped)?;
+    answer.await.map_err(|_| TableStopped)
+}
+
+/// Table that may be gone already. Rally changes can be waited for.
+pub struct WeakTableState {
+    live: watch::Receiver<LiveState>,
+    commands: mpsc::WeakUnboundedSender<(Command, Span)>,
+}
+
+impl WeakTableState {
+    /// Reads the latest rally state, `changed` waits for the next one
+    pub fn read_rally_state<T>(&mut self, read: impl FnOnce(&RallyState) -> T) -> T {
+        read(&self.live.borrow_and_update().rally_state)
+    }
+
+    /// Waits for the rally or the game to change since it was read last.
+    /// `false` once the table is gone.
+    pub async fn changed(&mut self) -> bool {
+        self.live.changed().await.is_ok()
+    }
+
+    pub fn min_reaction_time(&self) -> Duration {
+        self.live.borrow().settings.min_reaction_time()
+    }
+
+    /// Same as [`TableState::hit`], `None` if the table is gone
+    pub async fn hit(&self, side: Side, hitter: Option<Partner>) -> Option<HitOutcome> {
+        let commands = self.commands.upgrade()?;
+        ask(&commands, |reply| Command::Hit {
+            side,
+            hitter,
+            reply,
+        })
+        .await
+        .ok()
+    }
+}
+
+impl Serialize for TableState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let live = self.live.borrow();
+        let mut table = serializer.serialize_struct("TableState", 5)?;
+        table.serialize_field("rallyState", &live.rally_state)?;
+        table.serialize_field("gameState", &live.game_state)?;
+        table.serialize_field("players", &live.claims)?;
+        table.serialize_field("settings", &live.settings)?;
+        table.serialize_field("bots", &live.bots)?;
+        table.end()
+    }
 }
 
 impl TableState {
-    pub fn new(game_state: GameState, db_handle: TableDbSyncHandle) -> Self {
+    pub fn new(
+        game_state: GameState,
+        claims: Claims,
+        settings: MatchSettings,
+        db_handle: TableDbSyncHandle,
+    ) -> Self {
+        let actor = TableActor::new(game_state, claims, settings, db_handle);
+        let (live, finished, wins) = (actor.live(), actor.finished(), actor.wins());
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(actor.run(receiver));
         Self {
-            game_state: Arc::new(RwLock::new(game_state)),
-            rally_state: Arc::default(),
-            db_handle,
+            live,
+            finished,
+            wins,
+            commands,
+            audited: None,
         }
     }
 
-    pub async fn lose_point(&self, side: Side) {
-        let game_state = {
-            let mut game_state = self
-                .game_state
-                .write()
-                .expect("game_state write lock was poisoned");
-            let mut rally_state = self
-                .rally_state
-                .write()
-                .expect("rally_state write lock was poisoned");
-            game_state.score.lose_point(side);
-            game_state.server = game_state.server.flip();
-            rally_state.side = game_state.server;
+    /// Sends `command` to the table's actor, returns its answer
+    async fn ask<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T, TableStopped> {
+        ask(&self.commands, |reply| match &self.audited {
+            Some(states) => Command::Audited {
+                command: Box::new(command(reply)),
+                states: states.clone(),
+            },
+            None => command(reply),
+        })
+        .await
+    }
+
+    /// Handle whose changes are audited, along with where their game states end up
+    pub fn audited(&self) -> (Self, Arc<Mutex<AuditedStates>>) {
+        let states = Arc::<Mutex<AuditedStates>>::default();
+        let table_state = Self {
+            audited: Some(states.clone()),
+            ..self.clone()
+        };
+        (table_state, states)
+    }
+
+    /// Handle for tasks the table owns, like its bots, so they don't keep it alive
+    pub fn downgrade(&self) -> WeakTableState {
+        WeakTableState {
+            live: self.live.clone(),
+            commands: self.commands.downgrade(),
+        }
+    }
+
+    pub fn game_state_snapshot(&self) -> GameState {
+        self.live.borrow().game_state.clone()
+    }
+
+    /// Seats and queue, as of the latest change
+    pub fn claims(&self) -> Arc<Claims> {
+        self.live.borrow().claims.clone()
+    }
+
+    pub fn settings(&self) -> Arc<MatchSettings> {
+        self.live.borrow().settings.clone()
+    }
+
+    /// Reads the latest rally state, without waiting for commands in flight
+    pub fn read_rally_state<T>(&self, read: impl FnOnce(&RallyState) -> T) -> T {
+        read(&self.live.borrow().rally_state)
+    }
+
+    pub fn rally_in_progress(&self) -> bool {
+        self.read_rally_state(|rally_state| rally_state.first_hit_at.is_some())
+    }
+
+    /// `hitter` is the doubles partner who hit, `None` when anyone may hit for the partner due
+    #[instrument(skip(self), ret(level = "debug"))]
+    pub async fn hit(
+        &self,
+        side: Side,
+        hitter: Option<Partner>,
+    ) -> Result<HitOutcome, TableStopped> {
+        self.ask(|reply| Command::Hit {
+            side,
+            hitter,
+            reply,
+        })
+        .await
+    }
+
+    pub fn winner(&self) -> Option<Side> {
+        self.live.borrow().game_state.winner
+    }
+
+    /// Sees the final game state once the match is won. Closes when the match is dropped.
+    pub fn finished(&self) -> watch::Receiver<Option<GameState>> {
+        self.finished.clone()
+    }
+
+    /// Sees the winner of every win of the table since it was opened or restored, `None`
+    /// once the win is undone. A win undone and won again is a new one.
+    pub fn wins(&self) -> watch::Receiver<Vec<Option<Side>>> {
+        self.wins.clone()
+    }
+
+    /// Brings back the game state from before the last point - score, server and
+    /// longest rally. Ongoing rally is cancelled, a won match is open again.
+    /// `None` if there is nothing to undo.
+    pub async fn undo_point(&self, by: String) -> Result<Option<GameState>, TableStopped> {
+        self.ask(|reply| Command::UndoPoint { by, reply }).await
+    }
+
+    /// Ends the rally without anyone scoring, same server serves again
+    pub async fn cancel_rally(&self) -> Result<(), TableStopped> {
+        self.ask(Command::CancelRally).await
+    }
+
+    /// Replays the point - rally is cancelled, same server serves again.
+    /// `false` if there was no rally to replay.
+    pub async fn call_let(&self, by: String) -> Result<bool, TableStopped> {
+        self.ask(|reply| Command::CallLet { by, reply }).await
+    }
+
+    /// Player on `side` asks for a let of the ongoing rally.
+    /// `true` once both sides asked for the same rally.
+    pub async fn request_let(&self, side: Side) -> Result<bool, TableStopped> {
+        self.ask(|reply| Command::RequestLet { side, reply }).await
+    }
+
+    /// Stops the ball's countdown until `resume`. `false` if already paused.
+    pub async fn pause(&self) -> Result<bool, TableStopped> {
+        self.ask(Command::Pause).await
+    }
 
-            update_statistics(&mut game_state, &rally_state);
+    /// Restarts the countdown with the air time left when pausing. `false` if not paused.
+    pub async fn resume(&self) -> Result<bool, TableStopped> {
+        self.ask(Command::Resume).await
+    }
+
+    /// `None` winner discards the rally. `false` if there was no rally to end.
+    pub async fn end_rally(&self, winner: Option<Side>) -> Result<bool, TableStopped> {
+        self.ask(|reply| Command::EndRally { winner, reply }).await
+    }
 
-            rally_state.hit_timeout = None;
-            rally_state.first_hit_at = None;
-            rally_state.hit_count = 0;
+    /// Back to the initial state, players stay
+    pub async fn reset(&self) -> Result<(), TableStopped> {
+        self.ask(Command::Reset).await
+    }
+
+    /// Wins the match if the score is a winning one, or reopens a finished match if it isn't
+    pub async fn set_score(&self, score: Score, by: String) -> Result<(), TableStopped> {
+        self.ask(|reply| Command::SetScore { score, by, reply })
+            .await
+    }
+
+    /// `false` if a rally is in progress - server can't change mid-rally
+    pub async fn set_server(&self, server: Side) -> Result<bool, TableStopped> {
+        self.ask(|reply| Command::SetServer { server, reply }).await
+    }
+
+    /// From now on the match has its own settings, config defaults no longer apply
+    pub async fn set_settings(&self, settings: MatchSettings) -> Result<(), TableStopped> {
+        self.ask(|reply| Command::SetSettings { settings, reply })
+            .await
+    }
+
+    /// Returns the partner and token of the new claim, `None` if the side is already taken -
+    /// by a player or a bot. Doubles sides take two players. First claimant becomes the owner of the match.
+    pub async fn claim(
+        &self,
+        side: Side,
+        player: PlayerName,
+    ) -> Result<Option<(Partner, String)>, TableStopped> {
+        let token = Uuid::new_v4().simple().to_string();
+        let partner = self.claim_with_token(side, player, token.clone()).await?;
+        Ok(partner.map(|partner| (partner, token)))
+    }
+
+    /// Same as `claim`, for players who already have a token - e.g. from a tournament
+    pub async fn claim_with_token(
+        &self,
+        side: Side,
+        player: PlayerName,
+        token: String,
+    ) -> Result<Option<Partner>, TableStopped> {
+        let claim = Claim { player, token };
+        self.ask(|reply| Command::Claim { side, claim, reply })
+            .await
+    }
 
-            game_state.clone()
+    /// Puts the player at the end of the queue, returns their place counted from 1.
+    /// `None` if they already have a seat or wait for one.
+    pub async fn join_queue(
+        &self,
+        player: PlayerName,
+        token: String,
+    ) -> Result<Option<usize>, TableStopped> {
+        let claim = Claim { player, token };
+        self.ask(|reply| Command::JoinQueue { claim, reply }).await
+    }
+
+    /// Returns who left the queue
+    pub async fn leave_queue(&self, token: String) -> Result<Option<PlayerName>, TableStopped> {
+        self.ask(|reply| Command::LeaveQueue { token, reply }).await
+    }
+
+    /// Once the match is won, the winner stays on and the loser's seat goes to the first
+    /// challenger in the queue - bot or not - and the match starts over.
+    /// Returns the side the challenger took, `None` if nothing changed.
+    pub async fn next_challenger(&self) -> Result<Option<Side>, TableStopped> {
+        self.ask(Command::NextChallenger).await
+    }
+
+    /// Frees the seat, returns who had it
+    pub async fn kick(
+        &self,
+        side: Side,
+        partner: Partner,
+    ) -> Result<Option<PlayerName>, TableStopped> {
+        self.ask(|reply| Command::Kick {
+            side,
+            partner,
+            reply,
+        })
+        .await
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.live.borrow().game_state.mode
+    }
+
+    /// Pong side of wall matches, nobody can play it
+    pub fn is_wall(&self, side: Side) -> bool {
+        side == Side::Pong && self.mode() == GameMode::Wall
+    }
+
+    pub fn has_bot(&self, side: Side) -> bool {
+        self.live.borrow().bots.get(side).is_some()
+    }
+
+    /// Seats a bot on `side`, `play` starts its task once the side turns out to be free.
+    /// Returns the token that detaches it - the one sent, or a new one.
+    pub async fn attach_bot(
+        &self,
+        side: Side,
+        skill: BotSkill,
+        token: Option<String>,
+        referee: bool,
+        play: impl FnOnce() -> JoinHandle<()> + Send + 'static,
+    ) -> Result<Result<String, BotRefused>, TableStopped> {
+        let token = token.unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        let keeper = BotKeeper {
+            token: token.clone(),
+            referee,
         };
+        let attached = self
+            .ask(|reply| Command::AttachBot {
+                side,
+                skill,
+                keeper,
+                play: Box::new(play),
+                reply,
+            })
+            .await?;
+        Ok(attached.map(|()| token))
+    }
 
-        let db_handle = self.db_handle.clone();
-        tokio::spawn(async move {
-            if let Err(e) = db_handle.update_game_state(game_state).await {
-                error!("Error while updating game state in database: {e}")
-            }
-        });
+    /// Needs the token the bot was attached with, or a referee
+    pub async fn detach_bot(
+        &self,
+        side: Side,
+        token: Option<String>,
+        referee: bool,
+    ) -> Result<Result<(), BotRefused>, TableStopped> {
+        let keeper = BotKeeper {
+            token: token.unwrap_or_default(),
+            referee,
+        };
+        self.ask(|reply| Command::DetachBot {
+            side,
+            keeper,
+            reply,
+        })
+        .await
+    }
+
+    /// Stops bots of both sides
+    pub async fn remove_bots(&self) -> Result<(), TableStopped> {
+        self.ask(Command::RemoveBots).await
+    }
+
+    /// Partner of `side` the token belongs to. Hits without a matching token are only fine
+    /// while the player due to hit hasn't claimed their seat, `Err` has the one who did.
+    pub fn check_token(
+        &self,
+        side: Side,
+        token: Option<&str>,
+    ) -> Result<Option<Partner>, PlayerName> {
+        let live = self.live.borrow();
+        let due = live
+            .rally_state
+            .partners
+            .map_or(Partner::First, |partners| partners.get(side));
+        if let Some(partner) = token.and_then(|token| live.claims.partner_with_token(side, token)) {
+            return Ok(Some(partner));
+        }
+        match live.claims.seat(side, due) {
+            None => Ok(None),
+            Some(claim) => Err(claim.player.clone()),
+        }
     }
 }
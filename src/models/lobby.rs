@@ -0,0 +1,243 @@
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+
+use crate::database::TableUid;
+
+use super::{
+    game::Side,
+    player::PlayerName,
+    settings::{MAX_AIR_TIME_SECONDS, MAX_POINTS_TO_WIN},
+};
+
+/// Rating of players who haven't played a rated match yet
+pub const DEFAULT_RATING: i32 = 1500;
+/// How much a single match can move a rating
+const ELO_K: f64 = 32.;
+
+/// Lobby matches have to end, so the next one can be found
+pub const DEFAULT_POINTS_TO_WIN: usize = 11;
+
+/// What a player is looking for. Players are only paired with the same rules.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Preferences {
+    #[serde(default = "default_points_to_win")]
+    pub points_to_win: usize,
+    /// `None` is the server default
+    #[serde(default)]
+    pub air_time_seconds: Option<u64>,
+    /// result changes the ratings of both players
+    #[serde(default)]
+    pub rated: bool,
+    /// only opponents with a rating at most that far from the player's own
+    #[serde(default)]
+    pub max_rating_gap: Option<u32>,
+}
+
+fn default_points_to_win() -> usize {
+    DEFAULT_POINTS_TO_WIN
+}
+
+impl Preferences {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.points_to_win == 0 {
+            return Err("Points to win must be at least 1".to_string());
+        }
+        if self.points_to_win > MAX_POINTS_TO_WIN {
+            return Err(format!("Points to win must be at most {MAX_POINTS_TO_WIN}"));
+        }
+        if self.air_time_seconds == Some(0) {
+            return Err("Air time must be at least 1 second".to_string());
+        }
+        if self.air_time_seconds > Some(MAX_AIR_TIME_SECONDS) {
+            return Err(format!(
+                "Air time must be at most {MAX_AIR_TIME_SECONDS} seconds"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ratings are only looked up when they matter
+    pub fn needs_rating(&self) -> bool {
+        self.rated || self.max_rating_gap.is_some()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LobbyEntry {
+    pub ticket: String,
+    pub player: PlayerName,
+    /// claim token of the seat, once there is one
+    pub token: String,
+    pub preferences: Preferences,
+    pub rating: i32,
+    pub joined_at: Timestamp,
+}
+
+impl LobbyEntry {
+    fn accepts(&self, other: &LobbyEntry) -> bool {
+        self.preferences
+            .max_rating_gap
+            .is_none_or(|gap| self.rating.abs_diff(other.rating) <= gap)
+    }
+
+    /// Same rules, different players, and both fine with the other's rating
+    pub fn fits(&self, other: &LobbyEntry) -> bool {
+        let (mine, theirs) = (&self.preferences, &other.preferences);
+        self.player != other.player
+            && mine.points_to_win == theirs.points_to_win
+            && mine.air_time_seconds == theirs.air_time_seconds
+            && mine.rated == theirs.rated
+            && self.accepts(other)
+            && other.accepts(self)
+    }
+}
+
+#[derive(Clone, Serialize, PartialEq, Debug)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "status"
+)]
+pub enum TicketStatus {
+    Waiting,
+    /// hits on `side` need the token handed out when joining
+    Matched {
+        match_id: TableUid,
+        side: Side,
+        opponent: PlayerName,
+    },
+    /// nobody fitting showed up in time
+    Expired,
+    Left,
+}
+
+/// Players waiting for an opponent, longest waiting first
+#[derive(Default)]
+pub struct Lobby {
+    waiting: Vec<LobbyEntry>,
+}
+
+impl Lobby {
+    pub fn is_waiting(&self, player: &PlayerName) -> bool {
+        self.waiting.iter().any(|entry| &entry.player == player)
+    }
+
+    /// Takes the longest waiting player who fits, if any
+    pub fn take_opponent(&mut self, entry: &LobbyEntry) -> Option<LobbyEntry> {
+        let index = self.waiting.iter().position(|other| other.fits(entry))?;
+        Some(self.waiting.remove(index))
+    }
+
+    pub fn push(&mut self, entry: LobbyEntry) {
+        self.waiting.push(entry);
+    }
+
+    /// Back to the front of the line, when their match couldn't be opened
+    pub fn push_front(&mut self, entry: LobbyEntry) {
+        self.waiting.insert(0, entry);
+    }
+
+    pub fn remove(&mut self, ticket: &str) -> Option<LobbyEntry> {
+        let index = self
+            .waiting
+            .iter()
+            .position(|entry| entry.ticket == ticket)?;
+        Some(self.waiting.remove(index))
+    }
+}
+
+/// New ratings of the winner and the loser
+pub fn rate(winner: i32, loser: i32) -> (i32, i32) {
+    let expected = 1. / (1. + 10f64.powf((loser - winner) as f64 / 400.));
+    let change = (ELO_K * (1. - expected)).round() as i32;
+    (winner + change, loser - change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(player: &str, rating: i32, preferences: Preferences) -> LobbyEntry {
+        LobbyEntry {
+            ticket: player.to_string(),
+            player: PlayerName::try_from(player.to_string()).unwrap(),
+            token: String::new(),
+            preferences,
+            rating,
+            joined_at: Timestamp::UNIX_EPOCH,
+        }
+    }
+
+    fn casual() -> Preferences {
+        serde_json::from_str("{}").unwrap()
+    }
+
+    #[test]
+    fn only_same_rules_fit() {
+        let alice = entry("alice", 1500, casual());
+        assert!(alice.fits(&entry("bob", 2500, casual())));
+        assert!(!alice.fits(&entry("alice", 1500, casual())));
+
+        for other in [
+            Preferences {
+                points_to_win: 21,
+                ..casual()
+            },
+            Preferences {
+                air_time_seconds: Some(5),
+                ..casual()
+            },
+            Preferences {
+                rated: true,
+                ..casual()
+            },
+        ] {
+            assert!(!alice.fits(&entry("bob", 1500, other)));
+        }
+    }
+
+    #[test]
+    fn rating_gap_has_to_suit_both() {
+        let picky = Preferences {
+            max_rating_gap: Some(100),
+            ..casual()
+        };
+        let alice = entry("alice", 1500, picky.clone());
+        assert!(alice.fits(&entry("bob", 1600, casual())));
+        assert!(!alice.fits(&entry("bob", 1601, casual())));
+        assert!(entry("bob", 1400, casual()).fits(&alice));
+        assert!(!entry("bob", 1399, casual()).fits(&alice));
+    }
+
+    #[test]
+    fn longest_waiting_opponent_goes_first() {
+        let mut lobby = Lobby::default();
+        lobby.push(entry("alice", 1500, casual()));
+        lobby.push(entry(
+            "bob",
+            1500,
+            Preferences {
+                rated: true,
+                ..casual()
+            },
+        ));
+        lobby.push(entry("carol", 1500, casual()));
+
+        let dave = entry("dave", 1500, casual());
+        assert_eq!(lobby.take_opponent(&dave).unwrap().player.as_str(), "alice");
+        assert_eq!(lobby.take_opponent(&dave).unwrap().player.as_str(), "carol");
+        assert!(lobby.take_opponent(&dave).is_none());
+        assert!(lobby.remove("bob").is_some());
+        assert!(lobby.remove("bob").is_none());
+    }
+
+    #[test]
+    fn upsets_move_ratings_more() {
+        assert_eq!(rate(1500, 1500), (1516, 1484));
+        let (favorite, _) = rate(1700, 1300);
+        let (underdog, _) = rate(1300, 1700);
+        assert!(favorite - 1700 < underdog - 1300);
+        assert_eq!(rate(1300, 1700), (1329, 1671));
+    }
+}
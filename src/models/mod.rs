@@ -1,3 +1,15 @@
 pub mod game;
 
+pub mod achievements;
 pub mod application;
+pub mod bot;
+pub mod career;
+pub mod event;
+pub mod lobby;
+pub mod player;
+pub mod records;
+pub mod settings;
+pub mod stats;
+pub mod table_actor;
+pub mod tournament;
+pub mod webhooks;
@@ -0,0 +1,151 @@
+use std::{collections::VecDeque, fmt};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::auth::constant_time_eq;
+
+use super::game::{Partner, Side};
+
+const PLAYER_NAME_MAX_LENGTH: usize = 32;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Debug)]
+#[serde(try_from = "String")]
+pub struct PlayerName(String);
+
+impl PlayerName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for PlayerName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let is_valid = !name.is_empty()
+            && name.len() <= PLAYER_NAME_MAX_LENGTH
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if is_valid {
+            Ok(Self(name))
+        } else {
+            Err(format!(
+                "Invalid player name. Must be 1 to {PLAYER_NAME_MAX_LENGTH} characters long and contain only letters, digits, '_' and '-'."
+            ))
+        }
+    }
+}
+
+impl fmt::Display for PlayerName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Claim {
+    pub player: PlayerName,
+    pub token: String,
+}
+
+/// Only the name is public - the token is handed out once, to the claimant
+impl Serialize for Claim {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.player.serialize(serializer)
+    }
+}
+
+/// Second players of doubles teams
+#[derive(Clone, Default, Serialize, Debug)]
+pub struct Partners {
+    pub ping: Option<Claim>,
+    pub pong: Option<Claim>,
+}
+
+/// Unclaimed side can be hit by anyone
+#[derive(Clone, Default, Serialize, Debug)]
+pub struct Claims {
+    pub ping: Option<Claim>,
+    pub pong: Option<Claim>,
+    /// only claimed in doubles matches
+    pub partners: Partners,
+    /// first player to claim any side, stays even when they leave
+    pub owner: Option<PlayerName>,
+    /// challengers waiting for the seat of the next loser, first in line first
+    pub queue: VecDeque<Claim>,
+}
+
+impl Claims {
+    pub fn get(&self, side: Side) -> Option<&Claim> {
+        self.seat(side, Partner::First)
+    }
+
+    pub fn seat(&self, side: Side, partner: Partner) -> Option<&Claim> {
+        match (side, partner) {
+            (Side::Ping, Partner::First) => self.ping.as_ref(),
+            (Side::Pong, Partner::First) => self.pong.as_ref(),
+            (Side::Ping, Partner::Second) => self.partners.ping.as_ref(),
+            (Side::Pong, Partner::Second) => self.partners.pong.as_ref(),
+        }
+    }
+
+    /// Partner of `side` who claimed it with the token, if any
+    pub fn partner_with_token(&self, side: Side, token: &str) -> Option<Partner> {
+        [Partner::First, Partner::Second]
+            .into_iter()
+            .find(|&partner| {
+                self.seat(side, partner)
+                    .is_some_and(|claim| constant_time_eq(claim.token.as_bytes(), token.as_bytes()))
+            })
+    }
+
+    /// Side claimed with the token, if any
+    pub fn side_with_token(&self, token: &str) -> Option<(Side, &PlayerName)> {
+        [Side::Ping, Side::Pong].into_iter().find_map(|side| {
+            let partner = self.partner_with_token(side, token)?;
+            self.seat(side, partner).map(|claim| (side, &claim.player))
+        })
+    }
+
+    /// Whether the player has a seat or waits for one
+    pub fn has_player(&self, player: &PlayerName) -> bool {
+        [
+            &self.ping,
+            &self.pong,
+            &self.partners.ping,
+            &self.partners.pong,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&self.queue)
+        .any(|claim| &claim.player == player)
+    }
+
+    pub fn seat_slot(&mut self, side: Side, partner: Partner) -> &mut Option<Claim> {
+        match (side, partner) {
+            (Side::Ping, Partner::First) => &mut self.ping,
+            (Side::Pong, Partner::First) => &mut self.pong,
+            (Side::Ping, Partner::Second) => &mut self.partners.ping,
+            (Side::Pong, Partner::Second) => &mut self.partners.pong,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_names() {
+        for valid in ["alice", "Bob_2", "x", "a-b", &"a".repeat(32)] {
+            assert!(PlayerName::try_from(valid.to_string()).is_ok(), "{valid}");
+        }
+        for invalid in ["", "with space", "zoë", "<script>", &"a".repeat(33)] {
+            assert!(
+                PlayerName::try_from(invalid.to_string()).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt;
+
+use jiff::Timestamp;
+
+use super::{
+    event::{MatchEvent, SidePlayers},
+    game::{Score, Side},
+};
+
+/// Record categories, each held by a single match
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RecordKind {
+    /// hits
+    LongestRallyByHits,
+    /// milliseconds
+    LongestRallyByDuration,
+    /// points the winner was behind at worst
+    BiggestComeback,
+    /// milliseconds from the first serve to the winning point
+    FastestGame,
+    /// points played in a single game
+    MostPoints,
+}
+
+impl RecordKind {
+    pub const ALL: [RecordKind; 5] = [
+        RecordKind::LongestRallyByHits,
+        RecordKind::LongestRallyByDuration,
+        RecordKind::BiggestComeback,
+        RecordKind::FastestGame,
+        RecordKind::MostPoints,
+    ];
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        RecordKind::ALL
+            .into_iter()
+            .find(|known| known.to_string() == kind)
+    }
+
+    /// Fastest game is the only one where less is better
+    pub fn beats(&self, value: i64, current: i64) -> bool {
+        match self {
+            RecordKind::FastestGame => value < current,
+            _ => value > current,
+        }
+    }
+}
+
+impl fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordKind::LongestRallyByHits => write!(f, "longest_rally_by_hits"),
+            RecordKind::LongestRallyByDuration => write!(f, "longest_rally_by_duration"),
+            RecordKind::BiggestComeback => write!(f, "biggest_comeback"),
+            RecordKind::FastestGame => write!(f, "fastest_game"),
+            RecordKind::MostPoints => write!(f, "most_points"),
+        }
+    }
+}
+
+/// Records a single event could set, with their values. Once an admin set the score of the
+/// game (`score_set`), its points weren't all played and the score sets no record.
+pub fn event_candidates(event: &MatchEvent, score_set: bool) -> Vec<(RecordKind, i64)> {
+    let (hits, duration, score) = match event {
+        MatchEvent::Point {
+            rally_hits,
+            rally_duration,
+            score,
+            ..
+        } => (*rally_hits, *rally_duration, Some(score)),
+        MatchEvent::Streak {
+            returns,
+            rally_duration,
+            ..
+        } => (*returns, *rally_duration, None),
+        _ => return vec![],
+    };
+
+    let mut candidates = vec![(RecordKind::LongestRallyByHits, hits as i64)];
+    if let Some(duration) = duration {
+        candidates.push((
+            RecordKind::LongestRallyByDuration,
+            duration.as_millis() as i64,
+        ));
+    }
+    if let Some(score) = score.filter(|_| !score_set) {
+        candidates.push((RecordKind::MostPoints, (score.ping + score.pong) as i64));
+    }
+    candidates
+}
+
+/// Players the records set by `event` go to - who played the rally, or won and lost the game
+pub fn record_holders(event: &MatchEvent) -> SidePlayers {
+    match event {
+        MatchEvent::Point { players, .. } | MatchEvent::Streak { players, .. } => players.clone(),
+        MatchEvent::Won { ping, pong, .. } => SidePlayers {
+            ping: ping.as_ref().and_then(|side| side.player.clone()),
+            pong: pong.as_ref().and_then(|side| side.player.clone()),
+            ping_partner: ping.as_ref().and_then(|side| side.partner.clone()),
+            pong_partner: pong.as_ref().and_then(|side| side.partner.clone()),
+        },
+        _ => SidePlayers::default(),
+    }
+}
+
+/// Records a won game could set. `game` has events of the game before the win, oldest first.
+pub fn game_candidates(
+    game: &[(Timestamp, MatchEvent)],
+    won_at: Timestamp,
+    winner: Side,
+) -> Vec<(RecordKind, i64)> {
+    let behind = |score: &Score| match winner {
+        Side::Ping => score.pong as i64 - score.ping as i64,
+        Side::Pong => score.ping as i64 - score.pong as i64,
+    };
+    let mut candidates = vec![];
+
+    // scores the game went through - an undo takes back the ones after the score it returns to,
+    // a set score replaces all before it
+    let mut scores: Vec<&Score> = vec![];
+    for (_, event) in game {
+        match event {
+            MatchEvent::Point { score, .. } => scores.push(score),
+            MatchEvent::ScoreSet { score, .. } => scores = vec![score],
+            MatchEvent::Undo { score, .. } => {
+                while scores.last().is_some_and(|last| *last != score) {
+                    scores.pop();
+                }
+                if scores.is_empty() {
+                    scores.push(score);
+                }
+            }
+            _ => {}
+        }
+    }
+    let worst = scores.into_iter().map(behind).max();
+    if let Some(worst) = worst.filter(|&worst| worst > 0) {
+        candidates.push((RecordKind::BiggestComeback, worst));
+    }
+
+    // wasn't played from the start
+    if game
+        .iter()
+        .any(|(_, event)| matches!(event, MatchEvent::ScoreSet { .. }))
+    {
+        return candidates;
+    }
+
+    // the first rally started before its point was recorded
+    let started_at = game.iter().find_map(|(occurred_at, event)| match event {
+        MatchEvent::Point { rally_duration, .. } => {
+            Some(*occurred_at - rally_duration.unwrap_or_default())
+        }
+        _ => None,
+    });
+    if let Some(started_at) = started_at {
+        let duration = won_at.duration_since(started_at);
+        candidates.push((RecordKind::FastestGame, duration.as_millis() as i64));
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::SignedDuration;
+
+    use super::*;
+    use crate::models::{event::SideResult, game::MissReason, player::PlayerName};
+
+    fn point(loser: Side, ping: usize, pong: usize, rally_ms: Option<i64>) -> MatchEvent {
+        MatchEvent::Point {
+            loser,
+            reason: MissReason::Timeout,
+            rally_hits: 3,
+            rally_duration: rally_ms.map(SignedDuration::from_millis),
+            score: Score { ping, pong },
+            match_point_saved: false,
+            players: SidePlayers::default(),
+        }
+    }
+
+    fn at(seconds: i64) -> Timestamp {
+        Timestamp::from_second(seconds).unwrap()
+    }
+
+    #[test]
+    fn kinds_are_stored_by_name() {
+        for kind in RecordKind::ALL {
+            assert_eq!(RecordKind::parse(&kind.to_string()), Some(kind));
+        }
+        assert_eq!(RecordKind::parse("slowest_game"), None);
+    }
+
+    #[test]
+    fn only_fastest_game_wants_less() {
+        assert!(RecordKind::MostPoints.beats(3, 2));
+        assert!(!RecordKind::MostPoints.beats(2, 2));
+        assert!(RecordKind::FastestGame.beats(2, 3));
+    }
+
+    #[test]
+    fn records_go_to_the_players_of_the_event() {
+        let alice = PlayerName::try_from("alice".to_string()).unwrap();
+        let side = |player: Option<PlayerName>| SideResult {
+            player,
+            partner: None,
+            hits: 0,
+            reaction_time_total_ms: 0,
+            reactions: 0,
+        };
+        let won = MatchEvent::Won {
+            winner: Side::Ping,
+            score: Score::default(),
+            ping: Some(side(Some(alice.clone()))),
+            pong: Some(side(None)),
+            longest_rally: None,
+        };
+        assert_eq!(
+            record_holders(&won),
+            SidePlayers {
+                ping: Some(alice.clone()),
+                ..SidePlayers::default()
+            }
+        );
+
+        let bob = PlayerName::try_from("bob".to_string()).unwrap();
+        let won_in_doubles = MatchEvent::Won {
+            winner: Side::Pong,
+            score: Score::default(),
+            ping: Some(side(None)),
+            pong: Some(SideResult {
+                partner: Some(bob.clone()),
+                ..side(Some(alice.clone()))
+            }),
+            longest_rally: None,
+        };
+        assert_eq!(
+            record_holders(&won_in_doubles),
+            SidePlayers {
+                pong: Some(alice),
+                pong_partner: Some(bob),
+                ..SidePlayers::default()
+            }
+        );
+        // played before points had their players
+        assert_eq!(
+            record_holders(&point(Side::Ping, 1, 0, None)),
+            SidePlayers::default()
+        );
+    }
+
+    #[test]
+    fn points_can_set_rally_records_and_most_points() {
+        assert_eq!(
+            event_candidates(&point(Side::Ping, 4, 7, Some(1500)), false),
+            vec![
+                (RecordKind::LongestRallyByHits, 3),
+                (RecordKind::LongestRallyByDuration, 1500),
+                (RecordKind::MostPoints, 11),
+            ]
+        );
+        assert!(event_candidates(&MatchEvent::Reset, false).is_empty());
+    }
+
+    #[test]
+    fn set_score_is_not_played_points() {
+        assert_eq!(
+            event_candidates(&point(Side::Ping, 4, 7, Some(1500)), true),
+            vec![
+                (RecordKind::LongestRallyByHits, 3),
+                (RecordKind::LongestRallyByDuration, 1500),
+            ]
+        );
+    }
+
+    #[test]
+    fn comeback_and_game_time_come_from_the_whole_game() {
+        let game = vec![
+            (at(10), point(Side::Pong, 1, 0, Some(2000))),
+            (at(20), point(Side::Pong, 2, 0, None)),
+            (at(30), point(Side::Pong, 3, 0, None)),
+            (
+                at(35),
+                MatchEvent::Undo {
+                    by: "ref".to_string(),
+                    score: Score { ping: 2, pong: 0 },
+                },
+            ),
+            (at(40), point(Side::Ping, 2, 1, None)),
+        ];
+
+        // 3:0 was taken back, the winner was 2 behind at worst
+        assert_eq!(
+            game_candidates(&game, at(50), Side::Pong),
+            vec![
+                (RecordKind::BiggestComeback, 2),
+                (RecordKind::FastestGame, 42_000),
+            ]
+        );
+        // no comeback when the winner was never behind
+        assert_eq!(
+            game_candidates(&game, at(50), Side::Ping),
+            vec![(RecordKind::FastestGame, 42_000)]
+        );
+    }
+
+    #[test]
+    fn set_score_replaces_the_score_and_the_game_time() {
+        let set = |ping, pong| MatchEvent::ScoreSet {
+            by: "ref".to_string(),
+            score: Score { ping, pong },
+        };
+        let game = vec![
+            (at(10), point(Side::Pong, 1, 0, Some(2000))),
+            (at(20), set(5, 0)),
+            (at(30), set(1, 0)),
+            (at(40), point(Side::Ping, 1, 1, None)),
+        ];
+
+        // the mistake was corrected, the game was never played at 5:0
+        assert_eq!(
+            game_candidates(&game, at(50), Side::Pong),
+            vec![(RecordKind::BiggestComeback, 1)]
+        );
+
+        let undone = vec![
+            (at(10), point(Side::Pong, 1, 0, Some(2000))),
+            (at(20), set(5, 0)),
+            (
+                at(30),
+                MatchEvent::Undo {
+                    by: "ref".to_string(),
+                    score: Score { ping: 1, pong: 0 },
+                },
+            ),
+        ];
+        assert_eq!(
+            game_candidates(&undone, at(50), Side::Pong),
+            vec![(RecordKind::BiggestComeback, 1)]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::BALL_AIR_TIME_SECONDS;
+
+/// Longer than any sensible match, and small enough to keep score arithmetic far from overflow
+pub const MAX_POINTS_TO_WIN: usize = 1000;
+/// A ball in the air holds the match and a long-poll, an hour is plenty
+pub const MAX_AIR_TIME_SECONDS: u64 = 3600;
+
+/// What happens to a hit made before the minimum reaction time has passed
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TooEarly {
+    /// request fails, the ball stays in play
+    #[default]
+    Reject,
+    /// hitting side loses the point
+    Miss,
+}
+
+impl fmt::Display for TooEarly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TooEarly::Reject => write!(f, "reject"),
+            TooEarly::Miss => write!(f, "miss"),
+        }
+    }
+}
+
+impl FromStr for TooEarly {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(TooEarly::Reject),
+            "miss" => Ok(TooEarly::Miss),
+            other => Err(format!(
+                "unknown mode '{other}', expected 'reject' or 'miss'"
+            )),
+        }
+    }
+}
+
+/// Rules that can differ between matches
+#[derive(Clone, Serialize, Deserialize, PartialEq, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchSettings {
+    /// How long after a hit the other side has to wait before returning the ball. 0 disables it.
+    pub min_reaction_time_ms: u64,
+    pub too_early: TooEarly,
+    /// Match is won by the first side to reach it with a two point lead. `None` plays on forever.
+    #[serde(default)]
+    pub points_to_win: Option<usize>,
+    /// How long a side has to return the ball. `None` is `BALL_AIR_TIME_SECONDS`.
+    #[serde(default)]
+    pub air_time_seconds: Option<u64>,
+}
+
+impl MatchSettings {
+    pub fn min_reaction_time(&self) -> Duration {
+        Duration::from_millis(self.min_reaction_time_ms)
+    }
+
+    pub fn air_time(&self) -> Duration {
+        Duration::from_secs(self.air_time_seconds.unwrap_or(BALL_AIR_TIME_SECONDS))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(points) = self.points_to_win
+            && !(1..=MAX_POINTS_TO_WIN).contains(&points)
+        {
+            return Err(format!(
+                "pointsToWin must be between 1 and {MAX_POINTS_TO_WIN}"
+            ));
+        }
+        if let Some(seconds) = self.air_time_seconds
+            && !(1..=MAX_AIR_TIME_SECONDS).contains(&seconds)
+        {
+            return Err(format!(
+                "airTimeSeconds must be between 1 and {MAX_AIR_TIME_SECONDS}"
+            ));
+        }
+        if self.min_reaction_time() >= self.air_time() {
+            return Err("minReactionTimeMs must be shorter than the air time".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_must_leave_a_playable_match() {
+        for invalid in [
+            MatchSettings {
+                points_to_win: Some(0),
+                ..MatchSettings::default()
+            },
+            MatchSettings {
+                points_to_win: Some(MAX_POINTS_TO_WIN + 1),
+                ..MatchSettings::default()
+            },
+            MatchSettings {
+                air_time_seconds: Some(0),
+                ..MatchSettings::default()
+            },
+            MatchSettings {
+                air_time_seconds: Some(MAX_AIR_TIME_SECONDS + 1),
+                ..MatchSettings::default()
+            },
+            MatchSettings {
+                min_reaction_time_ms: 2000,
+                air_time_seconds: Some(2),
+                ..MatchSettings::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+        assert!(MatchSettings::default().validate().is_ok());
+    }
+}
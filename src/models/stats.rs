@@ -0,0 +1,295 @@
+use jiff::SignedDuration;
+use serde::{Deserialize, Serialize};
+
+use super::game::{LongestRally, MissReason, Side};
+
+/// Hits of the ongoing rally, only counted once it ends with a point
+#[derive(Clone, Default, Debug)]
+pub struct RallyHits {
+    /// hitting side and the time since the previous hit, if there was one
+    hits: Vec<(Side, Option<SignedDuration>)>,
+}
+
+impl RallyHits {
+    pub fn record(&mut self, side: Side, since_last_hit: Option<SignedDuration>) {
+        self.hits.push((side, since_last_hit));
+    }
+}
+
+/// How a rally ended in a match that counts points
+pub struct PointPlayed {
+    pub server: Side,
+    pub loser: Side,
+    pub reason: MissReason,
+    /// the loser would have won the game with this point
+    pub match_point: bool,
+}
+
+/// Width of the reaction time buckets, the median is as precise as that
+pub const REACTION_BUCKET_MS: u64 = 50;
+/// The last bucket takes every slower return as well
+const REACTION_BUCKETS: usize = 32;
+
+/// Reaction times of a side, summed up so the game state doesn't grow with the match
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionTimes {
+    pub count: usize,
+    pub total_ms: u64,
+    /// returns per `REACTION_BUCKET_MS` wide bucket, fastest first
+    buckets: [usize; REACTION_BUCKETS],
+}
+
+impl ReactionTimes {
+    pub fn record(&mut self, ms: u64) {
+        self.count += 1;
+        self.total_ms += ms;
+        let bucket = ((ms / REACTION_BUCKET_MS) as usize).min(REACTION_BUCKETS - 1);
+        self.buckets[bucket] += 1;
+    }
+
+    pub fn average_ms(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total_ms as f64 / self.count as f64)
+    }
+
+    /// Middle of the bucket the median falls in
+    pub fn median_ms(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let nth = |index: usize| {
+            let mut seen = 0;
+            let bucket = self
+                .buckets
+                .iter()
+                .position(|&times| {
+                    seen += times;
+                    seen > index
+                })
+                .expect("index is below the count");
+            (bucket as f64 + 0.5) * REACTION_BUCKET_MS as f64
+        };
+        Some((nth((self.count - 1) / 2) + nth(self.count / 2)) / 2.)
+    }
+}
+
+/// Counters of a single side
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SideStats {
+    pub hits: usize,
+    pub points_won: usize,
+    pub won_on_timeouts: usize,
+    pub won_on_wrong_side_hits: usize,
+    pub serves: usize,
+    pub serves_won: usize,
+    /// points won while the opponent was a point away from winning
+    #[serde(default)]
+    pub match_points_saved: usize,
+    /// time since the previous hit of every return. Game states saved with exact times
+    /// start over.
+    #[serde(default)]
+    pub reaction_times: ReactionTimes,
+}
+
+impl SideStats {
+    /// Sum of all reaction times in milliseconds, and how many there were
+    pub fn reaction_time_totals(&self) -> (u64, usize) {
+        (self.reaction_times.total_ms, self.reaction_times.count)
+    }
+
+    pub fn average_reaction_time_ms(&self) -> Option<f64> {
+        self.reaction_times.average_ms()
+    }
+
+    pub fn median_reaction_time_ms(&self) -> Option<f64> {
+        self.reaction_times.median_ms()
+    }
+
+    pub fn serve_win_percentage(&self) -> Option<f64> {
+        (self.serves > 0).then(|| self.serves_won as f64 * 100. / self.serves as f64)
+    }
+}
+
+/// Statistics of rallies that ended with a point, kept in the game state so undo and
+/// reset apply to them as well
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchStats {
+    pub ping: SideStats,
+    pub pong: SideStats,
+    pub rallies: usize,
+    pub rally_hits: usize,
+    /// unlike `longest_rally` of the game state, only duration counts
+    pub longest_by_duration: Option<LongestRally>,
+}
+
+impl MatchStats {
+    pub fn side(&self, side: Side) -> &SideStats {
+        match side {
+            Side::Ping => &self.ping,
+            Side::Pong => &self.pong,
+        }
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut SideStats {
+        match side {
+            Side::Ping => &mut self.ping,
+            Side::Pong => &mut self.pong,
+        }
+    }
+
+    /// `point` is `None` in wall matches, which have no points to win
+    pub fn end_rally(
+        &mut self,
+        hits: &RallyHits,
+        duration: Option<SignedDuration>,
+        point: Option<PointPlayed>,
+    ) {
+        self.rallies += 1;
+        self.rally_hits += hits.hits.len();
+        for &(side, since_last_hit) in &hits.hits {
+            let stats = self.side_mut(side);
+            stats.hits += 1;
+            if let Some(reaction_time) = since_last_hit {
+                let ms = reaction_time.as_millis().max(0) as u64;
+                stats.reaction_times.record(ms);
+            }
+        }
+
+        if let Some(duration) = duration
+            && self
+                .longest_by_duration
+                .as_ref()
+                .is_none_or(|longest| longest.duration < duration)
+        {
+            self.longest_by_duration = Some(LongestRally {
+                hit_count: hits.hits.len(),
+                duration,
+            });
+        }
+
+        if let Some(PointPlayed {
+            server,
+            loser,
+            reason,
+            match_point,
+        }) = point
+        {
+            let winner = self.side_mut(loser.flip());
+            winner.points_won += 1;
+            if match_point {
+                winner.match_points_saved += 1;
+            }
+            match reason {
+                MissReason::Timeout => winner.won_on_timeouts += 1,
+                MissReason::WrongSide => winner.won_on_wrong_side_hits += 1,
+                _ => {}
+            }
+            let server_stats = self.side_mut(server);
+            server_stats.serves += 1;
+            if server != loser {
+                server_stats.serves_won += 1;
+            }
+        }
+    }
+
+    pub fn average_rally_length(&self) -> Option<f64> {
+        (self.rallies > 0).then(|| self.rally_hits as f64 / self.rallies as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rally(hits: &[(Side, Option<i64>)]) -> RallyHits {
+        let mut rally = RallyHits::default();
+        for &(side, ms) in hits {
+            rally.record(side, ms.map(SignedDuration::from_millis));
+        }
+        rally
+    }
+
+    #[test]
+    fn points_and_serves_are_counted_for_the_right_side() {
+        let mut stats = MatchStats::default();
+        stats.end_rally(
+            &rally(&[(Side::Ping, None), (Side::Pong, Some(300))]),
+            Some(SignedDuration::from_millis(300)),
+            Some(PointPlayed {
+                server: Side::Ping,
+                loser: Side::Ping,
+                reason: MissReason::Timeout,
+                match_point: false,
+            }),
+        );
+        stats.end_rally(
+            &rally(&[]),
+            None,
+            Some(PointPlayed {
+                server: Side::Pong,
+                loser: Side::Ping,
+                reason: MissReason::WrongSide,
+                match_point: true,
+            }),
+        );
+
+        assert_eq!(stats.rallies, 2);
+        assert_eq!(stats.average_rally_length(), Some(1.));
+        assert_eq!((stats.ping.hits, stats.pong.hits), (1, 1));
+        assert_eq!(stats.pong.points_won, 2);
+        assert_eq!(stats.pong.won_on_timeouts, 1);
+        assert_eq!(stats.pong.won_on_wrong_side_hits, 1);
+        assert_eq!(stats.pong.match_points_saved, 1);
+        assert_eq!(stats.ping.serve_win_percentage(), Some(0.));
+        assert_eq!(stats.pong.serve_win_percentage(), Some(100.));
+    }
+
+    #[test]
+    fn reaction_times_have_average_and_median() {
+        let mut stats = MatchStats::default();
+        assert_eq!(stats.ping.median_reaction_time_ms(), None);
+
+        let hits: Vec<_> = [100, 200, 200, 900]
+            .into_iter()
+            .map(|ms| (Side::Ping, Some(ms)))
+            .collect();
+        stats.end_rally(&rally(&hits), None, None);
+        assert_eq!(stats.ping.average_reaction_time_ms(), Some(350.));
+        assert_eq!(stats.ping.median_reaction_time_ms(), Some(225.));
+
+        stats.end_rally(&rally(&[(Side::Ping, Some(1000))]), None, None);
+        assert_eq!(stats.ping.median_reaction_time_ms(), Some(225.));
+        stats.end_rally(&rally(&[(Side::Ping, Some(1000))]), None, None);
+        assert_eq!(stats.ping.median_reaction_time_ms(), Some(575.));
+    }
+
+    #[test]
+    fn slow_reactions_share_the_last_bucket() {
+        let mut reaction_times = ReactionTimes::default();
+        for ms in [60_000, 120_000, 180_000] {
+            reaction_times.record(ms);
+        }
+        assert_eq!(reaction_times.average_ms(), Some(120_000.));
+        assert_eq!(
+            reaction_times.median_ms(),
+            Some((REACTION_BUCKETS as f64 - 0.5) * REACTION_BUCKET_MS as f64)
+        );
+    }
+
+    #[test]
+    fn longest_rally_by_duration_ignores_hit_count() {
+        let mut stats = MatchStats::default();
+        let long = SignedDuration::from_secs(20);
+        stats.end_rally(&rally(&[(Side::Ping, None)]), Some(long), None);
+        stats.end_rally(
+            &rally(&[(Side::Ping, None), (Side::Pong, Some(100))]),
+            Some(SignedDuration::from_secs(1)),
+            None,
+        );
+
+        let longest = stats.longest_by_duration.unwrap();
+        assert_eq!((longest.hit_count, longest.duration), (1, long));
+    }
+}
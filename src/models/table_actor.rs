@@ -0,0 +1,1094 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use jiff::Timestamp;
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+    time::{Instant, sleep_until},
+};
+use tracing::{Span, instrument};
+
+use crate::auth::constant_time_eq;
+use crate::clock;
+use crate::database::TableDbSyncHandle;
+use crate::metrics::METRICS;
+
+use super::achievements;
+use super::bot::{Bot, BotRefused, BotSkill, BotSkills, Bots};
+use super::event::{MatchEvent, SidePlayers, SideResult};
+use super::game::{
+    AuditedStates, GameMode, GameState, HitOutcome, LongestRally, MissReason, Partner, RallyState,
+    Score, Side,
+};
+use super::player::{Claim, Claims, PlayerName};
+use super::settings::{MatchSettings, TooEarly};
+use super::stats::PointPlayed;
+
+/// How many points back can be undone
+const UNDO_LIMIT: usize = 10;
+
+/// Everything the table's actor owns, as it left it after its latest command
+#[derive(Clone, Default)]
+pub(super) struct LiveState {
+    pub game_state: GameState,
+    pub rally_state: RallyState,
+    pub claims: Arc<Claims>,
+    pub settings: Arc<MatchSettings>,
+    pub bots: BotSkills,
+}
+
+/// Sides that asked to replay the rally that started at given time
+#[derive(Default)]
+struct LetRequests {
+    rally_started_at: Option<Timestamp>,
+    sides: Vec<Side>,
+}
+
+/// Starts a bot's task, once its side is free
+pub(super) type PlayBot = Box<dyn FnOnce() -> JoinHandle<()> + Send>;
+
+/// Who attaches or detaches a bot
+pub(super) struct BotKeeper {
+    /// player token sent along, or a new one for the bot
+    pub token: String,
+    /// referees may take any seat, and detach any bot
+    pub referee: bool,
+}
+
+/// Changes of the table, each answered once it's applied and published
+pub(super) enum Command {
+    Hit {
+        side: Side,
+        hitter: Option<Partner>,
+        reply: oneshot::Sender<HitOutcome>,
+    },
+    UndoPoint {
+        by: String,
+        reply: oneshot::Sender<Option<GameState>>,
+    },
+    CallLet {
+        by: String,
+        reply: oneshot::Sender<bool>,
+    },
+    CancelRally(oneshot::Sender<()>),
+    Pause(oneshot::Sender<bool>),
+    Resume(oneshot::Sender<bool>),
+    EndRally {
+        winner: Option<Side>,
+        reply: oneshot::Sender<bool>,
+    },
+    Reset(oneshot::Sender<()>),
+    SetScore {
+        score: Score,
+        by: String,
+        reply: oneshot::Sender<()>,
+    },
+    SetServer {
+        server: Side,
+        reply: oneshot::Sender<bool>,
+    },
+    NextChallenger(oneshot::Sender<Option<Side>>),
+    SetSettings {
+        settings: MatchSettings,
+        reply: oneshot::Sender<()>,
+    },
+    Claim {
+        side: Side,
+        claim: Claim,
+        reply: oneshot::Sender<Option<Partner>>,
+    },
+    JoinQueue {
+        claim: Claim,
+        reply: oneshot::Sender<Option<usize>>,
+    },
+    LeaveQueue {
+        token: String,
+        reply: oneshot::Sender<Option<PlayerName>>,
+    },
+    Kick {
+        side: Side,
+        partner: Partner,
+        reply: oneshot::Sender<Option<PlayerName>>,
+    },
+    RequestLet {
+        side: Side,
+        reply: oneshot::Sender<bool>,
+    },
+    AttachBot {
+        side: Side,
+        skill: BotSkill,
+        keeper: BotKeeper,
+        play: PlayBot,
+        reply: oneshot::Sender<Result<(), BotRefused>>,
+    },
+    DetachBot {
+        side: Side,
+        keeper: BotKeeper,
+        reply: oneshot::Sender<Result<(), BotRefused>>,
+    },
+    RemoveBots(oneshot::Sender<()>),
+    /// `command`, with the game states around it kept in `states`
+    Audited {
+        command: Box<Command>,
+        states: Arc<Mutex<AuditedStates>>,
+    },
+}
+
+/// Sole owner of the state of a table - game, rally, seats, settings and bots. Commands are applied one at a time
+/// in the order they were sent, and the ball's countdown runs in the same loop - a hit and
+/// the timeout it beats can't race.
+pub(super) struct TableActor {
+    game_state: GameState,
+    rally_state: RallyState,
+    /// game states from before the recent points, latest last
+    undo_stack: Vec<GameState>,
+    /// span of the command that started the ball's countdown, so timeouts are traceable
+    countdown_span: Span,
+    claims: Arc<Claims>,
+    settings: Arc<MatchSettings>,
+    /// stopped along with the actor
+    bots: Bots,
+    let_requests: LetRequests,
+    /// of the audited command being applied, gets the game state after it
+    auditing: Option<Arc<Mutex<AuditedStates>>>,
+    live: watch::Sender<LiveState>,
+    /// final game state, once the match is won
+    finished: watch::Sender<Option<GameState>>,
+    /// winner of every win, `None` once it's undone
+    wins: watch::Sender<Vec<Option<Side>>>,
+    /// achievements the latest win unlocked first, taken back if it's undone
+    unlocked_by_win: Vec<(PlayerName, &'static str)>,
+    db_handle: TableDbSyncHandle,
+}
+
+impl TableActor {
+    pub fn new(
+        game_state: GameState,
+        claims: Claims,
+        settings: MatchSettings,
+        db_handle: TableDbSyncHandle,
+    ) -> Self {
+        let mut rally_state = RallyState::default();
+        rally_state.restart(&game_state);
+        let finished = game_state.winner.map(|_| game_state.clone());
+        let wins = game_state.winner.map(Some).into_iter().collect();
+        let (claims, settings) = (Arc::new(claims), Arc::new(settings));
+        Self {
+            live: watch::Sender::new(LiveState {
+                game_state: game_state.clone(),
+                rally_state: rally_state.clone(),
+                claims: claims.clone(),
+                settings: settings.clone(),
+                bots: BotSkills::default(),
+            }),
+            finished: watch::Sender::new(finished),
+            wins: watch::Sender::new(wins),
+            unlocked_by_win: vec![],
+            game_state,
+            rally_state,
+            undo_stack: vec![],
+            countdown_span: Span::none(),
+            claims,
+            settings,
+            bots: Bots::default(),
+            let_requests: LetRequests::default(),
+            auditing: None,
+            db_handle,
+        }
+    }
+
+    pub fn live(&self) -> watch::Receiver<LiveState> {
+        self.live.subscribe()
+    }
+
+    pub fn finished(&self) -> watch::Receiver<Option<GameState>> {
+        self.finished.subscribe()
+    }
+
+    pub fn wins(&self) -> watch::Receiver<Vec<Option<Side>>> {
+        self.wins.subscribe()
+    }
+
+    /// Runs until every handle of the table is gone
+    pub async fn run(mut self, mut commands: mpsc::UnboundedReceiver<(Command, Span)>) {
+        loop {
+            tokio::select! {
+                command = commands.recv() => {
+                    let Some((command, span)) = command else {
+                        break;
+                    };
+                    span.in_scope(|| self.apply(command));
+                }
+                () = countdown(self.rally_state.deadline) => {
+                    self.catch_up();
+                    self.publish();
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, command: Command) {
+        self.catch_up();
+        self.execute(command);
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::Hit {
+                side,
+                hitter,
+                reply,
+            } => {
+                let outcome = self.hit(side, hitter);
+                self.answer(reply, outcome);
+            }
+            Command::UndoPoint { by, reply } => {
+                let undone = self.undo_point(by);
+                self.answer(reply, undone);
+            }
+            Command::CallLet { by, reply } => {
+                let called = self.call_let(by);
+                self.answer(reply, called);
+            }
+            Command::CancelRally(reply) => {
+                self.cancel_rally();
+                self.answer(reply, ());
+            }
+            Command::Pause(reply) => {
+                let paused = self.pause();
+                self.answer(reply, paused);
+            }
+            Command::Resume(reply) => {
+                let resumed = self.resume();
+                self.answer(reply, resumed);
+            }
+            Command::EndRally { winner, reply } => {
+                let ended = self.end_rally(winner);
+                self.answer(reply, ended);
+            }
+            Command::Reset(reply) => {
+                self.reset();
+                self.answer(reply, ());
+            }
+            Command::SetScore { score, by, reply } => {
+                self.set_score(score, by);
+                self.answer(reply, ());
+            }
+            Command::SetServer { server, reply } => {
+                let changed = self.set_server(server);
+                self.answer(reply, changed);
+            }
+            Command::NextChallenger(reply) => {
+                let side = self.next_challenger();
+                self.answer(reply, side);
+            }
+            Command::SetSettings { settings, reply } => {
+                self.set_settings(settings);
+                self.answer(reply, ());
+            }
+            Command::Claim { side, claim, reply } => {
+                let partner = self.claim(side, claim);
+                self.answer(reply, partner);
+            }
+            Command::JoinQueue { claim, reply } => {
+                let place = self.join_queue(claim);
+                self.answer(reply, place);
+            }
+            Command::LeaveQueue { token, reply } => {
+                let player = self.leave_queue(&token);
+                self.answer(reply, player);
+            }
+            Command::Kick {
+                side,
+                partner,
+                reply,
+            } => {
+                let player = self.kick(side, partner);
+                self.answer(reply, player);
+            }
+            Command::RequestLet { side, reply } => {
+                let agreed = self.request_let(side);
+                self.answer(reply, agreed);
+            }
+            Command::AttachBot {
+                side,
+                skill,
+                keeper,
+                play,
+                reply,
+            } => {
+                let attached = self.attach_bot(side, skill, keeper, play);
+                self.answer(reply, attached);
+            }
+            Command::DetachBot {
+                side,
+                keeper,
+                reply,
+            } => {
+                let detached = self.detach_bot(side, &keeper);
+                self.answer(reply, detached);
+            }
+            Command::RemoveBots(reply) => {
+                self.bots = Bots::default();
+                self.answer(reply, ());
+            }
+            Command::Audited { command, states } => {
+                // overdue ball already dropped, it's part of the state before
+                states
+                    .lock()
+                    .expect("audited states lock was poisoned")
+                    .before
+                    .get_or_insert_with(|| self.game_state.clone());
+                self.auditing = Some(states);
+                self.execute(*command);
+            }
+        }
+    }
+
+    /// Readers see the change before the sender hears back, and so does the audit
+    fn answer<T>(&mut self, reply: oneshot::Sender<T>, answer: T) {
+        if let Some(states) = self.auditing.take() {
+            states
+                .lock()
+                .expect("audited states lock was poisoned")
+                .after = Some(self.game_state.clone());
+        }
+        self.publish();
+        // nobody waits for it if the request was dropped
+        let _ = reply.send(answer);
+    }
+
+    fn publish(&self) {
+        self.live.send_replace(LiveState {
+            game_state: self.game_state.clone(),
+            rally_state: self.rally_state.clone(),
+            claims: self.claims.clone(),
+            settings: self.settings.clone(),
+            bots: self.bots.skills(),
+        });
+    }
+
+    /// The ball drops at its deadline, before anything that comes after it
+    fn catch_up(&mut self) {
+        if self
+            .rally_state
+            .deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            let span = self.countdown_span.clone();
+            span.in_scope(|| self.time_out());
+        }
+    }
+
+    fn time_out(&mut self) {
+        let side = self.rally_state.side;
+        self.lose_point(side, MissReason::Timeout);
+    }
+
+    /// The side expected to hit next loses the point if the ball isn't returned within `air_time`
+    fn start_countdown(&mut self, air_time: Duration) {
+        self.rally_state.hit_timeout = Some(clock::now() + air_time);
+        self.rally_state.deadline = Some(Instant::now() + air_time);
+        self.countdown_span = Span::current();
+    }
+
+    fn hit(&mut self, side: Side, hitter: Option<Partner>) -> HitOutcome {
+        let settings = self.settings.clone();
+        let against_wall = self.game_state.mode == GameMode::Wall;
+        if self.game_state.winner.is_some() {
+            return HitOutcome::Over;
+        }
+        let rally_state = &mut self.rally_state;
+        if rally_state.paused {
+            return HitOutcome::Paused;
+        }
+
+        let too_early = rally_state
+            .last_hit_at
+            .is_some_and(|last_hit| clock::now() < last_hit + settings.min_reaction_time());
+        if side == rally_state.side && too_early && settings.too_early == TooEarly::Reject {
+            return HitOutcome::TooEarly;
+        }
+
+        let due = rally_state.partners.map(|partners| partners.get(side));
+        let miss_reason = if side != rally_state.side {
+            Some(MissReason::WrongSide)
+        } else if hitter.is_some() && due.is_some() && hitter != due {
+            Some(MissReason::WrongPartner)
+        } else if too_early {
+            Some(MissReason::TooEarly)
+        } else {
+            None
+        };
+        if let Some(reason) = miss_reason {
+            self.lose_point(side, reason);
+            return HitOutcome::Missed;
+        }
+
+        let rally_state = &mut self.rally_state;
+        let time_to_deadline = rally_state
+            .hit_timeout
+            .map(|deadline| deadline.duration_since(clock::now()).as_secs_f64());
+        METRICS.record_hit(side, time_to_deadline);
+
+        // wall returns the ball right away, to the same side
+        if !against_wall {
+            rally_state.side = side.flip();
+        }
+        if let Some(partners) = &mut rally_state.partners {
+            partners.pass(side);
+        }
+        let since_last_hit = rally_state
+            .last_hit_at
+            .map(|last_hit| clock::now().duration_since(last_hit));
+        rally_state.hits.record(side, since_last_hit);
+        rally_state.hit_count += 1;
+        rally_state.first_hit_at.get_or_insert_with(clock::now);
+        rally_state.last_hit_at = Some(clock::now());
+        self.start_countdown(settings.air_time());
+
+        HitOutcome::Returned(self.rally_state.side)
+    }
+
+    #[instrument(skip(self))]
+    fn lose_point(&mut self, side: Side, reason: MissReason) {
+        if self.game_state.winner.is_some() {
+            return;
+        }
+        let points_to_win = self.points_to_win();
+        let seated = self.seated();
+        self.push_undo(self.game_state.clone());
+
+        let game_state = &mut self.game_state;
+        let rally_state = &mut self.rally_state;
+        update_statistics(game_state, rally_state);
+        METRICS.record_point(side, reason, rally_state.hit_count);
+        let rally_duration = rally_state
+            .first_hit_at
+            .map(|start| clock::now().duration_since(start));
+        // the loser would have won the game with this point
+        let match_point = points_to_win.is_some_and(|points_to_win| {
+            let mut score = game_state.score.clone();
+            score.lose_point(side.flip());
+            score.winner(points_to_win) == Some(side)
+        });
+        let point = game_state.wall.is_none().then_some(PointPlayed {
+            server: game_state.server,
+            loser: side,
+            reason,
+            match_point,
+        });
+        let [(_, [ping, ping_partner]), (_, [pong, pong_partner])] = seated.clone();
+        let players = SidePlayers {
+            ping,
+            pong,
+            ping_partner,
+            pong_partner,
+        };
+        let hits = std::mem::take(&mut rally_state.hits);
+        game_state.stats.end_rally(&hits, rally_duration, point);
+        let event = match &mut game_state.wall {
+            // the wall never misses, and the player always serves
+            Some(wall) => {
+                wall.end_rally(rally_state.hit_count);
+                MatchEvent::Streak {
+                    reason,
+                    returns: rally_state.hit_count,
+                    rally_duration,
+                    best: wall.best_streak,
+                    players,
+                }
+            }
+            None => {
+                game_state.score.lose_point(side);
+                // receiver serves next, to the partner of the last server
+                let server = game_state.server;
+                if let Some(rotation) = &mut game_state.rotation {
+                    rotation.pass(server);
+                }
+                game_state.server = server.flip();
+                MatchEvent::Point {
+                    loser: side,
+                    reason,
+                    rally_hits: rally_state.hit_count,
+                    rally_duration,
+                    score: game_state.score.clone(),
+                    match_point_saved: match_point,
+                    players,
+                }
+            }
+        };
+        // streaks of wall matches go on
+        if game_state.wall.is_none() {
+            game_state.winner =
+                points_to_win.and_then(|points_to_win| game_state.score.winner(points_to_win));
+        }
+        rally_state.side = game_state.server;
+        rally_state.partners = game_state.rotation;
+
+        rally_state.hit_timeout = None;
+        rally_state.deadline = None;
+        rally_state.first_hit_at = None;
+        rally_state.last_hit_at = None;
+        rally_state.hit_count = 0;
+        rally_state.paused_air_time = None;
+
+        self.db_handle.update_game_state(self.game_state.clone());
+        self.save_event_unlocking(event, &seated);
+        if let Some(winner) = self.game_state.winner {
+            // the winning point can still be undone, until the table starts over
+            self.win(winner, &seated);
+        }
+    }
+
+    fn points_to_win(&self) -> Option<usize> {
+        self.settings.points_to_win
+    }
+
+    /// Players in the seats of each side, careers follow every one of them
+    fn seated(&self) -> Seated {
+        [Side::Ping, Side::Pong].map(|side| {
+            let players = [Partner::First, Partner::Second].map(|partner| {
+                self.claims
+                    .seat(side, partner)
+                    .map(|claim| claim.player.clone())
+            });
+            (side, players)
+        })
+    }
+
+    /// Announces the win of the game state's score, and lets the next challenger in
+    fn win(&mut self, winner: Side, seated: &Seated) {
+        let [ping, pong] = seated.clone().map(|(side, [player, partner])| {
+            SideResult::new(player, partner, self.game_state.stats.side(side))
+        });
+        let won = MatchEvent::Won {
+            winner,
+            score: self.game_state.score.clone(),
+            ping: Some(ping),
+            pong: Some(pong),
+            longest_rally: self
+                .game_state
+                .longest_rally
+                .as_ref()
+                .map(|longest| longest.hit_count),
+        };
+        self.unlocked_by_win = self.save_event_unlocking(won, seated);
+        self.finished.send_replace(Some(self.game_state.clone()));
+        self.wins.send_modify(|wins| wins.push(Some(winner)));
+        self.next_challenger();
+    }
+
+    /// Saves `event` along with achievements it unlocks for seated players. They are
+    /// announced right after it, the first time a player unlocks them. Returns who unlocked what.
+    fn save_event_unlocking(
+        &self,
+        event: MatchEvent,
+        seated: &Seated,
+    ) -> Vec<(PlayerName, &'static str)> {
+        let unlocked = achievements::unlocked(&event, &self.game_state);
+        self.db_handle.save_event(event);
+        let mut unlocks = vec![];
+        for (side, achievement) in unlocked {
+            let players = seated
+                .iter()
+                .filter(|(seat, _)| *seat == side)
+                .flat_map(|(_, players)| players.iter().flatten());
+            // doubles partners unlock it together
+            for player in players {
+                self.db_handle
+                    .unlock_achievement(player.clone(), achievement.id);
+                unlocks.push((player.clone(), achievement.id));
+            }
+        }
+        unlocks
+    }
+
+    fn push_undo(&mut self, game_state: GameState) {
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(game_state);
+    }
+
+    fn undo_point(&mut self, by: String) -> Option<GameState> {
+        let mut game_state = self.undo_stack.pop()?;
+        // lets are not points, they stay
+        game_state.lets = self.game_state.lets;
+        self.game_state = game_state;
+        self.rally_state.restart(&self.game_state);
+        // a won match is open again, its win no longer counts
+        let won = self.finished.borrow().is_some();
+        if won {
+            self.finished.send_replace(None);
+            self.wins.send_modify(|wins| {
+                if let Some(latest) = wins.last_mut() {
+                    *latest = None;
+                }
+            });
+        }
+
+        self.db_handle.update_game_state(self.game_state.clone());
+        self.db_handle.save_event(MatchEvent::Undo {
+            by,
+            score: self.game_state.score.clone(),
+        });
+        if won {
+            for (player, achievement) in std::mem::take(&mut self.unlocked_by_win) {
+                self.db_handle.revoke_achievement(player, achievement);
+            }
+        }
+        Some(self.game_state.clone())
+    }
+
+    fn cancel_rally(&mut self) {
+        self.rally_state.restart(&self.game_state);
+    }
+
+    fn call_let(&mut self, by: String) -> bool {
+        if self.rally_state.first_hit_at.is_none() {
+            return false;
+        }
+        let rally_hits = self.rally_state.hit_count;
+        self.rally_state.restart(&self.game_state);
+        self.game_state.lets += 1;
+
+        self.db_handle.update_game_state(self.game_state.clone());
+        self.db_handle
+            .save_event(MatchEvent::Let { by, rally_hits });
+        true
+    }
+
+    fn pause(&mut self) -> bool {
+        let rally_state = &mut self.rally_state;
+        if rally_state.paused {
+            return false;
+        }
+        rally_state.paused = true;
+        rally_state.deadline = None;
+        rally_state.paused_air_time = rally_state.hit_timeout.take().map(|deadline| {
+            // already overdue - the timeout fires right after resuming
+            Duration::try_from(deadline.duration_since(clock::now())).unwrap_or_default()
+        });
+        true
+    }
+
+    fn resume(&mut self) -> bool {
+        if !self.rally_state.paused {
+            return false;
+        }
+        self.rally_state.paused = false;
+        if let Some(air_time) = self.rally_state.paused_air_time.take() {
+            self.start_countdown(air_time);
+        }
+        true
+    }
+
+    fn end_rally(&mut self, winner: Option<Side>) -> bool {
+        if self.rally_state.first_hit_at.is_none() {
+            return false;
+        }
+        match winner {
+            Some(winner) => self.lose_point(winner.flip(), MissReason::Forced),
+            None => self.cancel_rally(),
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.game_state = GameState::new(self.game_state.mode);
+        self.undo_stack.clear();
+        self.finished.send_replace(None);
+        self.rally_state.restart(&self.game_state);
+        self.db_handle.update_game_state(self.game_state.clone());
+        self.db_handle.save_event(MatchEvent::Reset);
+    }
+
+    /// Manual changes can't be mixed with undoing points scored before them.
+    /// The score decides the winner as if it was played - it can win the match, or take
+    /// the win away from a finished one.
+    fn set_score(&mut self, score: Score, by: String) {
+        let won_before = self.game_state.winner.is_some();
+        self.game_state.score = score.clone();
+        if self.game_state.wall.is_none() {
+            self.game_state.winner = self
+                .points_to_win()
+                .and_then(|points_to_win| score.winner(points_to_win));
+        }
+        self.undo_stack.clear();
+        self.db_handle.update_game_state(self.game_state.clone());
+        self.db_handle
+            .save_event(MatchEvent::ScoreSet { by, score });
+
+        match (won_before, self.game_state.winner) {
+            (false, Some(winner)) => {
+                self.rally_state.restart(&self.game_state);
+                let seated = self.seated();
+                self.win(winner, &seated);
+            }
+            (true, Some(_)) => {
+                self.finished.send_replace(Some(self.game_state.clone()));
+            }
+            (true, None) => {
+                self.finished.send_replace(None);
+            }
+            (false, None) => {}
+        }
+    }
+
+    fn set_server(&mut self, server: Side) -> bool {
+        if self.rally_state.first_hit_at.is_some() {
+            return false;
+        }
+        self.game_state.server = server;
+        self.rally_state.side = server;
+        self.undo_stack.clear();
+        self.db_handle.update_game_state(self.game_state.clone());
+        true
+    }
+
+    fn next_challenger(&mut self) -> Option<Side> {
+        let loser = self.game_state.winner?.flip();
+        {
+            let claims = Arc::make_mut(&mut self.claims);
+            let challenger = claims.queue.pop_front()?;
+
+            self.bots.slot(loser).take();
+            if let Some(claim) = claims.seat_slot(loser, Partner::First).take() {
+                self.db_handle.delete_claim(loser, Partner::First, claim);
+            }
+            *claims.seat_slot(loser, Partner::First) = Some(challenger.clone());
+            self.db_handle.save_claim(loser, Partner::First, challenger);
+            self.db_handle.save_queue(claims.queue.clone());
+        }
+        self.reset();
+        Some(loser)
+    }
+
+    fn set_settings(&mut self, settings: MatchSettings) {
+        self.settings = Arc::new(settings.clone());
+        self.db_handle.save_settings(settings);
+    }
+
+    fn claim(&mut self, side: Side, claim: Claim) -> Option<Partner> {
+        let seats: &[Partner] = match self.game_state.mode {
+            GameMode::Doubles => &[Partner::First, Partner::Second],
+            _ => &[Partner::First],
+        };
+        if self.bots.get(side).is_some() {
+            return None;
+        }
+        let partner = *seats
+            .iter()
+            .find(|&&partner| self.claims.seat(side, partner).is_none())?;
+        let claims = Arc::make_mut(&mut self.claims);
+        if claims.owner.is_none() {
+            claims.owner = Some(claim.player.clone());
+            self.db_handle.save_owner(claim.player.clone());
+        }
+        *claims.seat_slot(side, partner) = Some(claim.clone());
+
+        self.db_handle.save_claim(side, partner, claim);
+        Some(partner)
+    }
+
+    fn join_queue(&mut self, claim: Claim) -> Option<usize> {
+        if self.claims.has_player(&claim.player) {
+            return None;
+        }
+        let claims = Arc::make_mut(&mut self.claims);
+        claims.queue.push_back(claim);
+        self.db_handle.save_queue(claims.queue.clone());
+        Some(claims.queue.len())
+    }
+
+    fn leave_queue(&mut self, token: &str) -> Option<PlayerName> {
+        let place = self
+            .claims
+            .queue
+            .iter()
+            .position(|claim| constant_time_eq(claim.token.as_bytes(), token.as_bytes()))?;
+        let claims = Arc::make_mut(&mut self.claims);
+        let claim = claims.queue.remove(place)?;
+        self.db_handle.save_queue(claims.queue.clone());
+        Some(claim.player)
+    }
+
+    fn kick(&mut self, side: Side, partner: Partner) -> Option<PlayerName> {
+        self.claims.seat(side, partner)?;
+        let claim = Arc::make_mut(&mut self.claims)
+            .seat_slot(side, partner)
+            .take()?;
+
+        let player = claim.player.clone();
+        self.db_handle.delete_claim(side, partner, claim);
+        Some(player)
+    }
+
+    fn request_let(&mut self, side: Side) -> bool {
+        let rally_started_at = self.rally_state.first_hit_at;
+        let requests = &mut self.let_requests;
+        // requests made during earlier rallies don't count
+        if requests.rally_started_at != rally_started_at {
+            *requests = LetRequests {
+                rally_started_at,
+                sides: vec![],
+            };
+        }
+        if !requests.sides.contains(&side) {
+            requests.sides.push(side);
+        }
+        requests.sides.len() == 2
+    }
+
+    /// Sides played by a bot can't get another one. Claimed seats are handed over to the
+    /// bot by their players - every partner of the side - or by a referee.
+    fn attach_bot(
+        &mut self,
+        side: Side,
+        skill: BotSkill,
+        keeper: BotKeeper,
+        play: PlayBot,
+    ) -> Result<(), BotRefused> {
+        if self.bots.get(side).is_some() {
+            return Err(BotRefused::AlreadyPlayed);
+        }
+        let seats = [Partner::First, Partner::Second];
+        for claim in seats
+            .iter()
+            .filter_map(|&partner| self.claims.seat(side, partner))
+        {
+            if !keeper.referee && !constant_time_eq(claim.token.as_bytes(), keeper.token.as_bytes())
+            {
+                return Err(BotRefused::Claimed(claim.player.clone()));
+            }
+        }
+        for partner in seats {
+            if self.claims.seat(side, partner).is_none() {
+                continue;
+            }
+            if let Some(claim) = Arc::make_mut(&mut self.claims)
+                .seat_slot(side, partner)
+                .take()
+            {
+                self.db_handle.delete_claim(side, partner, claim);
+            }
+        }
+        *self.bots.slot(side) = Some(Bot {
+            skill,
+            token: keeper.token,
+            task: play(),
+        });
+        Ok(())
+    }
+
+    fn detach_bot(&mut self, side: Side, keeper: &BotKeeper) -> Result<(), BotRefused> {
+        let bot = self.bots.get(side).ok_or(BotRefused::NoBot)?;
+        if !keeper.referee && !constant_time_eq(bot.token.as_bytes(), keeper.token.as_bytes()) {
+            return Err(BotRefused::NotKeeper);
+        }
+        self.bots.slot(side).take();
+        Ok(())
+    }
+}
+
+/// Players in the seats of each side, first partner first
+type Seated = [(Side, [Option<PlayerName>; 2]); 2];
+
+/// Waits for the ball to drop, forever if it's not in the air
+async fn countdown(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Updates longest rally - hit count based, duration only breaks ties.
+/// Longest rally by duration is tracked separately in the match stats.
+fn update_statistics(game_state: &mut GameState, rally_state: &RallyState) {
+    if let Some(start) = rally_state.first_hit_at {
+        let current_rally_time = clock::now().duration_since(start);
+        match &mut game_state.longest_rally {
+            None => {
+                game_state.longest_rally = Some(LongestRally {
+                    hit_count: rally_state.hit_count,
+                    duration: current_rally_time,
+                })
+            }
+            Some(longest_rally) => {
+                if longest_rally.hit_count < rally_state.hit_count {
+                    longest_rally.duration = current_rally_time;
+                    longest_rally.hit_count = rally_state.hit_count
+                } else if longest_rally.hit_count == rally_state.hit_count
+                    && longest_rally.duration < current_rally_time
+                {
+                    longest_rally.duration = current_rally_time
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::BALL_AIR_TIME_SECONDS;
+    use crate::tests::utils::mock_clock;
+
+    const AIR_TIME: Duration = Duration::from_secs(BALL_AIR_TIME_SECONDS);
+
+    fn actor() -> TableActor {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        TableActor::new(
+            GameState::new(GameMode::Classic),
+            Claims::default(),
+            MatchSettings::default(),
+            TableDbSyncHandle::new(0, &pool, None),
+        )
+    }
+
+    fn ask<T>(actor: &mut TableActor, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> T {
+        let (reply, mut answer) = oneshot::channel();
+        actor.apply(command(reply));
+        answer.try_recv().unwrap()
+    }
+
+    fn hit(actor: &mut TableActor, side: Side) -> HitOutcome {
+        ask(actor, |reply| Command::Hit {
+            side,
+            hitter: None,
+            reply,
+        })
+    }
+
+    /// Moves both clocks, without giving the actor's loop a chance to run
+    async fn wait(duration: Duration) {
+        mock_clock::advance(duration);
+        tokio::time::advance(duration).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hit_after_the_deadline_comes_after_the_timeout() {
+        let mut actor = actor();
+        assert!(matches!(
+            hit(&mut actor, Side::Ping),
+            HitOutcome::Returned(Side::Pong)
+        ));
+
+        wait(AIR_TIME + Duration::from_millis(1)).await;
+        // pong lost the point and serves the next rally
+        assert!(matches!(
+            hit(&mut actor, Side::Pong),
+            HitOutcome::Returned(Side::Ping)
+        ));
+        assert_eq!(actor.game_state.score, Score { ping: 1, pong: 0 });
+        assert_eq!(actor.rally_state.hit_count, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hit_before_the_deadline_keeps_the_ball_in_play() {
+        let mut actor = actor();
+        hit(&mut actor, Side::Ping);
+
+        wait(AIR_TIME - Duration::from_millis(1)).await;
+        assert!(matches!(
+            hit(&mut actor, Side::Pong),
+            HitOutcome::Returned(Side::Ping)
+        ));
+        assert_eq!(actor.game_state.score, Score::default());
+        assert_eq!(actor.rally_state.deadline, Some(Instant::now() + AIR_TIME));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_ball_keeps_its_air_time() {
+        let mut actor = actor();
+        hit(&mut actor, Side::Ping);
+        wait(Duration::from_secs(1)).await;
+        assert!(ask(&mut actor, Command::Pause));
+
+        wait(AIR_TIME * 2).await;
+        assert!(matches!(hit(&mut actor, Side::Pong), HitOutcome::Paused));
+        assert!(ask(&mut actor, Command::Resume));
+        assert_eq!(actor.game_state.score, Score::default());
+        assert_eq!(
+            actor.rally_state.deadline,
+            Some(Instant::now() + AIR_TIME - Duration::from_secs(1))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn undo_brings_back_a_point_lost_on_timeout() {
+        let mut actor = actor();
+        let published = actor.live();
+        hit(&mut actor, Side::Ping);
+        wait(AIR_TIME).await;
+
+        // any command drops the ball first once it's overdue
+        ask(&mut actor, Command::CancelRally);
+        assert_eq!(
+            published.borrow().game_state.score,
+            Score { ping: 1, pong: 0 }
+        );
+        let undone = ask(&mut actor, |reply| Command::UndoPoint {
+            by: "referee".to_string(),
+            reply,
+        });
+        assert_eq!(
+            undone.map(|game_state| game_state.score),
+            Some(Score::default())
+        );
+        assert_eq!(published.borrow().game_state.server, Side::Ping);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn audited_state_before_has_the_overdue_point() {
+        let mut actor = actor();
+        hit(&mut actor, Side::Ping);
+        wait(AIR_TIME).await;
+
+        let states = Arc::<Mutex<AuditedStates>>::default();
+        ask(&mut actor, |reply| Command::Audited {
+            command: Box::new(Command::SetScore {
+                score: Score { ping: 5, pong: 3 },
+                by: "referee".to_string(),
+                reply,
+            }),
+            states: states.clone(),
+        });
+        let states = states.lock().unwrap();
+        let score = |game_state: &Option<GameState>| game_state.as_ref().unwrap().score.clone();
+        assert_eq!(score(&states.before), Score { ping: 1, pong: 0 });
+        assert_eq!(score(&states.after), Score { ping: 5, pong: 3 });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn winning_point_can_be_undone() {
+        let mut actor = actor();
+        Arc::make_mut(&mut actor.settings).points_to_win = Some(1);
+        let finished = actor.finished.subscribe();
+        let wins = actor.wins.subscribe();
+        // pong hits out of turn, serves and hits out of turn again
+        hit(&mut actor, Side::Pong);
+        hit(&mut actor, Side::Pong);
+        hit(&mut actor, Side::Pong);
+        assert_eq!(actor.game_state.winner, Some(Side::Ping));
+        assert!(finished.borrow().is_some());
+        assert_eq!(*wins.borrow(), vec![Some(Side::Ping)]);
+
+        let undone = ask(&mut actor, |reply| Command::UndoPoint {
+            by: "referee".to_string(),
+            reply,
+        });
+        assert_eq!(
+            undone.map(|game_state| game_state.score),
+            Some(Score { ping: 1, pong: 0 })
+        );
+        assert_eq!(actor.game_state.winner, None);
+        assert!(finished.borrow().is_none());
+        // watchers still see there was a win, and that it's undone
+        assert_eq!(*wins.borrow(), vec![None]);
+    }
+}
@@ -0,0 +1,640 @@
+use std::{cmp::Reverse, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::database::TableUid;
+
+use super::{
+    game::{Score, Side},
+    player::PlayerName,
+};
+
+pub const MAX_PLAYERS: usize = 32;
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormat {
+    SingleElimination,
+    /// losers get a second chance in the losers bracket, single grand final
+    DoubleElimination,
+    /// everyone plays everyone once
+    RoundRobin,
+}
+
+impl fmt::Display for TournamentFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TournamentFormat::SingleElimination => write!(f, "single_elimination"),
+            TournamentFormat::DoubleElimination => write!(f, "double_elimination"),
+            TournamentFormat::RoundRobin => write!(f, "round_robin"),
+        }
+    }
+}
+
+impl FromStr for TournamentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single_elimination" => Ok(TournamentFormat::SingleElimination),
+            "double_elimination" => Ok(TournamentFormat::DoubleElimination),
+            "round_robin" => Ok(TournamentFormat::RoundRobin),
+            other => Err(format!("unknown tournament format '{other}'")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Bracket {
+    /// whole single elimination and round robin, winners bracket of double elimination
+    Main,
+    /// players with a single loss, double elimination only
+    Losers,
+    /// winners of both brackets, double elimination only
+    Final,
+}
+
+/// Where a player of a tournament match comes from
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Slot {
+    Player(PlayerName),
+    /// by match number
+    WinnerOf(usize),
+    LoserOf(usize),
+    /// nobody, the other player goes through
+    Bye,
+}
+
+/// What's known about a slot so far
+#[derive(PartialEq, Debug)]
+enum Filled {
+    Player(PlayerName),
+    Bye,
+    Pending,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Outcome {
+    /// `None` only when both players were byes
+    pub winner: Option<PlayerName>,
+    pub loser: Option<PlayerName>,
+    /// `None` for walkovers
+    pub score: Option<Score>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentMatch {
+    pub bracket: Bracket,
+    /// counted from 1 in each bracket
+    pub round: usize,
+    pub ping: Slot,
+    pub pong: Slot,
+    /// created once both players are known
+    pub table: Option<TableUid>,
+    pub outcome: Option<Outcome>,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Standing {
+    pub player: PlayerName,
+    /// walkovers not included
+    pub played: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub points_for: usize,
+    pub points_against: usize,
+    /// out of an elimination tournament, or didn't win a finished one
+    pub eliminated: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Tournament {
+    pub id: i64,
+    pub name: String,
+    pub format: TournamentFormat,
+    /// best seed first
+    pub players: Vec<PlayerName>,
+    pub points_to_win: usize,
+    /// referenced by their index
+    pub matches: Vec<TournamentMatch>,
+}
+
+impl Tournament {
+    pub fn validate_players(players: &[PlayerName]) -> Result<(), String> {
+        if !(2..=MAX_PLAYERS).contains(&players.len()) {
+            return Err(format!("Tournament needs 2 to {MAX_PLAYERS} players"));
+        }
+        if let Some(duplicate) = players
+            .iter()
+            .enumerate()
+            .find_map(|(i, player)| players[..i].contains(player).then_some(player))
+        {
+            return Err(format!("Player {duplicate} is listed twice"));
+        }
+        Ok(())
+    }
+
+    /// Matches of the whole tournament, later rounds waiting for the results of earlier ones
+    pub fn generate_matches(
+        format: TournamentFormat,
+        players: &[PlayerName],
+    ) -> Vec<TournamentMatch> {
+        let mut matches = vec![];
+        match format {
+            TournamentFormat::SingleElimination => {
+                main_bracket(&mut matches, players);
+            }
+            TournamentFormat::DoubleElimination => {
+                let main_rounds = main_bracket(&mut matches, players);
+                let champion = losers_bracket(&mut matches, &main_rounds);
+                let main_final = *main_rounds.last().and_then(|round| round.first()).unwrap();
+                push_match(
+                    &mut matches,
+                    Bracket::Final,
+                    1,
+                    Slot::WinnerOf(main_final),
+                    champion,
+                );
+            }
+            TournamentFormat::RoundRobin => round_robin(&mut matches, players),
+        }
+        matches
+    }
+
+    fn resolve(&self, slot: &Slot) -> Filled {
+        let (number, winner) = match slot {
+            Slot::Player(player) => return Filled::Player(player.clone()),
+            Slot::Bye => return Filled::Bye,
+            Slot::WinnerOf(number) => (*number, true),
+            Slot::LoserOf(number) => (*number, false),
+        };
+        match &self.matches[number].outcome {
+            None => Filled::Pending,
+            Some(outcome) => {
+                let player = if winner {
+                    &outcome.winner
+                } else {
+                    &outcome.loser
+                };
+                player.clone().map_or(Filled::Bye, Filled::Player)
+            }
+        }
+    }
+
+    /// `None` until the slot is decided, or if it's a bye
+    pub fn player_in(&self, slot: &Slot) -> Option<PlayerName> {
+        match self.resolve(slot) {
+            Filled::Player(player) => Some(player),
+            Filled::Bye | Filled::Pending => None,
+        }
+    }
+
+    /// Ping and pong player, once both are known
+    pub fn players_of(&self, number: usize) -> Option<(PlayerName, PlayerName)> {
+        let game = &self.matches[number];
+        match (self.resolve(&game.ping), self.resolve(&game.pong)) {
+            (Filled::Player(ping), Filled::Player(pong)) => Some((ping, pong)),
+            _ => None,
+        }
+    }
+
+    /// Moves players past byes, returns matches that can be played but have no table yet
+    pub fn advance(&mut self) -> Vec<usize> {
+        let mut walkovers = true;
+        while walkovers {
+            walkovers = false;
+            for number in 0..self.matches.len() {
+                let game = &self.matches[number];
+                if game.outcome.is_some() || game.table.is_some() {
+                    continue;
+                }
+                let winner = match (self.resolve(&game.ping), self.resolve(&game.pong)) {
+                    (Filled::Player(player), Filled::Bye)
+                    | (Filled::Bye, Filled::Player(player)) => Some(player),
+                    (Filled::Bye, Filled::Bye) => None,
+                    _ => continue,
+                };
+                self.matches[number].outcome = Some(Outcome {
+                    winner,
+                    loser: None,
+                    score: None,
+                });
+                walkovers = true;
+            }
+        }
+
+        (0..self.matches.len())
+            .filter(|&number| {
+                let game = &self.matches[number];
+                game.outcome.is_none() && game.table.is_none() && self.players_of(number).is_some()
+            })
+            .collect()
+    }
+
+    /// `false` if the match can't have a result yet, or has one already
+    pub fn record_result(&mut self, number: usize, winner: Side, score: Score) -> bool {
+        if self.matches[number].outcome.is_some() {
+            return false;
+        }
+        let Some((ping, pong)) = self.players_of(number) else {
+            return false;
+        };
+        let (winner, loser) = match winner {
+            Side::Ping => (ping, pong),
+            Side::Pong => (pong, ping),
+        };
+        self.matches[number].outcome = Some(Outcome {
+            winner: Some(winner),
+            loser: Some(loser),
+            score: Some(score),
+        });
+        true
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.matches.iter().all(|game| game.outcome.is_some())
+    }
+
+    pub fn champion(&self) -> Option<PlayerName> {
+        match self.format {
+            TournamentFormat::RoundRobin => self
+                .is_finished()
+                .then(|| self.standings().swap_remove(0).player),
+            // final is always the last match
+            _ => self.matches.last()?.outcome.as_ref()?.winner.clone(),
+        }
+    }
+
+    /// Best first - by wins, then point difference, then seed
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self
+            .players
+            .iter()
+            .map(|player| Standing {
+                player: player.clone(),
+                played: 0,
+                wins: 0,
+                losses: 0,
+                points_for: 0,
+                points_against: 0,
+                eliminated: false,
+            })
+            .collect();
+        for outcome in self.matches.iter().filter_map(|game| game.outcome.as_ref()) {
+            // walkovers only move players on
+            let (Some(winner), Some(loser), Some(score)) =
+                (&outcome.winner, &outcome.loser, &outcome.score)
+            else {
+                continue;
+            };
+            let (won, lost) = (score.ping.max(score.pong), score.ping.min(score.pong));
+            for standing in &mut standings {
+                if &standing.player == winner {
+                    standing.played += 1;
+                    standing.wins += 1;
+                    standing.points_for += won;
+                    standing.points_against += lost;
+                } else if &standing.player == loser {
+                    standing.played += 1;
+                    standing.losses += 1;
+                    standing.points_for += lost;
+                    standing.points_against += won;
+                }
+            }
+        }
+
+        let lives = match self.format {
+            TournamentFormat::SingleElimination => Some(1),
+            TournamentFormat::DoubleElimination => Some(2),
+            TournamentFormat::RoundRobin => None,
+        };
+        // round robin champion is decided by the standings themselves
+        let champion = lives.and_then(|_| self.champion());
+        let finished = self.is_finished();
+        for standing in &mut standings {
+            let is_champion = champion.as_ref() == Some(&standing.player);
+            standing.eliminated = lives.is_some_and(|lives| standing.losses >= lives)
+                || (finished && lives.is_some() && !is_champion);
+        }
+
+        // stable, so seeds break the remaining ties
+        standings.sort_by_key(|standing| {
+            (
+                Reverse(champion.as_ref() == Some(&standing.player)),
+                standing.eliminated,
+                Reverse(standing.wins),
+                Reverse(standing.points_for as i64 - standing.points_against as i64),
+            )
+        });
+        standings
+    }
+}
+
+fn push_match(
+    matches: &mut Vec<TournamentMatch>,
+    bracket: Bracket,
+    round: usize,
+    ping: Slot,
+    pong: Slot,
+) -> usize {
+    matches.push(TournamentMatch {
+        bracket,
+        round,
+        ping,
+        pong,
+        table: None,
+        outcome: None,
+    });
+    matches.len() - 1
+}
+
+/// Bracket positions of seeds, so the best seeds meet as late as possible
+fn seed_positions(size: usize) -> Vec<usize> {
+    let mut positions = vec![0];
+    while positions.len() < size {
+        let len = positions.len() * 2;
+        positions = positions
+            .iter()
+            .flat_map(|&seed| [seed, len - 1 - seed])
+            .collect();
+    }
+    positions
+}
+
+/// Returns match numbers of each round, top seeds get the byes
+fn main_bracket(matches: &mut Vec<TournamentMatch>, players: &[PlayerName]) -> Vec<Vec<usize>> {
+    let seed = |position: usize| {
+        players
+            .get(position)
+            .map_or(Slot::Bye, |player| Slot::Player(player.clone()))
+    };
+    let mut round: Vec<usize> = seed_positions(players.len().next_power_of_two())
+        .chunks(2)
+        .map(|pair| push_match(matches, Bracket::Main, 1, seed(pair[0]), seed(pair[1])))
+        .collect();
+    let mut rounds = vec![round.clone()];
+    while round.len() > 1 {
+        round = round
+            .chunks(2)
+            .map(|pair| {
+                push_match(
+                    matches,
+                    Bracket::Main,
+                    rounds.len() + 1,
+                    Slot::WinnerOf(pair[0]),
+                    Slot::WinnerOf(pair[1]),
+                )
+            })
+            .collect();
+        rounds.push(round.clone());
+    }
+    rounds
+}
+
+/// Losers of each main round drop in one by one. Returns where the winner of the bracket comes from.
+fn losers_bracket(matches: &mut Vec<TournamentMatch>, main_rounds: &[Vec<usize>]) -> Slot {
+    fn pair_up(
+        matches: &mut Vec<TournamentMatch>,
+        round: &mut usize,
+        slots: Vec<Slot>,
+    ) -> Vec<Slot> {
+        *round += 1;
+        slots
+            .chunks(2)
+            .map(|pair| {
+                Slot::WinnerOf(push_match(
+                    matches,
+                    Bracket::Losers,
+                    *round,
+                    pair[0].clone(),
+                    pair[1].clone(),
+                ))
+            })
+            .collect()
+    }
+
+    let mut round = 0;
+    let mut survivors: Vec<Slot> = main_rounds[0].iter().map(|&n| Slot::LoserOf(n)).collect();
+    if survivors.len() > 1 {
+        survivors = pair_up(matches, &mut round, survivors);
+    }
+    for main_round in &main_rounds[1..] {
+        // survivors face the players who just dropped down
+        let dropped = main_round.iter().map(|&n| Slot::LoserOf(n));
+        let slots = survivors
+            .into_iter()
+            .zip(dropped)
+            .flat_map(|(survivor, dropped)| [survivor, dropped])
+            .collect();
+        survivors = pair_up(matches, &mut round, slots);
+        if survivors.len() > 1 {
+            survivors = pair_up(matches, &mut round, survivors);
+        }
+    }
+    survivors.remove(0)
+}
+
+/// Circle method - one player stays put, the others rotate around them
+fn round_robin(matches: &mut Vec<TournamentMatch>, players: &[PlayerName]) {
+    let mut seats: Vec<Slot> = players
+        .iter()
+        .map(|player| Slot::Player(player.clone()))
+        .collect();
+    if seats.len() % 2 == 1 {
+        seats.push(Slot::Bye);
+    }
+    let n = seats.len();
+    for round in 1..n {
+        for i in 0..n / 2 {
+            let (ping, pong) = (&seats[i], &seats[n - 1 - i]);
+            // sitting out isn't a match
+            if *ping != Slot::Bye && *pong != Slot::Bye {
+                push_match(matches, Bracket::Main, round, ping.clone(), pong.clone());
+            }
+        }
+        seats[1..].rotate_right(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn players(count: usize) -> Vec<PlayerName> {
+        (0..count)
+            .map(|i| PlayerName::try_from(format!("p{i}")).unwrap())
+            .collect()
+    }
+
+    fn tournament(format: TournamentFormat, count: usize) -> Tournament {
+        let players = players(count);
+        Tournament {
+            id: 1,
+            name: "test".to_string(),
+            format,
+            matches: Tournament::generate_matches(format, &players),
+            players,
+            points_to_win: 11,
+        }
+    }
+
+    fn win(tournament: &mut Tournament, number: usize, winner: Side) {
+        let score = match winner {
+            Side::Ping => Score { ping: 11, pong: 5 },
+            Side::Pong => Score { ping: 5, pong: 11 },
+        };
+        assert!(tournament.record_result(number, winner, score));
+    }
+
+    fn names(players: Option<(PlayerName, PlayerName)>) -> Option<(String, String)> {
+        players.map(|(ping, pong)| (ping.to_string(), pong.to_string()))
+    }
+
+    #[test]
+    fn top_seeds_get_the_byes() {
+        let mut tournament = tournament(TournamentFormat::SingleElimination, 5);
+        assert_eq!(tournament.matches.len(), 7);
+
+        // p1 and p2 both got byes, so they meet right away
+        let ready = tournament.advance();
+        assert_eq!(ready.len(), 2);
+        assert_eq!(
+            names(tournament.players_of(ready[0])),
+            Some(("p3".to_string(), "p4".to_string()))
+        );
+        assert_eq!(
+            names(tournament.players_of(ready[1])),
+            Some(("p1".to_string(), "p2".to_string()))
+        );
+        // byes aren't wins
+        assert!(tournament.standings().iter().all(|s| s.wins == 0));
+
+        // p0 waits for the winner of p3 and p4
+        win(&mut tournament, ready[0], Side::Pong);
+        let ready = tournament.advance();
+        // p1 and p2 still have no table
+        assert_eq!(ready.len(), 2);
+        assert_eq!(
+            names(tournament.players_of(ready[0])),
+            Some(("p0".to_string(), "p4".to_string()))
+        );
+    }
+
+    #[test]
+    fn double_elimination_needs_two_losses() {
+        let mut tournament = tournament(TournamentFormat::DoubleElimination, 4);
+        assert_eq!(tournament.matches.len(), 6);
+
+        assert_eq!(tournament.advance(), vec![0, 1]);
+        win(&mut tournament, 0, Side::Ping);
+        win(&mut tournament, 1, Side::Ping);
+        // main final and first losers round
+        assert_eq!(tournament.advance(), vec![2, 3]);
+        win(&mut tournament, 2, Side::Ping);
+        win(&mut tournament, 3, Side::Ping);
+        // loser of the main final gets another chance
+        assert_eq!(tournament.advance(), vec![4]);
+        assert_eq!(
+            names(tournament.players_of(4)),
+            Some(("p3".to_string(), "p1".to_string()))
+        );
+        win(&mut tournament, 4, Side::Pong);
+        assert_eq!(tournament.advance(), vec![5]);
+        assert_eq!(tournament.matches[5].bracket, Bracket::Final);
+        assert_eq!(tournament.champion(), None);
+        win(&mut tournament, 5, Side::Pong);
+
+        assert!(tournament.is_finished());
+        assert_eq!(tournament.champion().unwrap().as_str(), "p1");
+        let standings = tournament.standings();
+        assert_eq!(standings[0].player.as_str(), "p1");
+        assert_eq!((standings[0].wins, standings[0].losses), (3, 1));
+        assert!(!standings[0].eliminated);
+        assert!(standings[1..].iter().all(|standing| standing.eliminated));
+    }
+
+    #[test]
+    fn double_elimination_works_with_byes() {
+        for count in 2..=MAX_PLAYERS {
+            let mut tournament = tournament(TournamentFormat::DoubleElimination, count);
+            // higher seed always wins, until it's all decided
+            loop {
+                let ready = tournament.advance();
+                if ready.is_empty() {
+                    break;
+                }
+                for number in ready {
+                    win(&mut tournament, number, Side::Ping);
+                }
+            }
+            assert!(tournament.is_finished(), "{count} players");
+            assert_eq!(tournament.champion().unwrap().as_str(), "p0");
+            let standings = tournament.standings();
+            assert_eq!(
+                standings.iter().filter(|s| s.losses == 0).count(),
+                1,
+                "{count} players"
+            );
+        }
+    }
+
+    #[test]
+    fn everyone_meets_once_in_round_robin() {
+        let mut tournament = tournament(TournamentFormat::RoundRobin, 5);
+        assert_eq!(tournament.matches.len(), 10);
+
+        let mut pairs = HashSet::new();
+        for round in 1..=5 {
+            let mut playing = HashSet::new();
+            for game in tournament.matches.iter().filter(|game| game.round == round) {
+                for slot in [&game.ping, &game.pong] {
+                    let Slot::Player(player) = slot else {
+                        panic!("round robin has only players");
+                    };
+                    assert!(playing.insert(player.clone()), "{player} twice in {round}");
+                }
+                let mut pair =
+                    [game.ping.clone(), game.pong.clone()].map(|slot| format!("{slot:?}"));
+                pair.sort();
+                assert!(pairs.insert(pair));
+            }
+            // one of five sits out
+            assert_eq!(playing.len(), 4);
+        }
+
+        // all matches can be played right away
+        assert_eq!(tournament.advance().len(), 10);
+        for number in 0..10 {
+            win(&mut tournament, number, Side::Ping);
+        }
+        let standings = tournament.standings();
+        assert_eq!(standings.iter().map(|s| s.wins).sum::<usize>(), 10);
+        assert!(
+            standings
+                .windows(2)
+                .all(|pair| pair[0].wins >= pair[1].wins)
+        );
+        assert_eq!(tournament.champion(), Some(standings[0].player.clone()));
+    }
+
+    #[test]
+    fn player_list_is_validated() {
+        assert!(Tournament::validate_players(&players(1)).is_err());
+        assert!(Tournament::validate_players(&players(MAX_PLAYERS + 1)).is_err());
+        let mut duplicated = players(3);
+        duplicated.push(duplicated[0].clone());
+        assert_eq!(
+            Tournament::validate_players(&duplicated),
+            Err("Player p0 is listed twice".to_string())
+        );
+        assert!(Tournament::validate_players(&players(2)).is_ok());
+    }
+}
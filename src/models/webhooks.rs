@@ -0,0 +1,90 @@
+use std::{fmt, time::Duration};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Match events webhooks can be notified of, named as their match event kind
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    /// point scored
+    Point,
+    /// record broken
+    Record,
+    /// match finished
+    Won,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] =
+        [WebhookEvent::Point, WebhookEvent::Record, WebhookEvent::Won];
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        WebhookEvent::ALL
+            .into_iter()
+            .find(|known| known.to_string() == kind)
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookEvent::Point => write!(f, "point"),
+            WebhookEvent::Record => write!(f, "record"),
+            WebhookEvent::Won => write!(f, "won"),
+        }
+    }
+}
+
+/// Value of the signature header - hex encoded HMAC-SHA256 of the body, keyed with the secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Wait before the next attempt after `attempts` failed ones, `None` once it's time to give up
+pub fn retry_delay(base: Duration, attempts: u32, max_attempts: u32) -> Option<Duration> {
+    (attempts < max_attempts).then(|| base.saturating_mul(1 << (attempts - 1).min(16)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_stored_by_name() {
+        for event in WebhookEvent::ALL {
+            assert_eq!(WebhookEvent::parse(&event.to_string()), Some(event));
+        }
+        assert_eq!(WebhookEvent::parse("undo"), None);
+    }
+
+    #[test]
+    fn signature_is_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn retries_back_off_until_given_up() {
+        let base = Duration::from_secs(2);
+        let delays: Vec<_> = (1..=4)
+            .map(|attempts| retry_delay(base, attempts, 4))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(4)),
+                Some(Duration::from_secs(8)),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    database::{
+        MatchFilter, PlayedMatch, TableUid, get_achievements, get_career_results,
+        get_player_matches,
+    },
+    models::{
+        achievements,
+        application::AppState,
+        career::{Career, Outcome},
+        game::{Score, Side},
+        player::PlayerName,
+    },
+};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+pub fn player_routes() -> Router<AppState> {
+    Router::new()
+        .route("/{player}/matches", get(list_matches))
+        .route("/{player}/stats", get(career_stats))
+        .route("/{player}/achievements", get(list_achievements))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchesQuery {
+    opponent: Option<PlayerName>,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MatchView {
+    match_id: TableUid,
+    finished_at: Timestamp,
+    side: Side,
+    /// `None` if the other side wasn't claimed
+    opponent: Option<PlayerName>,
+    outcome: Outcome,
+    score: Score,
+    hits: usize,
+    average_reaction_time_ms: Option<f64>,
+    longest_rally: Option<usize>,
+}
+
+impl MatchView {
+    fn new(player: &PlayerName, played: PlayedMatch) -> Self {
+        let result = played.result;
+        let side = result
+            .side_of(player)
+            .expect("matches are found by their players");
+        let own = result.side(side);
+        MatchView {
+            match_id: played.match_uid,
+            finished_at: played.finished_at,
+            side,
+            opponent: result.side(side.flip()).player.clone(),
+            outcome: if result.winner == side {
+                Outcome::Won
+            } else {
+                Outcome::Lost
+            },
+            hits: own.hits,
+            average_reaction_time_ms: (own.reactions > 0)
+                .then(|| own.reaction_time_total_ms as f64 / own.reactions as f64),
+            longest_rally: result.longest_rally,
+            score: result.score,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlayerMatches {
+    matches: Vec<MatchView>,
+}
+
+/// Won matches the player took part in, newest first
+async fn list_matches(
+    State(state): State<AppState>,
+    Path(player): Path<PlayerName>,
+    Query(query): Query<MatchesQuery>,
+) -> Result<Json<PlayerMatches>, (StatusCode, String)> {
+    let filter = MatchFilter {
+        opponent: query.opponent,
+        from: query.from,
+        to: query.to,
+        limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        offset: query.offset.unwrap_or_default().max(0),
+    };
+    let matches = get_player_matches(&state.db_pool, &player, &filter)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Error while getting player matches from database");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get matches".to_string(),
+            )
+        })?;
+
+    Ok(Json(PlayerMatches {
+        matches: matches
+            .into_iter()
+            .map(|played| MatchView::new(&player, played))
+            .collect(),
+    }))
+}
+
+/// Players without finished matches have an empty career rather than a 404
+async fn career_stats(
+    State(state): State<AppState>,
+    Path(player): Path<PlayerName>,
+) -> Result<Json<Career>, (StatusCode, String)> {
+    let results = get_career_results(&state.db_pool, &player)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Error while getting player career from database");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get player stats".to_string(),
+            )
+        })?;
+    Ok(Json(Career::from_results(&player, &results)))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AchievementView {
+    id: &'static str,
+    title: &'static str,
+    description: &'static str,
+    match_id: TableUid,
+    unlocked_at: Timestamp,
+}
+
+#[derive(Serialize)]
+pub struct PlayerAchievements {
+    achievements: Vec<AchievementView>,
+}
+
+/// Unlocked achievements, oldest first
+async fn list_achievements(
+    State(state): State<AppState>,
+    Path(player): Path<PlayerName>,
+) -> Result<Json<PlayerAchievements>, (StatusCode, String)> {
+    let unlocked = get_achievements(&state.db_pool, &player)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Error while getting achievements from database");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get achievements".to_string(),
+            )
+        })?;
+
+    Ok(Json(PlayerAchievements {
+        achievements: unlocked
+            .into_iter()
+            // ones dropped from the code are left alone
+            .filter_map(|unlocked| {
+                let achievement = achievements::find(&unlocked.achievement)?;
+                Some(AchievementView {
+                    id: achievement.id,
+                    title: achievement.title,
+                    description: achievement.description,
+                    match_id: unlocked.match_uid,
+                    unlocked_at: unlocked.unlocked_at,
+                })
+            })
+            .collect(),
+    }))
+}
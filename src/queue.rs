@@ -0,0 +1,134 @@
+use axum::{
+    Extension, Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    database::TableUid,
+    game_table::PLAYER_TOKEN_HEADER,
+    models::{
+        application::AppState,
+        game::{GameMode, Side, TableState},
+        player::PlayerName,
+    },
+    tournament::is_tournament_table,
+};
+
+#[derive(Serialize)]
+pub struct Queue {
+    queue: Vec<PlayerName>,
+}
+
+pub async fn view_queue(Extension(table_state): Extension<TableState>) -> Json<Queue> {
+    let claims = table_state.claims();
+    Json(Queue {
+        queue: claims
+            .queue
+            .iter()
+            .map(|claim| claim.player.clone())
+            .collect(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct JoinRequest {
+    player: PlayerName,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinResponse {
+    player: PlayerName,
+    /// counted from 1, `None` if the seat was free for the taking right away
+    position: Option<usize>,
+    /// seat taken right away, if any
+    side: Option<Side>,
+    /// needed to leave the queue, and to hit once seated
+    token: String,
+}
+
+pub async fn join_queue(
+    State(state): State<AppState>,
+    Extension(uid): Extension<TableUid>,
+    Extension(table_state): Extension<TableState>,
+    Json(JoinRequest { player }): Json<JoinRequest>,
+) -> Result<(StatusCode, Json<JoinResponse>), (StatusCode, String)> {
+    let conflict = |message: &str| Err((StatusCode::CONFLICT, message.to_string()));
+    if table_state.mode() != GameMode::Classic {
+        return conflict("Queue is for classic matches only");
+    }
+    if table_state.settings().points_to_win.is_none() {
+        return conflict("Match has no points to win, the queue would never move");
+    }
+    if is_tournament_table(&state, &uid).await {
+        return conflict("Tournament matches have no queue");
+    }
+    if state.lobby.is_rated_pending(&uid) {
+        return conflict("Rated matches have no queue");
+    }
+
+    let token = Uuid::new_v4().simple().to_string();
+    let Some(position) = table_state
+        .join_queue(player.clone(), token.clone())
+        .await?
+    else {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{player} is already at the table or in the queue"),
+        ));
+    };
+    info!(%player, position, "Challenger joined the queue");
+
+    // the match may be over already, with nobody waiting until now
+    table_state.next_challenger().await?;
+    // handovers and leavers may have moved the queue since joining
+    let (side, position) = {
+        let claims = table_state.claims();
+        let side = claims.side_with_token(&token).map(|(side, _)| side);
+        let position = claims
+            .queue
+            .iter()
+            .position(|claim| claim.token == token)
+            .map(|place| place + 1);
+        (side, position)
+    };
+    if let Some(side) = side {
+        info!(%player, %side, "Challenger took the table");
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(JoinResponse {
+            player,
+            position,
+            side,
+            token,
+        }),
+    ))
+}
+
+pub async fn leave_queue(
+    Extension(table_state): Extension<TableState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let not_queued = || {
+        (
+            StatusCode::NOT_FOUND,
+            "Nobody with that token is in the queue".to_string(),
+        )
+    };
+    let token = headers
+        .get(PLAYER_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(not_queued)?;
+    let player = table_state
+        .leave_queue(token.to_string())
+        .await?
+        .ok_or_else(not_queued)?;
+    info!(%player, "Challenger left the queue");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use jiff::{SignedDuration, Timestamp};
+use tracing::debug;
+
+use crate::{
+    auth::constant_time_eq, clock, config::RateLimitConfig, database::TableUid,
+    models::application::AppState,
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Buckets are dropped once there are more than that and they are full again
+const MAX_IDLE_BUCKETS: usize = 10_000;
+/// Dropping goes through every bucket while holding the lock, so it's done at most that often
+const SWEEP_INTERVAL: SignedDuration = SignedDuration::from_secs(10);
+
+/// Token bucket per key - allows bursts up to `limit`, refills `limit` tokens per `period`
+pub struct RateLimiter<K> {
+    capacity: f64,
+    refill_per_second: f64,
+    buckets: Mutex<Buckets<K>>,
+}
+
+struct Buckets<K> {
+    by_key: HashMap<K, Bucket>,
+    swept_at: Timestamp,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Timestamp,
+}
+
+impl<K: Hash + Eq + Clone> RateLimiter<K> {
+    pub fn new(limit: u32, period: Duration) -> Self {
+        Self {
+            capacity: limit as f64,
+            refill_per_second: limit as f64 / period.as_secs_f64(),
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                swept_at: clock::now(),
+            }),
+        }
+    }
+
+    /// Takes a token for the key, or tells how long to wait for the next one
+    pub fn check(&self, key: &K) -> Result<(), Duration> {
+        let now = clock::now();
+        let mut buckets = self.buckets.lock().expect("rate limiter lock was poisoned");
+
+        if buckets.by_key.len() > MAX_IDLE_BUCKETS
+            && now.duration_since(buckets.swept_at) >= SWEEP_INTERVAL
+        {
+            buckets
+                .by_key
+                .retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+            buckets.swept_at = now;
+        }
+
+        let bucket = buckets.by_key.entry(key.clone()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1. {
+            bucket.tokens -= 1.;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1. - bucket.tokens) / self.refill_per_second,
+            ))
+        }
+    }
+
+    /// Returns a token taken by `check`, for requests turned away by another limit
+    pub fn give_back(&self, key: &K) {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock was poisoned");
+        if let Some(bucket) = buckets.by_key.get_mut(key) {
+            bucket.tokens = (bucket.tokens + 1.).min(self.capacity);
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Timestamp) -> f64 {
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64().max(0.);
+        (bucket.tokens + elapsed * self.refill_per_second).min(self.capacity)
+    }
+}
+
+/// Caps how many requests a key can have in flight at once
+pub struct ConcurrencyLimiter<K> {
+    limit: u32,
+    open: Arc<Mutex<HashMap<K, u32>>>,
+}
+
+/// Frees the slot when dropped, however the request ends
+pub struct OpenSlot<K: Hash + Eq> {
+    key: K,
+    open: Arc<Mutex<HashMap<K, u32>>>,
+}
+
+impl<K: Hash + Eq + Clone> ConcurrencyLimiter<K> {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            open: Arc::default(),
+        }
+    }
+
+    pub fn acquire(&self, key: &K) -> Option<OpenSlot<K>> {
+        let mut open = self
+            .open
+            .lock()
+            .expect("concurrency limiter lock was poisoned");
+        let count = open.entry(key.clone()).or_default();
+        if *count >= self.limit {
+            return None;
+        }
+        *count += 1;
+        Some(OpenSlot {
+            key: key.clone(),
+            open: self.open.clone(),
+        })
+    }
+}
+
+impl<K: Hash + Eq> Drop for OpenSlot<K> {
+    fn drop(&mut self) {
+        let mut open = self
+            .open
+            .lock()
+            .expect("concurrency limiter lock was poisoned");
+        if let Some(count) = open.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// `None` client IP is shared by everyone whose address could not be established
+type ClientIp = Option<IpAddr>;
+
+pub struct RateLimits {
+    config: RateLimitConfig,
+    hits_per_ip: Option<RateLimiter<ClientIp>>,
+    hits_per_match: Option<RateLimiter<TableUid>>,
+    match_creations_per_ip: Option<RateLimiter<ClientIp>>,
+    open_waits_per_ip: Option<ConcurrencyLimiter<ClientIp>>,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let second = Duration::from_secs(1);
+        let minute = Duration::from_secs(60);
+        Self {
+            hits_per_ip: config
+                .hits_per_second_per_ip
+                .map(|limit| RateLimiter::new(limit, second)),
+            hits_per_match: config
+                .hits_per_second_per_match
+                .map(|limit| RateLimiter::new(limit, second)),
+            match_creations_per_ip: config
+                .match_creations_per_minute_per_ip
+                .map(|limit| RateLimiter::new(limit, minute)),
+            open_waits_per_ip: config.open_waits_per_ip.map(ConcurrencyLimiter::new),
+            config: config.clone(),
+        }
+    }
+
+    /// `None` for allowlisted clients - they are not limited at all
+    fn client_ip(&self, request: &Request) -> Option<ClientIp> {
+        let headers = request.headers();
+        if self.has_trusted_api_key(headers) {
+            return None;
+        }
+
+        let ip = forwarded_for(headers, self.config.trusted_proxies).or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip())
+        });
+
+        match ip {
+            Some(ip) if self.config.trusted_ips.contains(&ip) => None,
+            ip => Some(ip),
+        }
+    }
+
+    fn has_trusted_api_key(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|key| {
+                self.config
+                    .api_keys
+                    .iter()
+                    .any(|known| constant_time_eq(known.as_bytes(), key.as_bytes()))
+            })
+    }
+
+    pub fn check_hit(&self, request: &Request, uid: &TableUid) -> Result<(), Duration> {
+        let Some(ip) = self.client_ip(request) else {
+            return Ok(());
+        };
+        if let Some(limiter) = &self.hits_per_ip {
+            limiter.check(&ip)?;
+        }
+        if let Some(limiter) = &self.hits_per_match
+            && let Err(retry_after) = limiter.check(uid)
+        {
+            // a busy match doesn't use up the budget of everyone playing it
+            if let Some(limiter) = &self.hits_per_ip {
+                limiter.give_back(&ip);
+            }
+            return Err(retry_after);
+        }
+        Ok(())
+    }
+
+    pub fn check_match_creation(&self, request: &Request) -> Result<(), Duration> {
+        match (self.client_ip(request), &self.match_creations_per_ip) {
+            (Some(ip), Some(limiter)) => limiter.check(&ip),
+            _ => Ok(()),
+        }
+    }
+
+    /// The slot to hold on to while waiting, or how long to wait before trying again.
+    /// Waits end within seconds, but when exactly can't be told.
+    pub fn open_wait(&self, request: &Request) -> Result<Option<OpenSlot<ClientIp>>, Duration> {
+        match (self.client_ip(request), &self.open_waits_per_ip) {
+            (Some(ip), Some(limiter)) => {
+                limiter.acquire(&ip).map(Some).ok_or(Duration::from_secs(1))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Address added by the outermost of `proxies`, entries left of it can be made up by anyone
+fn forwarded_for(headers: &HeaderMap, proxies: usize) -> Option<IpAddr> {
+    let hop = proxies.checked_sub(1)?;
+    headers
+        .get("x-forwarded-for")?
+        .to_str()
+        .ok()?
+        .rsplit(',')
+        .nth(hop)?
+        .trim()
+        .parse()
+        .ok()
+}
+
+pub fn too_many_requests(retry_after: Duration) -> Response {
+    // Retry-After only takes whole seconds, rounding down would invite immediate retry
+    let seconds = retry_after.as_secs_f64().ceil().max(1.) as u64;
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        "Rate limit exceeded",
+    )
+        .into_response()
+}
+
+/// Has to run after the match is resolved, as it needs its `TableUid`
+pub async fn limit_hits(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let uid = request
+        .extensions()
+        .get::<TableUid>()
+        .expect("limit_hits must run after match is resolved")
+        .clone();
+
+    if let Err(retry_after) = state.rate_limits.check_hit(&request, &uid) {
+        debug!(match_id = %uid, "Hit throttled");
+        return too_many_requests(retry_after);
+    }
+
+    next.run(request).await
+}
+
+/// For routes that create matches without going through `/matches/{id}`
+pub async fn limit_match_creations(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(retry_after) = state.rate_limits.check_match_creation(&request) {
+        debug!("Match creation throttled");
+        return too_many_requests(retry_after);
+    }
+
+    next.run(request).await
+}
+
+/// For long-polls, which hold a connection for a while each
+pub async fn limit_open_waits(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let _slot = match state.rate_limits.open_wait(&request) {
+        Ok(slot) => slot,
+        Err(retry_after) => {
+            debug!("Wait throttled");
+            return too_many_requests(retry_after);
+        }
+    };
+
+    next.run(request).await
+}
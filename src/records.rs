@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use axum::{Extension, Json, extract::State, http::StatusCode};
+use jiff::Timestamp;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit::Audit,
+    auth::{Actor, Scope},
+    database::{Record, TableUid, get_records, rebuild_records, update_records},
+    models::{application::AppState, player::PlayerName, records::RecordKind},
+};
+
+/// Events looked at in one transaction
+const UPDATE_BATCH: i64 = 500;
+
+/// Keeps records up to date in the background, `every` apart once caught up
+pub fn follow_records(pool: PgPool, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            loop {
+                match update_records(&pool, UPDATE_BATCH).await {
+                    // catching up, e.g. after a rebuild
+                    Ok(seen) if seen as i64 == UPDATE_BATCH => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        error!(error = %e, "Error while updating records");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[derive(Serialize)]
+struct Players {
+    ping: Option<PlayerName>,
+    pong: Option<PlayerName>,
+    partners: Partners,
+}
+
+/// Second players of doubles sides
+#[derive(Serialize)]
+struct Partners {
+    ping: Option<PlayerName>,
+    pong: Option<PlayerName>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordView {
+    value: i64,
+    match_id: TableUid,
+    players: Players,
+    set_at: Timestamp,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Records {
+    longest_rally_by_hits: Option<RecordView>,
+    longest_rally_by_duration: Option<RecordView>,
+    biggest_comeback: Option<RecordView>,
+    fastest_game: Option<RecordView>,
+    most_points: Option<RecordView>,
+}
+
+pub async fn list_records(
+    State(state): State<AppState>,
+) -> Result<Json<Records>, (StatusCode, String)> {
+    let records = get_records(&state.db_pool).await.map_err(|e| {
+        error!(error = %e, "Error while getting records from database");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to get records".to_string(),
+        )
+    })?;
+    let find = |kind: RecordKind| {
+        records
+            .iter()
+            .find(|record| record.kind == kind)
+            .cloned()
+            .map(|record: Record| RecordView {
+                value: record.value,
+                match_id: record.match_uid,
+                players: Players {
+                    ping: record.ping_player,
+                    pong: record.pong_player,
+                    partners: Partners {
+                        ping: record.ping_partner,
+                        pong: record.pong_partner,
+                    },
+                },
+                set_at: record.set_at,
+            })
+    };
+
+    Ok(Json(Records {
+        longest_rally_by_hits: find(RecordKind::LongestRallyByHits),
+        longest_rally_by_duration: find(RecordKind::LongestRallyByDuration),
+        biggest_comeback: find(RecordKind::BiggestComeback),
+        fastest_game: find(RecordKind::FastestGame),
+        most_points: find(RecordKind::MostPoints),
+    }))
+}
+
+/// Records are built again from match history in the background
+pub async fn rebuild(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    actor.require(Scope::Write)?;
+    rebuild_records(&state.db_pool).await.map_err(|e| {
+        error!(error = %e, "Error while rebuilding records");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to rebuild records".to_string(),
+        )
+    })?;
+    Audit::without_match(&state, &actor.name)
+        .record("rebuild_records", json!({}))
+        .await?;
+    Ok(StatusCode::ACCEPTED)
+}
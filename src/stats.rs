@@ -0,0 +1,66 @@
+use axum::{Extension, Json};
+use serde::Serialize;
+
+use crate::models::{
+    game::{LongestRally, Side, TableState},
+    stats::MatchStats,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SideView {
+    hits: usize,
+    points_won: usize,
+    /// opponent didn't return the ball in time
+    points_won_on_timeouts: usize,
+    /// opponent hit while the ball was on this side
+    points_won_on_wrong_side_hits: usize,
+    /// points won while the opponent was a point away from winning
+    match_points_saved: usize,
+    average_reaction_time_ms: Option<f64>,
+    median_reaction_time_ms: Option<f64>,
+    serves: usize,
+    serve_win_percentage: Option<f64>,
+}
+
+impl SideView {
+    fn new(stats: &MatchStats, side: Side) -> Self {
+        let stats = stats.side(side);
+        SideView {
+            hits: stats.hits,
+            points_won: stats.points_won,
+            points_won_on_timeouts: stats.won_on_timeouts,
+            points_won_on_wrong_side_hits: stats.won_on_wrong_side_hits,
+            match_points_saved: stats.match_points_saved,
+            average_reaction_time_ms: stats.average_reaction_time_ms(),
+            median_reaction_time_ms: stats.median_reaction_time_ms(),
+            serves: stats.serves,
+            serve_win_percentage: stats.serve_win_percentage(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsView {
+    rallies: usize,
+    average_rally_length: Option<f64>,
+    longest_rally_by_hits: Option<LongestRally>,
+    longest_rally_by_duration: Option<LongestRally>,
+    ping: SideView,
+    pong: SideView,
+}
+
+/// Only rallies that ended with a point count, the ongoing one joins once it's over
+pub async fn match_stats(Extension(table_state): Extension<TableState>) -> Json<StatsView> {
+    let game_state = table_state.game_state_snapshot();
+    let stats = &game_state.stats;
+    Json(StatsView {
+        rallies: stats.rallies,
+        average_rally_length: stats.average_rally_length(),
+        longest_rally_by_hits: game_state.longest_rally.clone(),
+        longest_rally_by_duration: stats.longest_by_duration.clone(),
+        ping: SideView::new(stats, Side::Ping),
+        pong: SideView::new(stats, Side::Pong),
+    })
+}
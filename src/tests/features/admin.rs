@@ -0,0 +1,286 @@
+use std::time::Duration;
+
+use axum::http::{StatusCode, header};
+use axum_test::TestServer;
+use serde_json::json;
+
+use crate::{
+    BALL_AIR_TIME_SECONDS,
+    config::{AdminConfig, Config},
+    models::game::{GameState, Score},
+    tests::{
+        features::{claims::CLAIM_PING_ENDPOINT, time_dependent::advance_time},
+        utils::{
+            MATCH_ENDPOINT, PING_ENDPOINT, init_test_state_with_config, mock_audit_log,
+            setup_test_server_from_state,
+        },
+    },
+};
+
+const ADMIN_KEY: &str = "rootkey";
+const VIEWER_KEY: &str = "viewkey";
+const ADMIN_MATCH_ENDPOINT: &str = "/admin/matches/test";
+
+fn admin_server() -> TestServer {
+    let config = Config {
+        admin: AdminConfig {
+            api_keys: vec![
+                format!("root:{ADMIN_KEY}:read,write").parse().unwrap(),
+                format!("viewer:{VIEWER_KEY}:read").parse().unwrap(),
+            ],
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+#[tokio::test]
+async fn admin_needs_valid_key() {
+    let server = admin_server();
+
+    let response = server.post(&format!("{ADMIN_MATCH_ENDPOINT}/reset")).await;
+    response.assert_status_unauthorized();
+    response.assert_header(header::WWW_AUTHENTICATE, "Bearer");
+
+    server
+        .post(&format!("{ADMIN_MATCH_ENDPOINT}/reset"))
+        .authorization_bearer("guess")
+        .await
+        .assert_status_unauthorized();
+}
+
+#[tokio::test]
+async fn read_scope_cannot_change_matches() {
+    let server = admin_server();
+
+    let response = server
+        .post(&format!("{ADMIN_MATCH_ENDPOINT}/reset"))
+        .authorization_bearer(VIEWER_KEY)
+        .await;
+    response.assert_status_forbidden();
+    response.assert_text("API key is missing 'write' scope");
+}
+
+#[tokio::test]
+async fn unknown_match_is_not_created() {
+    let server = admin_server();
+
+    server
+        .post("/admin/matches/nope/reset")
+        .authorization_bearer(ADMIN_KEY)
+        .await
+        .assert_status_not_found();
+    server
+        .get("/matches")
+        .await
+        .assert_json(&json!({ "openMatches": ["test"] }));
+}
+
+#[tokio::test]
+async fn audit_entry_has_the_game_states_around_the_change() {
+    let server = admin_server();
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    // ball dropped on pong's side, but nothing has seen it yet
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS)).await;
+
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/score"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "ping": 5, "pong": 3 }))
+        .await
+        .assert_status_ok();
+
+    let entries = mock_audit_log::take();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!((entry.actor.as_str(), entry.action), ("root", "set_score"));
+    let score = |game_state: &Option<GameState>| game_state.as_ref().unwrap().score.clone();
+    assert_eq!(score(&entry.game_state_before), Score { ping: 1, pong: 0 });
+    assert_eq!(score(&entry.game_state_after), Score { ping: 5, pong: 3 });
+}
+
+#[tokio::test]
+async fn set_score_and_server() {
+    let server = admin_server();
+
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/score"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "ping": 5, "pong": 3 }))
+        .await
+        .assert_json_contains(&json!({ "gameState": { "score": { "ping": 5, "pong": 3 } } }));
+
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/server"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "server": "pong" }))
+        .await
+        .assert_status_ok();
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "pong" },
+            "gameState": { "server": "pong", "score": { "ping": 5, "pong": 3 } }
+        }));
+}
+
+#[tokio::test]
+async fn winning_score_finishes_the_match() {
+    let server = admin_server();
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/settings"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "minReactionTimeMs": 0, "tooEarly": "reject", "pointsToWin": 11 }))
+        .await
+        .assert_status_ok();
+
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/score"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "ping": 11, "pong": 3 }))
+        .await
+        .assert_json_contains(&json!({ "gameState": { "winner": "ping" } }));
+    server.get(PING_ENDPOINT).await.assert_text("Match is over");
+
+    // lowering it takes the win away
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/score"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "ping": 10, "pong": 9 }))
+        .await
+        .assert_json_contains(&json!({ "gameState": { "winner": null } }));
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+}
+
+#[tokio::test]
+async fn unplayable_settings_are_rejected() {
+    let server = admin_server();
+    for settings in [
+        json!({ "minReactionTimeMs": 0, "tooEarly": "reject", "pointsToWin": 0 }),
+        json!({ "minReactionTimeMs": 0, "tooEarly": "reject", "airTimeSeconds": 0 }),
+        json!({ "minReactionTimeMs": 5000, "tooEarly": "reject", "airTimeSeconds": 3 }),
+    ] {
+        server
+            .put(&format!("{ADMIN_MATCH_ENDPOINT}/settings"))
+            .authorization_bearer(ADMIN_KEY)
+            .json(&settings)
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    // the match goes on with what it had
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+}
+
+#[tokio::test]
+async fn server_cannot_change_mid_rally() {
+    let server = admin_server();
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+
+    let response = server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/server"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "server": "pong" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Can't change server during a rally");
+}
+
+#[tokio::test]
+async fn end_rally_with_winner() {
+    let server = admin_server();
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+
+    server
+        .post(&format!("{ADMIN_MATCH_ENDPOINT}/end-rally"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "winner": "ping" }))
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "pong", "hitCount": 0 },
+            "gameState": { "server": "pong", "score": { "ping": 1, "pong": 0 } }
+        }));
+
+    server
+        .post(&format!("{ADMIN_MATCH_ENDPOINT}/end-rally"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "winner": "ping" }))
+        .await
+        .assert_status(StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn end_rally_without_winner() {
+    let server = admin_server();
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+
+    server
+        .post(&format!("{ADMIN_MATCH_ENDPOINT}/end-rally"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "winner": null }))
+        .await
+        .assert_status_ok();
+
+    // timeout of the cancelled rally doesn't score either
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 0, "serveTimestamp": null },
+            "gameState": { "server": "ping", "score": { "ping": 0, "pong": 0 } }
+        }));
+}
+
+#[tokio::test]
+async fn reset_keeps_players() {
+    let server = admin_server();
+    server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server
+        .put(&format!("{ADMIN_MATCH_ENDPOINT}/score"))
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "ping": 5, "pong": 3 }))
+        .await
+        .assert_status_ok();
+
+    server
+        .post(&format!("{ADMIN_MATCH_ENDPOINT}/reset"))
+        .authorization_bearer(ADMIN_KEY)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "server": "ping", "score": { "ping": 0, "pong": 0 } },
+            "players": { "ping": "alice" }
+        }));
+}
+
+#[tokio::test]
+async fn kick_frees_the_side() {
+    let server = admin_server();
+    server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server
+        .get(PING_ENDPOINT)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    server
+        .delete(&format!("{ADMIN_MATCH_ENDPOINT}/claims/ping"))
+        .authorization_bearer(ADMIN_KEY)
+        .await
+        .assert_json_contains(&json!({ "players": { "ping": null } }));
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+
+    server
+        .delete(&format!("{ADMIN_MATCH_ENDPOINT}/claims/ping"))
+        .authorization_bearer(ADMIN_KEY)
+        .await
+        .assert_status_not_found();
+}
@@ -14,7 +14,10 @@ async fn play_some_ping_pong() {
             "side": "ping",
             "hitTimeoutTimestamp": null,
             "serveTimestamp": null,
+            "lastHitTimestamp": null,
             "hitCount": 0,
+            "nextPartners": null,
+            "paused": false,
         },
         "gameState": {
             "server": "ping",
@@ -23,6 +26,56 @@ async fn play_some_ping_pong() {
                 "pong": 0
             },
             "longestRally": null,
+            "lets": 0,
+            "mode": "classic",
+            "wall": null,
+            "rotation": null,
+            "winner": null,
+            "stats": {
+                "ping": {
+                    "hits": 0,
+                    "pointsWon": 0,
+                    "wonOnTimeouts": 0,
+                    "wonOnWrongSideHits": 0,
+                    "serves": 0,
+                    "servesWon": 0,
+                    "matchPointsSaved": 0,
+                    "reactionTimes": { "count": 0, "totalMs": 0, "buckets": vec![0; 32] }
+                },
+                "pong": {
+                    "hits": 0,
+                    "pointsWon": 0,
+                    "wonOnTimeouts": 0,
+                    "wonOnWrongSideHits": 0,
+                    "serves": 0,
+                    "servesWon": 0,
+                    "matchPointsSaved": 0,
+                    "reactionTimes": { "count": 0, "totalMs": 0, "buckets": vec![0; 32] }
+                },
+                "rallies": 0,
+                "rallyHits": 0,
+                "longestByDuration": null
+            }
+        },
+        "players": {
+            "ping": null,
+            "pong": null,
+            "partners": {
+                "ping": null,
+                "pong": null
+            },
+            "owner": null,
+            "queue": []
+        },
+        "settings": {
+            "minReactionTimeMs": 0,
+            "tooEarly": "reject",
+            "pointsToWin": null,
+            "airTimeSeconds": null
+        },
+        "bots": {
+            "ping": null,
+            "pong": null
         }
     }));
 
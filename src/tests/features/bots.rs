@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    BALL_AIR_TIME_SECONDS,
+    database::TableUid,
+    game_table::PLAYER_TOKEN_HEADER,
+    models::game::GameMode,
+    tests::{
+        features::{
+            claims::CLAIM_PING_ENDPOINT,
+            corrections::{REFEREE_KEY, claim, server_with_referee},
+            time_dependent::advance_time,
+        },
+        utils::{
+            MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, init_test_state, setup_test_server,
+            setup_test_server_from_state, setup_test_server_with_mode,
+        },
+    },
+};
+
+const PING_BOT_ENDPOINT: &str = "/matches/test/bots/ping";
+const PONG_BOT_ENDPOINT: &str = "/matches/test/bots/pong";
+
+fn steady_bot(miss_probability: f64) -> Value {
+    json!({
+        "reactionTimeMs": 100,
+        "reactionTimeSpreadMs": 0,
+        "missProbability": miss_probability
+    })
+}
+
+/// Small steps, so the bot notices the ball before its reaction time starts running
+async fn play_for(duration: Duration) {
+    let step = Duration::from_millis(10);
+    for _ in 0..duration.as_millis() / step.as_millis() {
+        advance_time(step).await;
+    }
+}
+
+async fn hit_count(server: &TestServer) -> u64 {
+    let state: Value = server.get(MATCH_ENDPOINT).await.json();
+    state["rallyState"]["hitCount"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn bot_returns_the_ball() {
+    let server = setup_test_server();
+    let response = server.post(PONG_BOT_ENDPOINT).json(&steady_bot(0.0)).await;
+    response.assert_status(StatusCode::CREATED);
+    response.assert_json_contains(&json!({
+        "bots": {
+            "ping": null,
+            "pong": {
+                "reactionTimeMs": 100,
+                "reactionTimeSpreadMs": 0,
+                "missProbability": 0.0,
+                "slowdownPerHitMs": 0
+            }
+        }
+    }));
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    play_for(Duration::from_millis(150)).await;
+    assert_eq!(hit_count(&server).await, 2);
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    play_for(Duration::from_millis(150)).await;
+    assert_eq!(hit_count(&server).await, 4);
+}
+
+#[tokio::test]
+async fn players_cant_hit_for_the_bot() {
+    let server = setup_test_server();
+    server
+        .post(PING_BOT_ENDPOINT)
+        .json(&steady_bot(0.0))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server.get(PING_ENDPOINT).await;
+    response.assert_status_forbidden();
+    response.assert_text("ping side is played by a bot");
+
+    // nor take its side
+    server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    let response = server.post(PING_BOT_ENDPOINT).json(&json!({})).await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("ping side is already played by a bot");
+}
+
+#[tokio::test]
+async fn bot_cant_take_a_claimed_side() {
+    let server = setup_test_server();
+    server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server.post(PING_BOT_ENDPOINT).json(&json!({})).await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("ping side is claimed by alice");
+
+    server
+        .post(PING_BOT_ENDPOINT)
+        .json(&json!({ "missProbability": 2 }))
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn only_whoever_attached_a_bot_can_detach_it() {
+    let server = server_with_referee();
+    let response = server.post(PONG_BOT_ENDPOINT).json(&steady_bot(0.0)).await;
+    let token = response.header(PLAYER_TOKEN_HEADER);
+
+    let response = server.delete(PONG_BOT_ENDPOINT).await;
+    response.assert_status_forbidden();
+    response.assert_text("Only whoever attached the bot or a referee can do that");
+    server
+        .delete(PONG_BOT_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, "guessed")
+        .await
+        .assert_status_forbidden();
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "bots": { "pong": { "reactionTimeMs": 100 } } }));
+
+    server
+        .delete(PONG_BOT_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, token)
+        .await
+        .assert_status_ok();
+    server
+        .post(PONG_BOT_ENDPOINT)
+        .json(&steady_bot(0.0))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server
+        .delete(PONG_BOT_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn claimed_side_is_handed_to_a_bot_by_its_player_or_a_referee() {
+    let server = server_with_referee();
+    let alice = claim(&server, "ping", "alice").await;
+    claim(&server, "pong", "bob").await;
+
+    let response = server
+        .post(PING_BOT_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &alice)
+        .json(&steady_bot(0.0))
+        .await;
+    response.assert_status(StatusCode::CREATED);
+    response.assert_json_contains(&json!({ "players": { "ping": null, "pong": "bob" } }));
+    assert_eq!(response.header(PLAYER_TOKEN_HEADER), alice.as_str());
+
+    server
+        .post(PONG_BOT_ENDPOINT)
+        .json(&steady_bot(0.0))
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    server
+        .post(PONG_BOT_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .json(&steady_bot(0.0))
+        .await
+        .assert_json_contains(&json!({ "players": { "pong": null } }));
+}
+
+#[tokio::test]
+async fn doubles_side_is_handed_to_a_bot_by_both_partners_only() {
+    let server = setup_test_server_with_mode(GameMode::Doubles);
+    let alice = claim(&server, "ping", "alice").await;
+    claim(&server, "ping", "bob").await;
+
+    let response = server
+        .post(PING_BOT_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, alice)
+        .json(&steady_bot(0.0))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("ping side is claimed by bob");
+}
+
+#[tokio::test]
+async fn bot_that_always_misses_lets_the_ball_drop() {
+    let server = setup_test_server();
+    server
+        .post(PONG_BOT_ENDPOINT)
+        .json(&steady_bot(1.0))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    play_for(Duration::from_millis(150)).await;
+    assert_eq!(hit_count(&server).await, 1);
+
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 1, "pong": 0 }, "server": "pong" }
+        }));
+
+    // serves still go in
+    play_for(Duration::from_millis(150)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 1 }
+        }));
+}
+
+#[tokio::test]
+async fn bots_can_play_each_other() {
+    let server = setup_test_server();
+    server
+        .post(PING_BOT_ENDPOINT)
+        .json(&steady_bot(0.0))
+        .await
+        .assert_status(StatusCode::CREATED);
+    let pong_bot = server.post(PONG_BOT_ENDPOINT).json(&steady_bot(0.0)).await;
+    pong_bot.assert_status(StatusCode::CREATED);
+    let token = pong_bot.header(PLAYER_TOKEN_HEADER);
+
+    play_for(Duration::from_millis(750)).await;
+    let rally_length = hit_count(&server).await;
+    assert!(rally_length >= 3, "{rally_length}");
+
+    // detached bot stops playing, the other one waits for the ball
+    server
+        .delete(PONG_BOT_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, token.clone())
+        .await
+        .assert_json_contains(&json!({ "bots": { "pong": null } }));
+    server
+        .delete(PONG_BOT_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, token)
+        .await
+        .assert_status_not_found();
+
+    let state: Value = server.get(MATCH_ENDPOINT).await.json();
+    if state["rallyState"]["side"] == "pong" {
+        server.get(PONG_ENDPOINT).await.assert_text("ping");
+    }
+    let rally_length = hit_count(&server).await;
+    play_for(Duration::from_millis(150)).await;
+    assert_eq!(hit_count(&server).await, rally_length + 1);
+    play_for(Duration::from_millis(150)).await;
+    assert_eq!(hit_count(&server).await, rally_length + 1);
+}
+
+#[tokio::test]
+async fn bot_doesnt_keep_its_table_alive() {
+    let state = init_test_state();
+    let server = setup_test_server_from_state(state.clone());
+    server
+        .post(PONG_BOT_ENDPOINT)
+        .json(&steady_bot(0.0))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+
+    let table_state = state
+        .game_tables
+        .remove(&TableUid::parse("test").unwrap())
+        .unwrap();
+    let mut table = table_state.downgrade();
+    drop(table_state);
+    play_for(Duration::from_millis(150)).await;
+    // actor stopped, and its bots with it
+    table.read_rally_state(|_| ());
+    assert!(!table.changed().await);
+}
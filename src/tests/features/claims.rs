@@ -0,0 +1,88 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::{
+    game_table::PLAYER_TOKEN_HEADER,
+    tests::utils::{MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, setup_test_server},
+};
+
+pub const CLAIM_PING_ENDPOINT: &str = "/matches/test/claims/ping";
+
+#[tokio::test]
+async fn claimed_side_needs_a_token() {
+    let server = setup_test_server();
+
+    let response = server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await;
+    response.assert_status(StatusCode::CREATED);
+    let claim: Value = response.json();
+    assert_eq!(claim["side"], "ping");
+    assert_eq!(claim["player"], "alice");
+    let token = claim["token"].as_str().unwrap();
+
+    // token is never shown to anyone else
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "players": { "ping": "alice", "pong": null } }));
+    assert!(!server.get(MATCH_ENDPOINT).await.text().contains(token));
+
+    let response = server.get(PING_ENDPOINT).await;
+    response.assert_status(StatusCode::FORBIDDEN);
+    response.assert_text("ping side is claimed by alice");
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, "wrong")
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, token)
+        .await
+        .assert_text("pong");
+    // unclaimed side is open for anyone
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+}
+
+#[tokio::test]
+async fn side_can_be_claimed_once() {
+    let server = setup_test_server();
+
+    server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "bob" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("ping side is already claimed");
+
+    server
+        .post("/matches/test/claims/pong")
+        .json(&json!({ "player": "bob" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn invalid_claims_are_rejected() {
+    let server = setup_test_server();
+
+    server
+        .post(CLAIM_PING_ENDPOINT)
+        .json(&json!({ "player": "not valid" }))
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    server
+        .post("/matches/test/claims/middle")
+        .json(&json!({ "player": "alice" }))
+        .await
+        .assert_status_bad_request();
+}
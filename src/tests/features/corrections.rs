@@ -0,0 +1,332 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    BALL_AIR_TIME_SECONDS,
+    config::{AdminConfig, Config},
+    game_table::PLAYER_TOKEN_HEADER,
+    tests::{
+        features::time_dependent::advance_time,
+        utils::{
+            MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, init_test_state_with_config,
+            setup_test_server_from_state,
+        },
+    },
+};
+
+const UNDO_ENDPOINT: &str = "/matches/test/undo";
+const LET_ENDPOINT: &str = "/matches/test/let";
+const ADMIN_KEY: &str = "rootkey";
+pub const REFEREE_KEY: &str = "refkey";
+const VIEWER_KEY: &str = "viewkey";
+
+pub fn server_with_referee() -> TestServer {
+    let config = Config {
+        admin: AdminConfig {
+            api_keys: vec![
+                format!("ref:{REFEREE_KEY}:referee").parse().unwrap(),
+                format!("root:{ADMIN_KEY}:read,write").parse().unwrap(),
+                format!("viewer:{VIEWER_KEY}:read").parse().unwrap(),
+            ],
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+/// returns the token
+pub async fn claim(server: &TestServer, side: &str, player: &str) -> String {
+    let claim: Value = server
+        .post(&format!("{MATCH_ENDPOINT}/claims/{side}"))
+        .json(&json!({ "player": player }))
+        .await
+        .json();
+    claim["token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn owner_can_undo_a_misclick() {
+    let server = server_with_referee();
+    let owner_token = claim(&server, "ping", "alice").await;
+    let other_token = claim(&server, "pong", "bob").await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "players": { "owner": "alice" } }));
+
+    // wrong tab - pong misses on ping's serve
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &other_token)
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    // only the owner can take it back
+    server
+        .post(UNDO_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &other_token)
+        .await
+        .assert_status_forbidden();
+    server.post(UNDO_ENDPOINT).await.assert_status_forbidden();
+
+    server
+        .post(UNDO_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 0 },
+            "gameState": { "server": "ping", "score": { "ping": 0, "pong": 0 } }
+        }));
+
+    let response = server
+        .post(UNDO_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Nothing to undo");
+}
+
+#[tokio::test]
+async fn undo_reverts_longest_rally() {
+    let server = server_with_referee();
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": {
+                "server": "pong",
+                "score": { "ping": 0, "pong": 1 },
+                "longestRally": { "hitCount": 2 }
+            }
+        }));
+
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": {
+                "server": "ping",
+                "score": { "ping": 0, "pong": 0 },
+                "longestRally": null
+            }
+        }));
+}
+
+#[tokio::test]
+async fn undo_goes_back_point_by_point() {
+    let server = server_with_referee();
+    // ping and pong miss in turns - 1:0, 1:1
+    server.get(PONG_ENDPOINT).await;
+    server.get(PING_ENDPOINT).await;
+
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "server": "pong", "score": { "ping": 1, "pong": 0 } }
+        }));
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "server": "ping", "score": { "ping": 0, "pong": 0 } }
+        }));
+}
+
+#[tokio::test]
+async fn undo_cancels_ongoing_rally() {
+    let server = server_with_referee();
+    server.get(PONG_ENDPOINT).await;
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+
+    // timeout of the cancelled rally doesn't count
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 0 },
+            "gameState": { "server": "ping", "score": { "ping": 0, "pong": 0 } }
+        }));
+}
+
+#[tokio::test]
+async fn referee_needs_the_right_key() {
+    let server = server_with_referee();
+    server.get(PONG_ENDPOINT).await;
+
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer("guess")
+        .await
+        .assert_status_unauthorized();
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer(VIEWER_KEY)
+        .await
+        .assert_status_forbidden();
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "score": { "ping": 1, "pong": 0 } } }));
+}
+
+#[tokio::test]
+async fn owner_can_call_a_let() {
+    let server = server_with_referee();
+    let owner_token = claim(&server, "ping", "alice").await;
+    server.get(PONG_ENDPOINT).await;
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    // ping serving at 1:1
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_text("pong");
+
+    server
+        .post(LET_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 0, "hitTimeoutTimestamp": null },
+            "gameState": { "server": "ping", "score": { "ping": 1, "pong": 1 }, "lets": 1 }
+        }));
+
+    // timeout was aborted with the rally
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "server": "ping", "score": { "ping": 1, "pong": 1 }, "lets": 1 }
+        }));
+
+    // nothing to replay now
+    let response = server
+        .post(LET_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("No rally in progress");
+}
+
+#[tokio::test]
+async fn players_can_agree_on_a_let() {
+    let server = server_with_referee();
+    // owner left, so the players have to agree
+    claim(&server, "ping", "carol").await;
+    server
+        .delete("/admin/matches/test/claims/ping")
+        .authorization_bearer(ADMIN_KEY)
+        .await
+        .assert_status_ok();
+    let ping_token = claim(&server, "ping", "alice").await;
+    let pong_token = claim(&server, "pong", "bob").await;
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &ping_token)
+        .await
+        .assert_text("pong");
+    server.post(LET_ENDPOINT).await.assert_status_forbidden();
+
+    let response = server
+        .post(LET_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &pong_token)
+        .await;
+    response.assert_status(StatusCode::ACCEPTED);
+    response.assert_text("Let requested by bob, waiting for ping side to agree");
+    // asking twice doesn't make it an agreement
+    server
+        .post(LET_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &pong_token)
+        .await
+        .assert_status(StatusCode::ACCEPTED);
+
+    server
+        .post(LET_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &ping_token)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 0 },
+            "gameState": { "server": "ping", "score": { "ping": 0, "pong": 0 }, "lets": 1 }
+        }));
+}
+
+#[tokio::test]
+async fn let_requests_expire_with_the_rally() {
+    let server = server_with_referee();
+    claim(&server, "ping", "carol").await;
+    server
+        .delete("/admin/matches/test/claims/ping")
+        .authorization_bearer(ADMIN_KEY)
+        .await
+        .assert_status_ok();
+    let ping_token = claim(&server, "ping", "alice").await;
+    let pong_token = claim(&server, "pong", "bob").await;
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &ping_token)
+        .await
+        .assert_text("pong");
+    server
+        .post(LET_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &pong_token)
+        .await
+        .assert_status(StatusCode::ACCEPTED);
+
+    // rally ends with a point, next one starts
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &pong_token)
+        .await
+        .assert_text("ping");
+
+    server
+        .post(LET_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &ping_token)
+        .await
+        .assert_status(StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn undo_keeps_lets() {
+    let server = server_with_referee();
+    server.get(PONG_ENDPOINT).await;
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    server
+        .post(LET_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+
+    server
+        .post(UNDO_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 0, "pong": 0 }, "lets": 1 }
+        }));
+}
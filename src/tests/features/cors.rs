@@ -0,0 +1,119 @@
+use axum::http::{HeaderValue, Method, header};
+use axum_test::TestServer;
+
+use crate::{
+    config::{AllowList, Config, CorsConfig, CorsPolicy},
+    tests::utils::{
+        MATCH_ENDPOINT, PING_ENDPOINT, init_test_state_with_config, setup_test_server,
+        setup_test_server_from_state,
+    },
+};
+
+const ALLOWED_ORIGIN: &str = "https://scoreboard.example.com";
+const OTHER_ORIGIN: &str = "https://evil.example.com";
+
+fn server_with_public_policy(public: CorsPolicy) -> TestServer {
+    let config = Config {
+        cors: CorsConfig {
+            public,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+fn restricted_policy() -> CorsPolicy {
+    CorsPolicy {
+        origins: AllowList::Only(vec![HeaderValue::from_static(ALLOWED_ORIGIN)]),
+        methods: AllowList::Only(vec![Method::GET]),
+        headers: AllowList::Any,
+        max_age: None,
+    }
+}
+
+async fn preflight(server: &TestServer, path: &str, origin: &str) -> axum_test::TestResponse {
+    server
+        .method(Method::OPTIONS, path)
+        .add_header(header::ORIGIN, origin)
+        .add_header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .await
+}
+
+#[tokio::test]
+async fn any_origin_allowed_by_default() {
+    let server = setup_test_server();
+
+    let response = preflight(&server, PING_ENDPOINT, OTHER_ORIGIN).await;
+    response.assert_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+}
+
+#[tokio::test]
+async fn configured_origin_allowed() {
+    let server = server_with_public_policy(restricted_policy());
+
+    let response = preflight(&server, PING_ENDPOINT, ALLOWED_ORIGIN).await;
+    response.assert_status_ok();
+    response.assert_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, ALLOWED_ORIGIN);
+    response.assert_header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET");
+
+    let response = server
+        .get(MATCH_ENDPOINT)
+        .add_header(header::ORIGIN, ALLOWED_ORIGIN)
+        .await;
+    response.assert_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, ALLOWED_ORIGIN);
+    response.assert_header(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        "x-request-id,x-player-token",
+    );
+}
+
+#[tokio::test]
+async fn disallowed_origin_rejected_on_preflight() {
+    let server = server_with_public_policy(restricted_policy());
+
+    let response = preflight(&server, PING_ENDPOINT, OTHER_ORIGIN).await;
+    // without the allow-origin header browser refuses to send the actual request
+    assert!(
+        response
+            .maybe_header(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn preflight_max_age_is_configurable() {
+    let server = server_with_public_policy(CorsPolicy {
+        max_age: Some(std::time::Duration::from_secs(600)),
+        ..restricted_policy()
+    });
+
+    preflight(&server, MATCH_ENDPOINT, ALLOWED_ORIGIN)
+        .await
+        .assert_header(header::ACCESS_CONTROL_MAX_AGE, "600");
+}
+
+#[tokio::test]
+async fn admin_has_separate_policy() {
+    // public policy allows everyone, admin one nobody by default
+    let server = setup_test_server();
+    let response = preflight(&server, "/admin/matches", OTHER_ORIGIN).await;
+    assert!(
+        response
+            .maybe_header(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none()
+    );
+
+    let config = Config {
+        cors: CorsConfig {
+            admin: restricted_policy(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = setup_test_server_from_state(init_test_state_with_config(config));
+    // preflight doesn't carry credentials, so it can't require them
+    let response = preflight(&server, "/admin/matches", ALLOWED_ORIGIN).await;
+    response.assert_status_ok();
+    response.assert_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, ALLOWED_ORIGIN);
+}
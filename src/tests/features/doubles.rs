@@ -0,0 +1,152 @@
+use axum::http::StatusCode;
+use axum_test::{TestResponse, TestServer};
+use serde_json::{Value, json};
+
+use crate::{
+    game_table::PLAYER_TOKEN_HEADER,
+    models::game::GameMode,
+    tests::{
+        features::corrections::claim,
+        utils::{MATCH_ENDPOINT, setup_test_server_with_mode},
+    },
+};
+
+struct Teams {
+    alice: String,
+    bob: String,
+    carol: String,
+    dave: String,
+}
+
+/// alice and bob play ping, carol and dave pong - first claimant of a side is the first partner
+async fn doubles_with_teams() -> (TestServer, Teams) {
+    let server = setup_test_server_with_mode(GameMode::Doubles);
+    let teams = Teams {
+        alice: claim(&server, "ping", "alice").await,
+        bob: claim(&server, "ping", "bob").await,
+        carol: claim(&server, "pong", "carol").await,
+        dave: claim(&server, "pong", "dave").await,
+    };
+    (server, teams)
+}
+
+async fn hit(server: &TestServer, side: &str, token: &str) -> TestResponse {
+    server
+        .get(&format!("{MATCH_ENDPOINT}/{side}"))
+        .add_header(PLAYER_TOKEN_HEADER, token)
+        .await
+}
+
+#[tokio::test]
+async fn sides_take_two_players() {
+    let (server, _) = doubles_with_teams().await;
+
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "players": {
+                "ping": "alice",
+                "pong": "carol",
+                "partners": { "ping": "bob", "pong": "dave" },
+                "owner": "alice"
+            },
+            "gameState": { "mode": "doubles", "rotation": { "ping": "first", "pong": "first" } },
+            "rallyState": { "nextPartners": { "ping": "first", "pong": "first" } }
+        }));
+
+    let response = server
+        .post("/matches/test/claims/ping")
+        .json(&json!({ "player": "erin" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("ping side is already claimed");
+}
+
+#[tokio::test]
+async fn partners_alternate_hits() {
+    let (server, teams) = doubles_with_teams().await;
+
+    hit(&server, "ping", &teams.alice).await.assert_text("pong");
+    hit(&server, "pong", &teams.carol).await.assert_text("ping");
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "nextPartners": { "ping": "second", "pong": "second" } }
+        }));
+    hit(&server, "ping", &teams.bob).await.assert_text("pong");
+    hit(&server, "pong", &teams.dave).await.assert_text("ping");
+    hit(&server, "ping", &teams.alice).await.assert_text("pong");
+
+    // carol's turn
+    let response = hit(&server, "pong", &teams.dave).await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("MISS");
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 1, "pong": 0 }, "server": "pong" }
+        }));
+}
+
+#[tokio::test]
+async fn serve_rotates_between_all_four_players() {
+    let (server, teams) = doubles_with_teams().await;
+    // server and receiver of each point - the receiver serves next, to the last server's partner
+    let rotation = [
+        (&teams.alice, &teams.carol),
+        (&teams.carol, &teams.bob),
+        (&teams.bob, &teams.dave),
+        (&teams.dave, &teams.alice),
+        (&teams.alice, &teams.carol),
+    ];
+
+    for (point, (server_token, receiver_token)) in rotation.iter().enumerate() {
+        let state: Value = server.get(MATCH_ENDPOINT).await.json();
+        let serving_side = state["gameState"]["server"].as_str().unwrap().to_string();
+        let receiving_side = if serving_side == "ping" {
+            "pong"
+        } else {
+            "ping"
+        };
+
+        hit(&server, &serving_side, server_token)
+            .await
+            .assert_status_ok();
+        hit(&server, receiving_side, receiver_token)
+            .await
+            .assert_status_ok();
+        // ball is back with the server's side, the receiver hits again - wrong side
+        hit(&server, receiving_side, receiver_token)
+            .await
+            .assert_text("MISS");
+        let state: Value = server.get(MATCH_ENDPOINT).await.json();
+        assert_eq!(
+            state["gameState"]["score"][&serving_side],
+            point / 2 + 1,
+            "{state}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn free_seat_can_be_played_by_anyone() {
+    let server = setup_test_server_with_mode(GameMode::Doubles);
+    let alice = claim(&server, "ping", "alice").await;
+
+    let response = server.get("/matches/test/ping").await;
+    response.assert_status_forbidden();
+    response.assert_text("ping side is claimed by alice");
+
+    hit(&server, "ping", &alice).await.assert_text("pong");
+    server.get("/matches/test/pong").await.assert_text("ping");
+    // second ping seat is free
+    server.get("/matches/test/ping").await.assert_text("pong");
+    server.get("/matches/test/pong").await.assert_text("ping");
+    server
+        .get("/matches/test/ping")
+        .await
+        .assert_status_forbidden();
+}
@@ -0,0 +1,54 @@
+use serde_json::json;
+
+use crate::tests::utils::{init_test_state, setup_test_server, setup_test_server_from_state};
+use crate::{AppState, models::application::Readiness};
+
+#[tokio::test]
+async fn liveness_does_not_depend_on_anything() {
+    let server = setup_test_server();
+
+    let response = server.get("/healthz").await;
+    response.assert_status_ok();
+    response.assert_text("OK");
+}
+
+#[tokio::test]
+async fn not_ready_without_database() {
+    // test state has a pool pointing nowhere
+    let server = setup_test_server();
+
+    let response = server.get("/readyz").await;
+    response.assert_status_service_unavailable();
+    response.assert_json_contains(&json!({
+        "ready": false,
+        "shuttingDown": false,
+        "database": { "ok": false },
+        "migrations": { "ok": false },
+        "gameTables": { "ok": true },
+    }));
+}
+
+#[tokio::test]
+async fn not_ready_when_shutting_down() {
+    let state = init_test_state();
+    state.readiness.begin_shutdown();
+    let server = setup_test_server_from_state(state);
+
+    let response = server.get("/readyz").await;
+    response.assert_status_service_unavailable();
+    response.assert_json_contains(&json!({ "ready": false, "shuttingDown": true }));
+}
+
+#[tokio::test]
+async fn not_ready_until_tables_are_loaded() {
+    let state = AppState {
+        readiness: Readiness::default(),
+        ..init_test_state()
+    };
+    let server = setup_test_server_from_state(state);
+
+    server.get("/readyz").await.assert_json_contains(&json!({
+        "ready": false,
+        "gameTables": { "ok": false, "error": "Game tables not loaded yet" },
+    }));
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    config::{Config, RateLimitConfig},
+    tests::{
+        features::time_dependent::advance_time,
+        utils::{init_test_state_with_config, setup_test_server, setup_test_server_from_state},
+    },
+};
+
+const JOIN_ENDPOINT: &str = "/lobby/join";
+
+async fn wait_in_lobby(server: &TestServer, request: Value) -> String {
+    let response = server.post(JOIN_ENDPOINT).json(&request).await;
+    response.assert_status(StatusCode::ACCEPTED);
+    let body: Value = response.json();
+    assert_eq!(body["status"], "waiting");
+    assert!(body["token"].is_string());
+    body["ticket"].as_str().unwrap().to_string()
+}
+
+fn ticket_endpoint(ticket: &str) -> String {
+    format!("/lobby/tickets/{ticket}")
+}
+
+#[tokio::test]
+async fn players_with_other_rules_keep_waiting() {
+    let server = setup_test_server();
+    let alice = wait_in_lobby(&server, json!({ "player": "alice" })).await;
+    let bob = wait_in_lobby(&server, json!({ "player": "bob", "pointsToWin": 21 })).await;
+    wait_in_lobby(&server, json!({ "player": "carol", "airTimeSeconds": 5 })).await;
+
+    for ticket in [alice, bob] {
+        server
+            .get(&ticket_endpoint(&ticket))
+            .await
+            .assert_json(&json!({ "status": "waiting" }));
+    }
+}
+
+#[tokio::test]
+async fn player_waits_only_once() {
+    let server = setup_test_server();
+    wait_in_lobby(&server, json!({ "player": "alice" })).await;
+
+    let response = server
+        .post(JOIN_ENDPOINT)
+        .json(&json!({ "player": "alice", "pointsToWin": 21 }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("alice is already waiting in the lobby");
+}
+
+#[tokio::test]
+async fn preferences_are_validated() {
+    let server = setup_test_server();
+    for (request, message) in [
+        (
+            json!({ "player": "alice", "pointsToWin": 0 }),
+            "Points to win must be at least 1",
+        ),
+        (
+            json!({ "player": "alice", "airTimeSeconds": 0 }),
+            "Air time must be at least 1 second",
+        ),
+    ] {
+        let response = server.post(JOIN_ENDPOINT).json(&request).await;
+        response.assert_status_bad_request();
+        response.assert_text(message);
+    }
+}
+
+#[tokio::test]
+async fn leaving_ends_the_wait() {
+    let server = setup_test_server();
+    let ticket = wait_in_lobby(&server, json!({ "player": "alice" })).await;
+    let endpoint = ticket_endpoint(&ticket);
+
+    let (status, left) = tokio::join!(
+        async { server.get(&endpoint).add_query_param("wait", 10).await },
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            server.delete(&endpoint).await
+        }
+    );
+    left.assert_status(StatusCode::NO_CONTENT);
+    status.assert_json(&json!({ "status": "left" }));
+
+    let response = server.delete(&endpoint).await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Ticket is no longer waiting");
+}
+
+#[tokio::test]
+async fn unknown_tickets_are_not_found() {
+    let server = setup_test_server();
+    let endpoint = ticket_endpoint("nope");
+
+    for response in [server.get(&endpoint).await, server.delete(&endpoint).await] {
+        response.assert_status_not_found();
+        response.assert_text("Ticket not found");
+    }
+}
+
+#[tokio::test]
+async fn tickets_expire_and_are_forgotten() {
+    let server = setup_test_server();
+    let ticket = wait_in_lobby(&server, json!({ "player": "alice" })).await;
+    let endpoint = ticket_endpoint(&ticket);
+    let ttl = Config::default().lobby.ticket_ttl + Duration::from_secs(1);
+
+    advance_time(ttl).await;
+    server
+        .get(&endpoint)
+        .await
+        .assert_json(&json!({ "status": "expired" }));
+    // the player can look for a match again
+    wait_in_lobby(&server, json!({ "player": "alice" })).await;
+
+    advance_time(ttl).await;
+    server.get(&endpoint).await.assert_status_not_found();
+}
+
+#[tokio::test]
+async fn open_waits_are_limited() {
+    let config = Config {
+        rate_limits: RateLimitConfig {
+            open_waits_per_ip: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = setup_test_server_from_state(init_test_state_with_config(config));
+    let ticket = wait_in_lobby(&server, json!({ "player": "alice" })).await;
+    let endpoint = ticket_endpoint(&ticket);
+
+    let (first, second) = tokio::join!(
+        async { server.get(&endpoint).add_query_param("wait", 1).await },
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            server.get(&endpoint).await
+        }
+    );
+    first.assert_json(&json!({ "status": "waiting" }));
+    second.assert_status(StatusCode::TOO_MANY_REQUESTS);
+
+    // the slot is free again once the wait is over
+    server.get(&endpoint).await.assert_status_ok();
+}
@@ -0,0 +1,52 @@
+use crate::tests::utils::{PING_ENDPOINT, PONG_ENDPOINT, setup_test_server};
+
+/// Metrics are global and tests run in parallel, so only lower bounds can be checked
+fn metric_value(metrics: &str, series: &str) -> f64 {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(series))
+        .unwrap_or_else(|| panic!("{series} not found in metrics"))
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn gameplay_is_visible_in_metrics() {
+    let server = setup_test_server();
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    // pong tries to hit again - wrong side, so ping scores
+    server.get(PONG_ENDPOINT).await.assert_text("MISS");
+
+    let response = server.get("/metrics").await;
+    response.assert_status_ok();
+    response.assert_header("content-type", "text/plain; version=0.0.4");
+    let metrics = response.text();
+
+    assert!(metric_value(&metrics, r#"ping_pong_hits_total{side="ping"}"#) >= 1.);
+    assert!(metric_value(&metrics, r#"ping_pong_hits_total{side="pong"}"#) >= 1.);
+    assert!(
+        metric_value(
+            &metrics,
+            r#"ping_pong_misses_total{reason="wrong_side",side="pong"}"#
+        ) >= 1.
+    );
+    assert!(metric_value(&metrics, r#"ping_pong_points_total{side="ping"}"#) >= 1.);
+    assert!(metric_value(&metrics, "ping_pong_rally_length_hits_count") >= 1.);
+    assert!(metric_value(&metrics, "ping_pong_hit_time_to_deadline_seconds_count") >= 1.);
+    assert!(metric_value(&metrics, "ping_pong_active_matches") >= 1.);
+    assert!(
+        metric_value(
+            &metrics,
+            r#"ping_pong_http_requests_total{method="GET",route="/matches/{id}/ping",status="200"}"#
+        ) >= 1.
+    );
+    assert!(
+        metric_value(
+            &metrics,
+            r#"ping_pong_http_request_duration_seconds_count{method="GET",route="/matches/{id}/pong"}"#
+        ) >= 2.
+    );
+}
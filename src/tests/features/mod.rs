@@ -4,6 +4,24 @@
 //! of src/ (such as those in the tests/ directory) are compiled as separate crates without #[cfg(test)],
 //! so conditional compilation for tests does not apply to them.
 
+mod admin;
 mod basic_game;
+mod bots;
+mod claims;
+mod corrections;
+mod cors;
+mod doubles;
+mod health;
+mod lobby;
+mod metrics;
 mod multiple_matches;
+mod pause;
+mod queue;
+mod rate_limiting;
+mod reaction_time;
+mod request_id;
+mod stats;
 mod time_dependent;
+mod tournaments;
+mod wall;
+mod winning;
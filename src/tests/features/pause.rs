@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use jiff::Timestamp;
+use serde_json::{Value, json};
+
+use crate::{
+    BALL_AIR_TIME_SECONDS,
+    game_table::PLAYER_TOKEN_HEADER,
+    tests::{
+        features::{
+            corrections::{REFEREE_KEY, claim, server_with_referee},
+            time_dependent::advance_time,
+        },
+        utils::{MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT},
+    },
+};
+
+const PAUSE_ENDPOINT: &str = "/matches/test/pause";
+const RESUME_ENDPOINT: &str = "/matches/test/resume";
+
+fn hit_timeout(state: &Value) -> Timestamp {
+    state["rallyState"]["hitTimeoutTimestamp"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn paused_ball_keeps_its_remaining_air_time() {
+    let server = server_with_referee();
+    let owner_token = claim(&server, "ping", "alice").await;
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_text("pong");
+    let deadline_before_pause = hit_timeout(&server.get(MATCH_ENDPOINT).await.json());
+    advance_time(Duration::from_secs(10)).await;
+
+    server
+        .post(PAUSE_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "paused": true, "hitTimeoutTimestamp": null, "hitCount": 1 }
+        }));
+
+    // ball would be long gone if it wasn't paused
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS * 3)).await;
+    let response = server.get(PONG_ENDPOINT).await;
+    response.assert_status(StatusCode::LOCKED);
+    response.assert_text("Match is paused");
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_status(StatusCode::LOCKED);
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 0, "pong": 0 } },
+            "rallyState": { "side": "pong", "hitCount": 1 }
+        }));
+
+    let resumed: Value = server
+        .post(RESUME_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .json();
+    assert_eq!(resumed["rallyState"]["paused"], false);
+    // moved by exactly the time spent paused
+    assert_eq!(
+        hit_timeout(&resumed),
+        deadline_before_pause + Duration::from_secs(BALL_AIR_TIME_SECONDS * 3)
+    );
+
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS - 11)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "score": { "ping": 0, "pong": 0 } } }));
+
+    advance_time(Duration::from_secs(2)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "score": { "ping": 1, "pong": 0 } } }));
+}
+
+#[tokio::test]
+async fn rally_goes_on_after_resume() {
+    let server = server_with_referee();
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    server
+        .post(PAUSE_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+    server
+        .post(RESUME_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "side": "ping", "hitCount": 2, "paused": false }
+        }));
+}
+
+#[tokio::test]
+async fn serve_waits_for_resume() {
+    let server = server_with_referee();
+
+    server
+        .post(PAUSE_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "paused": true, "serveTimestamp": null }
+        }));
+    server
+        .get(PING_ENDPOINT)
+        .await
+        .assert_status(StatusCode::LOCKED);
+
+    server
+        .post(RESUME_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+}
+
+#[tokio::test]
+async fn pause_stays_when_rally_is_dropped() {
+    let server = server_with_referee();
+    let owner_token = claim(&server, "ping", "alice").await;
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await;
+    server
+        .post(PAUSE_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_status_ok();
+    server
+        .post("/matches/test/let")
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "paused": true, "hitCount": 0, "side": "ping" },
+            "gameState": { "lets": 1 }
+        }));
+
+    // nothing to count down after the rally is gone
+    server
+        .post(RESUME_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_json_contains(&json!({
+            "rallyState": { "paused": false, "hitTimeoutTimestamp": null }
+        }));
+}
+
+#[tokio::test]
+async fn only_owner_or_referee_can_pause() {
+    let server = server_with_referee();
+    let owner_token = claim(&server, "ping", "alice").await;
+    let other_token = claim(&server, "pong", "bob").await;
+
+    let response = server
+        .post(PAUSE_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &other_token)
+        .await;
+    response.assert_status(StatusCode::FORBIDDEN);
+    server
+        .post(PAUSE_ENDPOINT)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    server
+        .post(RESUME_ENDPOINT)
+        .authorization_bearer("wrong")
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    let response = server
+        .post(RESUME_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Match is not paused");
+
+    server
+        .post(PAUSE_ENDPOINT)
+        .add_header(PLAYER_TOKEN_HEADER, &owner_token)
+        .await
+        .assert_status_ok();
+    let response = server
+        .post(PAUSE_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Match is already paused");
+
+    // referee can resume the owner's pause
+    server
+        .post(RESUME_ENDPOINT)
+        .authorization_bearer(REFEREE_KEY)
+        .await
+        .assert_status_ok();
+}
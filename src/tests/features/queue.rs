@@ -0,0 +1,170 @@
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    config::Config,
+    game_table::PLAYER_TOKEN_HEADER,
+    models::{game::GameMode, settings::MatchSettings},
+    tests::{
+        features::corrections::claim,
+        utils::{
+            MATCH_ENDPOINT, init_test_state_with_config, setup_test_server,
+            setup_test_server_from_state, setup_test_server_with_mode,
+        },
+    },
+};
+
+const QUEUE_ENDPOINT: &str = "/matches/test/queue";
+
+fn server_playing_to(points_to_win: usize) -> TestServer {
+    let config = Config {
+        match_defaults: MatchSettings {
+            points_to_win: Some(points_to_win),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+async fn join(server: &TestServer, player: &str) -> Value {
+    let response = server
+        .post(QUEUE_ENDPOINT)
+        .json(&json!({ "player": player }))
+        .await;
+    response.assert_status(StatusCode::CREATED);
+    response.json()
+}
+
+/// `side` serves if it has to, then hits out of turn
+async fn lose_points(server: &TestServer, side: &str, token: &str, points: usize) {
+    let endpoint = format!("{MATCH_ENDPOINT}/{side}");
+    for _ in 0..points {
+        let state: Value = server.get(MATCH_ENDPOINT).await.json();
+        if state["gameState"]["server"] == side {
+            server
+                .get(&endpoint)
+                .add_header(PLAYER_TOKEN_HEADER, token)
+                .await
+                .assert_status_ok();
+        }
+        server
+            .get(&endpoint)
+            .add_header(PLAYER_TOKEN_HEADER, token)
+            .await
+            .assert_text("MISS");
+    }
+}
+
+#[tokio::test]
+async fn challengers_wait_in_line() {
+    let server = server_playing_to(2);
+
+    assert_eq!(join(&server, "alice").await["position"], 1);
+    let bob = join(&server, "bob").await;
+    assert_eq!(bob["position"], 2);
+    assert_eq!(bob["side"], Value::Null);
+    server
+        .get(QUEUE_ENDPOINT)
+        .await
+        .assert_json(&json!({ "queue": ["alice", "bob"] }));
+
+    let response = server
+        .post(QUEUE_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("alice is already at the table or in the queue");
+
+    let leave = || {
+        server
+            .delete(QUEUE_ENDPOINT)
+            .add_header(PLAYER_TOKEN_HEADER, bob["token"].as_str().unwrap())
+    };
+    leave().await.assert_status(StatusCode::NO_CONTENT);
+    leave().await.assert_status_not_found();
+    server
+        .get(QUEUE_ENDPOINT)
+        .await
+        .assert_json(&json!({ "queue": ["alice"] }));
+}
+
+#[tokio::test]
+async fn winner_stays_on() {
+    let server = server_playing_to(2);
+    let alice = claim(&server, "ping", "alice").await;
+    let bob = claim(&server, "pong", "bob").await;
+    let carol = join(&server, "carol").await;
+    join(&server, "dave").await;
+
+    lose_points(&server, "pong", &bob, 2).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 0, "pong": 0 }, "winner": null },
+            "players": { "ping": "alice", "pong": "carol", "queue": ["dave"] }
+        }));
+
+    // bob is out, carol plays with the token she got in the queue
+    server
+        .get(&format!("{MATCH_ENDPOINT}/pong"))
+        .add_header(PLAYER_TOKEN_HEADER, &bob)
+        .await
+        .assert_status_forbidden();
+    lose_points(&server, "ping", &alice, 2).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "players": { "ping": "dave", "pong": "carol", "queue": [] }
+        }));
+    server
+        .get(&format!("{MATCH_ENDPOINT}/ping"))
+        .add_header(PLAYER_TOKEN_HEADER, carol["token"].as_str().unwrap())
+        .await
+        .assert_status_forbidden();
+}
+
+#[tokio::test]
+async fn finished_match_takes_the_first_challenger() {
+    let server = server_playing_to(2);
+    claim(&server, "ping", "alice").await;
+    let bob = claim(&server, "pong", "bob").await;
+
+    // nobody waiting, the match stays over
+    lose_points(&server, "pong", &bob, 2).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "winner": "ping" } }));
+
+    let carol = join(&server, "carol").await;
+    assert_eq!(carol["position"], Value::Null);
+    assert_eq!(carol["side"], "pong");
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "winner": null },
+            "players": { "pong": "carol", "queue": [] }
+        }));
+}
+
+#[tokio::test]
+async fn queue_needs_a_match_that_ends() {
+    let response = setup_test_server()
+        .post(QUEUE_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Match has no points to win, the queue would never move");
+
+    let response = setup_test_server_with_mode(GameMode::Wall)
+        .post(QUEUE_ENDPOINT)
+        .json(&json!({ "player": "alice" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Queue is for classic matches only");
+}
@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+
+use crate::{
+    config::{Config, RateLimitConfig},
+    rate_limit::API_KEY_HEADER,
+    tests::utils::{
+        MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, init_test_state_with_config, mock_clock,
+        setup_test_server_from_state,
+    },
+};
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+const CLIENT_A: &str = "203.0.113.1";
+const CLIENT_B: &str = "203.0.113.2";
+const CLIENT_C: &str = "203.0.113.3";
+
+fn limited_server(rate_limits: RateLimitConfig) -> axum_test::TestServer {
+    let config = Config {
+        rate_limits: RateLimitConfig {
+            trusted_proxies: 1,
+            ..rate_limits
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+#[tokio::test]
+async fn hits_are_limited_per_ip() {
+    let server = limited_server(RateLimitConfig {
+        hits_per_second_per_ip: Some(2),
+        ..Default::default()
+    });
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await
+        .assert_text("pong");
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await
+        .assert_text("ping");
+
+    let throttled = server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await;
+    throttled.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    throttled.assert_header("retry-after", "1");
+
+    // throttled hit is not a miss - nothing happened to the game
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&serde_json::json!({
+            "rallyState": { "side": "ping", "hitCount": 2 }
+        }));
+
+    // other clients have their own budget
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_B)
+        .await
+        .assert_text("pong");
+
+    // and budget comes back with time
+    mock_clock::advance(Duration::from_secs(1));
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await
+        .assert_text("ping");
+}
+
+#[tokio::test]
+async fn hits_are_limited_per_match() {
+    let server = limited_server(RateLimitConfig {
+        hits_per_second_per_match: Some(1),
+        ..Default::default()
+    });
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await
+        .assert_text("pong");
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_B)
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+
+    // reading match state is not limited
+    server.get(MATCH_ENDPOINT).await.assert_status_ok();
+}
+
+#[tokio::test]
+async fn hits_throttled_per_match_keep_the_client_budget() {
+    let server = limited_server(RateLimitConfig {
+        hits_per_second_per_ip: Some(1),
+        hits_per_second_per_match: Some(2),
+        ..Default::default()
+    });
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await
+        .assert_text("pong");
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_B)
+        .await
+        .assert_text("ping");
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_C)
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+
+    // match has a token again, the client never lost theirs
+    mock_clock::advance(Duration::from_millis(500));
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_C)
+        .await
+        .assert_text("pong");
+}
+
+#[tokio::test]
+async fn trusted_clients_are_exempt() {
+    let server = limited_server(RateLimitConfig {
+        hits_per_second_per_ip: Some(1),
+        trusted_ips: vec![CLIENT_A.parse().unwrap()],
+        api_keys: vec!["bot-key".to_string()],
+        ..Default::default()
+    });
+
+    for _ in 0..5 {
+        server
+            .get(PING_ENDPOINT)
+            .add_header(FORWARDED_FOR, CLIENT_A)
+            .await
+            .assert_text("pong");
+        server
+            .get(PONG_ENDPOINT)
+            .add_header(FORWARDED_FOR, CLIENT_B)
+            .add_header(API_KEY_HEADER, "bot-key")
+            .await
+            .assert_text("ping");
+    }
+
+    // wrong key does not help
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_B)
+        .add_header(API_KEY_HEADER, "guess")
+        .await
+        .assert_text("pong");
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_B)
+        .add_header(API_KEY_HEADER, "guess")
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn forwarded_for_is_ignored_unless_trusted() {
+    let config = Config {
+        rate_limits: RateLimitConfig {
+            hits_per_second_per_ip: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = setup_test_server_from_state(init_test_state_with_config(config));
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_A)
+        .await
+        .assert_text("pong");
+    // spoofed header doesn't get a fresh budget
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, CLIENT_B)
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn spoofed_forwarded_for_entries_are_ignored() {
+    let server = limited_server(RateLimitConfig {
+        hits_per_second_per_ip: Some(1),
+        ..Default::default()
+    });
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, format!("{CLIENT_B}, {CLIENT_A}"))
+        .await
+        .assert_text("pong");
+    // the client made up the left entry, the proxy added the right one
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, format!("{CLIENT_C}, {CLIENT_A}"))
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn client_is_found_behind_several_proxies() {
+    let config = Config {
+        rate_limits: RateLimitConfig {
+            hits_per_second_per_ip: Some(1),
+            trusted_proxies: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = setup_test_server_from_state(init_test_state_with_config(config));
+
+    server
+        .get(PING_ENDPOINT)
+        .add_header(FORWARDED_FOR, format!("{CLIENT_C}, {CLIENT_A}, {CLIENT_B}"))
+        .await
+        .assert_text("pong");
+    server
+        .get(PONG_ENDPOINT)
+        .add_header(FORWARDED_FOR, format!("{CLIENT_A}, {CLIENT_B}"))
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    config::{AdminConfig, Config},
+    models::settings::{MatchSettings, TooEarly},
+    tests::{
+        features::time_dependent::advance_time,
+        utils::{
+            MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, init_test_state_with_config,
+            setup_test_server_from_state,
+        },
+    },
+};
+
+const ADMIN_KEY: &str = "rootkey";
+const VIEWER_KEY: &str = "viewkey";
+const SETTINGS_ENDPOINT: &str = "/admin/matches/test/settings";
+
+fn server_with_defaults(min_reaction_time_ms: u64, too_early: TooEarly) -> TestServer {
+    let config = Config {
+        match_defaults: MatchSettings {
+            min_reaction_time_ms,
+            too_early,
+            ..Default::default()
+        },
+        admin: AdminConfig {
+            api_keys: vec![
+                format!("root:{ADMIN_KEY}:read,write").parse().unwrap(),
+                format!("viewer:{VIEWER_KEY}:read").parse().unwrap(),
+            ],
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+#[tokio::test]
+async fn too_early_return_is_rejected() {
+    let server = server_with_defaults(500, TooEarly::Reject);
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    let before: Value = server.get(MATCH_ENDPOINT).await.json();
+    assert!(before["rallyState"]["lastHitTimestamp"].is_string());
+
+    advance_time(Duration::from_millis(499)).await;
+    let response = server.get(PONG_ENDPOINT).await;
+    response.assert_status(StatusCode::TOO_EARLY);
+    response.assert_text("Too early");
+
+    // ball is still in the air, nothing changed
+    let after: Value = server.get(MATCH_ENDPOINT).await.json();
+    assert_eq!(before, after);
+
+    advance_time(Duration::from_millis(1)).await;
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+}
+
+#[tokio::test]
+async fn too_early_return_can_be_a_miss() {
+    let server = server_with_defaults(500, TooEarly::Miss);
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    advance_time(Duration::from_millis(100)).await;
+    let response = server.get(PONG_ENDPOINT).await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("MISS");
+
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 1, "pong": 0 }, "server": "pong" },
+            "rallyState": { "hitCount": 0, "lastHitTimestamp": null }
+        }));
+
+    // serve doesn't wait for anything
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+}
+
+#[tokio::test]
+async fn no_minimum_by_default() {
+    let server = server_with_defaults(0, TooEarly::Reject);
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+}
+
+#[tokio::test]
+async fn admin_can_change_settings_of_a_match() {
+    let server = server_with_defaults(0, TooEarly::Reject);
+
+    server
+        .put(SETTINGS_ENDPOINT)
+        .authorization_bearer(VIEWER_KEY)
+        .json(&json!({ "minReactionTimeMs": 300, "tooEarly": "miss" }))
+        .await
+        .assert_status_forbidden();
+
+    server
+        .put(SETTINGS_ENDPOINT)
+        .authorization_bearer(ADMIN_KEY)
+        .json(&json!({ "minReactionTimeMs": 300, "tooEarly": "miss" }))
+        .await
+        .assert_json_contains(&json!({
+            "settings": { "minReactionTimeMs": 300, "tooEarly": "miss" }
+        }));
+
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    server
+        .get(PONG_ENDPOINT)
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "score": { "ping": 1, "pong": 0 } } }));
+}
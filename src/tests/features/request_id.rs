@@ -0,0 +1,28 @@
+use crate::REQUEST_ID_HEADER;
+use crate::tests::utils::{MATCH_ENDPOINT, PING_ENDPOINT, setup_test_server};
+
+#[tokio::test]
+async fn request_id_is_generated_when_missing() {
+    let server = setup_test_server();
+
+    let first = server.get(MATCH_ENDPOINT).await;
+    let second = server.get(MATCH_ENDPOINT).await;
+
+    let first_id = first.header(REQUEST_ID_HEADER);
+    let second_id = second.header(REQUEST_ID_HEADER);
+    assert!(!first_id.is_empty());
+    assert_ne!(first_id, second_id);
+}
+
+#[tokio::test]
+async fn incoming_request_id_is_echoed_back() {
+    let server = setup_test_server();
+
+    let response = server
+        .get(PING_ENDPOINT)
+        .add_header(REQUEST_ID_HEADER, "disputed-point-42")
+        .await;
+
+    response.assert_text("pong");
+    response.assert_header(REQUEST_ID_HEADER, "disputed-point-42");
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    BALL_AIR_TIME_SECONDS,
+    config::Config,
+    models::settings::MatchSettings,
+    tests::{
+        features::time_dependent::advance_time,
+        utils::{
+            MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, init_test_state_with_config,
+            setup_test_server, setup_test_server_from_state,
+        },
+    },
+};
+
+const STATS_ENDPOINT: &str = "/matches/test/stats";
+
+#[tokio::test]
+async fn stats_of_a_new_match_are_empty() {
+    let server = setup_test_server();
+    let side = json!({
+        "hits": 0,
+        "pointsWon": 0,
+        "pointsWonOnTimeouts": 0,
+        "pointsWonOnWrongSideHits": 0,
+        "matchPointsSaved": 0,
+        "averageReactionTimeMs": null,
+        "medianReactionTimeMs": null,
+        "serves": 0,
+        "serveWinPercentage": null
+    });
+
+    server.get(STATS_ENDPOINT).await.assert_json(&json!({
+        "rallies": 0,
+        "averageRallyLength": null,
+        "longestRallyByHits": null,
+        "longestRallyByDuration": null,
+        "ping": side,
+        "pong": side
+    }));
+}
+
+#[tokio::test]
+async fn finished_rallies_are_counted() {
+    let server = setup_test_server();
+
+    // ping serves and hits out of turn after the return
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    advance_time(Duration::from_millis(300)).await;
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    advance_time(Duration::from_millis(500)).await;
+    server.get(PING_ENDPOINT).await.assert_text("pong");
+    server.get(PING_ENDPOINT).await.assert_text("MISS");
+
+    // ongoing rallies are left out
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+    server
+        .get(STATS_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallies": 1,
+            "pong": { "hits": 1 }
+        }));
+
+    // ping doesn't return pong's serve
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "score": { "ping": 0, "pong": 2 } } }));
+
+    server
+        .get(STATS_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "rallies": 2,
+            "averageRallyLength": 2.0,
+            "longestRallyByHits": { "hitCount": 3 },
+            "longestRallyByDuration": { "hitCount": 1 },
+            "ping": {
+                "hits": 2,
+                "pointsWon": 0,
+                "averageReactionTimeMs": 500.0,
+                // middle of the 500-549 ms bucket
+                "medianReactionTimeMs": 525.0,
+                "serves": 1,
+                "serveWinPercentage": 0.0
+            },
+            "pong": {
+                "hits": 2,
+                "pointsWon": 2,
+                "pointsWonOnTimeouts": 1,
+                "pointsWonOnWrongSideHits": 1,
+                "averageReactionTimeMs": 300.0,
+                "medianReactionTimeMs": 325.0,
+                "serves": 1,
+                "serveWinPercentage": 100.0
+            }
+        }));
+}
+
+/// `side` serves if it has to, then hits out of turn
+async fn lose_point(server: &TestServer, side: &str) {
+    let endpoint = format!("{MATCH_ENDPOINT}/{side}");
+    let state: Value = server.get(MATCH_ENDPOINT).await.json();
+    if state["gameState"]["server"] == side {
+        server.get(&endpoint).await.assert_status_ok();
+    }
+    server.get(&endpoint).await.assert_text("MISS");
+}
+
+#[tokio::test]
+async fn saved_match_points_are_counted() {
+    let config = Config {
+        match_defaults: MatchSettings {
+            points_to_win: Some(3),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = setup_test_server_from_state(init_test_state_with_config(config));
+
+    for _ in 0..2 {
+        lose_point(&server, "pong").await;
+    }
+    // pong saves at 2:0 and 2:1, ping couldn't have won at 3:2
+    for _ in 0..3 {
+        lose_point(&server, "ping").await;
+    }
+
+    server
+        .get(STATS_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "ping": { "matchPointsSaved": 0 },
+            "pong": { "matchPointsSaved": 2, "pointsWon": 3 }
+        }));
+}
@@ -8,7 +8,7 @@ use crate::tests::utils::{
     setup_test_server_with_matches,
 };
 
-async fn advance_time(duration: Duration) {
+pub async fn advance_time(duration: Duration) {
     tokio::time::pause();
     mock_clock::advance(duration);
     // yield to actually start processing tasks
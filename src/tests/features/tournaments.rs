@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+    models::{
+        game::{Score, Side},
+        player::PlayerName,
+        tournament::{Tournament, TournamentFormat},
+    },
+    tests::utils::{init_test_state, setup_test_server, setup_test_server_from_state},
+    tournament::RunningTournament,
+};
+
+/// Tournaments can't be created without a database, so this one is there from the start.
+/// alice got a bye, bob and carol played - carol won.
+fn server_with_tournament() -> TestServer {
+    let players: Vec<PlayerName> = ["alice", "bob", "carol"]
+        .map(|name| PlayerName::try_from(name.to_string()).unwrap())
+        .to_vec();
+    let mut tournament = Tournament {
+        id: 7,
+        name: "Friday cup".to_string(),
+        format: TournamentFormat::SingleElimination,
+        matches: Tournament::generate_matches(TournamentFormat::SingleElimination, &players),
+        players,
+        points_to_win: 11,
+    };
+    tournament.advance();
+    assert!(tournament.record_result(1, Side::Pong, Score { ping: 7, pong: 11 }));
+    tournament.advance();
+
+    let state = init_test_state();
+    let tokens = vec![String::new(); 3];
+    state.tournaments.write().unwrap().insert(
+        7,
+        Arc::new(Mutex::new(RunningTournament::new(tournament, tokens))),
+    );
+    setup_test_server_from_state(state)
+}
+
+#[tokio::test]
+async fn invalid_tournaments_are_rejected() {
+    let server = setup_test_server();
+
+    for (body, message) in [
+        (
+            json!({ "name": "cup", "format": "round_robin", "players": ["alice"] }),
+            "Tournament needs 2 to 32 players",
+        ),
+        (
+            json!({ "name": "cup", "format": "round_robin", "players": ["alice", "bob", "alice"] }),
+            "Player alice is listed twice",
+        ),
+        (
+            json!({ "name": " ", "format": "round_robin", "players": ["alice", "bob"] }),
+            "Tournament name must be 1 to 100 characters",
+        ),
+        (
+            json!({ "name": "cup", "format": "round_robin", "players": ["alice", "bob"], "pointsToWin": 0 }),
+            "Points to win must be at least 1",
+        ),
+    ] {
+        let response = server.post("/tournaments").json(&body).await;
+        response.assert_status_bad_request();
+        response.assert_text(message);
+    }
+
+    server
+        .post("/tournaments")
+        .json(&json!({ "name": "cup", "format": "swiss", "players": ["alice", "bob"] }))
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn unknown_tournament_is_not_found() {
+    let server = setup_test_server();
+
+    for endpoint in ["/tournaments/1/standings", "/tournaments/1/bracket"] {
+        let response = server.get(endpoint).await;
+        response.assert_status_not_found();
+        response.assert_text("Tournament 1 not found");
+    }
+    server
+        .get("/tournaments")
+        .await
+        .assert_json(&json!({ "tournaments": [] }));
+}
+
+#[tokio::test]
+async fn bracket_shows_who_plays_whom() {
+    let server = server_with_tournament();
+
+    server.get("/tournaments").await.assert_json(&json!({
+        "tournaments": [{
+            "id": 7,
+            "name": "Friday cup",
+            "format": "single_elimination",
+            "players": 3,
+            "finished": false,
+            "champion": null
+        }]
+    }));
+
+    server
+        .get("/tournaments/7/bracket")
+        .await
+        .assert_json(&json!({
+            "format": "single_elimination",
+            "rounds": [
+                {
+                    "bracket": "main",
+                    "round": 1,
+                    "matches": [
+                        {
+                            "number": 0,
+                            "ping": { "player": "alice" },
+                            "pong": "bye",
+                            "pingPlayer": "alice",
+                            "pongPlayer": null,
+                            "table": null,
+                            "winner": "alice",
+                            "score": null
+                        },
+                        {
+                            "number": 1,
+                            "ping": { "player": "bob" },
+                            "pong": { "player": "carol" },
+                            "pingPlayer": "bob",
+                            "pongPlayer": "carol",
+                            "table": null,
+                            "winner": "carol",
+                            "score": { "ping": 7, "pong": 11 }
+                        }
+                    ]
+                },
+                {
+                    "bracket": "main",
+                    "round": 2,
+                    "matches": [{
+                        "number": 2,
+                        "ping": { "winnerOf": 0 },
+                        "pong": { "winnerOf": 1 },
+                        "pingPlayer": "alice",
+                        "pongPlayer": "carol",
+                        "table": null,
+                        "winner": null,
+                        "score": null
+                    }]
+                }
+            ]
+        }));
+}
+
+#[tokio::test]
+async fn standings_follow_results() {
+    let server = server_with_tournament();
+
+    server
+        .get("/tournaments/7/standings")
+        .await
+        .assert_json(&json!({
+            "finished": false,
+            "champion": null,
+            "standings": [
+                { "player": "carol", "played": 1, "wins": 1, "losses": 0, "pointsFor": 11, "pointsAgainst": 7, "eliminated": false },
+                { "player": "alice", "played": 0, "wins": 0, "losses": 0, "pointsFor": 0, "pointsAgainst": 0, "eliminated": false },
+                { "player": "bob", "played": 1, "wins": 0, "losses": 1, "pointsFor": 7, "pointsAgainst": 11, "eliminated": true }
+            ]
+        }));
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::{
+    BALL_AIR_TIME_SECONDS,
+    models::game::GameMode,
+    tests::{
+        features::time_dependent::advance_time,
+        utils::{
+            MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, setup_test_server,
+            setup_test_server_with_mode,
+        },
+    },
+};
+
+#[tokio::test]
+async fn wall_returns_every_ball() {
+    let server = setup_test_server_with_mode(GameMode::Wall);
+
+    for _ in 0..3 {
+        server.get(PING_ENDPOINT).await.assert_text("ping");
+    }
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "mode": "wall" },
+            "rallyState": { "side": "ping", "hitCount": 3 }
+        }));
+
+    let response = server.get(PONG_ENDPOINT).await;
+    response.assert_status_forbidden();
+    response.assert_text("pong side is a wall");
+}
+
+#[tokio::test]
+async fn missed_ball_ends_the_streak() {
+    let server = setup_test_server_with_mode(GameMode::Wall);
+
+    for _ in 0..4 {
+        server.get(PING_ENDPOINT).await;
+    }
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "wall": { "lastStreak": 4, "bestStreak": 4, "rallies": 1 } }
+        }));
+
+    server.get(PING_ENDPOINT).await;
+    advance_time(Duration::from_secs(BALL_AIR_TIME_SECONDS + 1)).await;
+
+    // nobody scores and the player keeps serving
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": {
+                "server": "ping",
+                "score": { "ping": 0, "pong": 0 },
+                "wall": { "lastStreak": 1, "bestStreak": 4, "rallies": 2 },
+                "longestRally": { "hitCount": 4 }
+            },
+            "rallyState": { "side": "ping", "hitCount": 0 }
+        }));
+}
+
+#[tokio::test]
+async fn wall_side_cant_be_taken() {
+    let server = setup_test_server_with_mode(GameMode::Wall);
+
+    let response = server
+        .post("/matches/test/claims/pong")
+        .json(&json!({ "player": "alice" }))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("pong side is a wall");
+
+    let response = server
+        .post("/matches/test/bots/pong")
+        .json(&json!({}))
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("pong side is a wall");
+
+    // a bot can practice against it though
+    server
+        .post("/matches/test/bots/ping")
+        .json(&json!({}))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn mode_of_existing_match_cant_change() {
+    let server = setup_test_server_with_mode(GameMode::Wall);
+    let response = server
+        .get(MATCH_ENDPOINT)
+        .add_query_param("mode", "classic")
+        .await;
+    response.assert_status(StatusCode::CONFLICT);
+    response.assert_text("Match test is a wall match");
+    server
+        .get(MATCH_ENDPOINT)
+        .add_query_param("mode", "wall")
+        .await
+        .assert_status_ok();
+
+    let server = setup_test_server();
+    server
+        .get(MATCH_ENDPOINT)
+        .add_query_param("mode", "wall")
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({ "gameState": { "mode": "classic", "wall": null } }));
+}
@@ -0,0 +1,80 @@
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use crate::{
+    config::Config,
+    models::settings::MatchSettings,
+    tests::utils::{
+        MATCH_ENDPOINT, PING_ENDPOINT, PONG_ENDPOINT, init_test_state_with_config,
+        setup_test_server_from_state,
+    },
+};
+
+fn server_playing_to(points_to_win: usize) -> TestServer {
+    let config = Config {
+        match_defaults: MatchSettings {
+            points_to_win: Some(points_to_win),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    setup_test_server_from_state(init_test_state_with_config(config))
+}
+
+/// Serves if needed, then hits out of turn
+async fn miss(server: &TestServer, endpoint: &str, side: &str) {
+    let state: Value = server.get(MATCH_ENDPOINT).await.json();
+    if state["gameState"]["server"] == side {
+        server.get(endpoint).await.assert_status_ok();
+    }
+    server.get(endpoint).await.assert_text("MISS");
+}
+
+#[tokio::test]
+async fn match_is_won_with_a_two_point_lead() {
+    let server = server_playing_to(3);
+
+    for _ in 0..2 {
+        miss(&server, PING_ENDPOINT, "ping").await;
+    }
+    for _ in 0..3 {
+        miss(&server, PONG_ENDPOINT, "pong").await;
+    }
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 3, "pong": 2 }, "winner": null }
+        }));
+
+    miss(&server, PONG_ENDPOINT, "pong").await;
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 4, "pong": 2 }, "winner": "ping" }
+        }));
+
+    for endpoint in [PING_ENDPOINT, PONG_ENDPOINT] {
+        let response = server.get(endpoint).await;
+        response.assert_status(StatusCode::CONFLICT);
+        response.assert_text("Match is over");
+    }
+}
+
+#[tokio::test]
+async fn matches_go_on_forever_by_default() {
+    let server = setup_test_server_from_state(init_test_state_with_config(Config::default()));
+
+    for _ in 0..15 {
+        miss(&server, PONG_ENDPOINT, "pong").await;
+    }
+    server
+        .get(MATCH_ENDPOINT)
+        .await
+        .assert_json_contains(&json!({
+            "gameState": { "score": { "ping": 15, "pong": 0 }, "winner": null }
+        }));
+    server.get(PONG_ENDPOINT).await.assert_text("ping");
+}
@@ -1,12 +1,19 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
 use axum_test::TestServer;
 use sqlx::PgPool;
 
 use crate::{
-    AppState, create_app_from_state,
+    AppState,
+    config::Config,
+    create_app_from_state,
     database::{TableDbSyncHandle, TableUid},
-    models::game::{GameState, TableState},
+    models::{
+        application::Readiness,
+        game::{GameMode, GameState, TableState},
+        player::Claims,
+    },
+    rate_limit::RateLimits,
 };
 
 pub const MATCH_ID: &str = "test";
@@ -19,7 +26,11 @@ pub fn setup_test_server() -> TestServer {
 }
 
 pub fn setup_test_server_with_matches(ids: &[&str]) -> TestServer {
-    let state = init_test_state_with_matches(ids);
+    let state = init_test_state_with_matches(ids, Config::default());
+    setup_test_server_from_state(state)
+}
+
+pub fn setup_test_server_from_state(state: AppState) -> TestServer {
     let app = create_app_from_state(state);
     TestServer::builder()
         .mock_transport()
@@ -78,7 +89,48 @@ pub mod mock_clock {
     }
 }
 
-fn init_test_state_with_matches(ids: &[&str]) -> AppState {
+pub mod mock_audit_log {
+    use std::cell::RefCell;
+
+    use crate::database::NewAuditEntry;
+
+    // Same as the mock clock, each test thread has its own log
+    thread_local! {
+        static ENTRIES: RefCell<Vec<NewAuditEntry>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub fn insert(entry: NewAuditEntry) {
+        ENTRIES.with(|entries| entries.borrow_mut().push(entry));
+    }
+
+    /// Entries recorded on this thread so far, oldest first
+    pub fn take() -> Vec<NewAuditEntry> {
+        ENTRIES.with(|entries| entries.take())
+    }
+}
+
+pub fn init_test_state() -> AppState {
+    init_test_state_with_config(Config::default())
+}
+
+pub fn init_test_state_with_config(config: Config) -> AppState {
+    init_test_state_with_matches(&[MATCH_ID], config)
+}
+
+fn init_test_state_with_matches(ids: &[&str], config: Config) -> AppState {
+    init_test_state_with_games(ids, GameMode::Classic, config)
+}
+
+/// Matches can't be created without a database, so other modes have to be there from the start
+pub fn setup_test_server_with_mode(mode: GameMode) -> TestServer {
+    setup_test_server_from_state(init_test_state_with_games(
+        &[MATCH_ID],
+        mode,
+        Config::default(),
+    ))
+}
+
+fn init_test_state_with_games(ids: &[&str], mode: GameMode, config: Config) -> AppState {
     let dummy_pool =
         PgPool::connect_lazy("postgres://localhost/unused").expect("Failed to connect to database");
     let tables = ids
@@ -88,15 +140,26 @@ fn init_test_state_with_matches(ids: &[&str]) -> AppState {
             (
                 TableUid::parse(id).unwrap(),
                 TableState::new(
-                    GameState::default(),
-                    TableDbSyncHandle::new(i as i64, &dummy_pool),
+                    GameState::new(mode),
+                    Claims::default(),
+                    config.match_defaults.clone(),
+                    TableDbSyncHandle::new(i as i64, &dummy_pool, None),
                 ),
             )
         })
         .collect();
 
+    let readiness = Readiness::default();
+    readiness.mark_tables_loaded();
+
     AppState {
-        game_tables: Arc::new(RwLock::new(tables)),
+        game_tables: Arc::new(tables),
         db_pool: dummy_pool,
+        readiness,
+        rate_limits: Arc::new(RateLimits::new(&config.rate_limits)),
+        config: Arc::new(config),
+        tournaments: Default::default(),
+        lobby: Default::default(),
+        cluster: None,
     }
 }
@@ -0,0 +1,465 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, watch};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    database::{DbError, TableUid, create_tournament, get_tournaments, save_tournament_matches},
+    game_table::{archive_table, open_table},
+    models::{
+        application::AppState,
+        game::{GameState, Score},
+        player::{Claim, PlayerName},
+        settings::{MAX_POINTS_TO_WIN, MatchSettings},
+        tournament::{Bracket, Slot, Standing, Tournament, TournamentFormat},
+    },
+    rate_limit::limit_match_creations,
+};
+
+/// When neither the request nor the config say otherwise
+const DEFAULT_POINTS_TO_WIN: usize = 11;
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Tournament with the tokens of its players, by seed
+pub struct RunningTournament {
+    pub tournament: Tournament,
+    tokens: Vec<String>,
+}
+
+impl RunningTournament {
+    pub fn new(tournament: Tournament, tokens: Vec<String>) -> Self {
+        RunningTournament { tournament, tokens }
+    }
+
+    fn token_of(&self, player: &PlayerName) -> &str {
+        let seed = self
+            .tournament
+            .players
+            .iter()
+            .position(|known| known == player)
+            .expect("tournament matches only have its own players");
+        &self.tokens[seed]
+    }
+}
+
+pub type Tournaments = Arc<RwLock<HashMap<i64, Arc<Mutex<RunningTournament>>>>>;
+
+pub fn tournament_routes(state: AppState) -> Router<AppState> {
+    let creation = Router::new()
+        .route("/", post(create))
+        .route_layer(middleware::from_fn_with_state(state, limit_match_creations));
+
+    Router::new()
+        .route("/", get(list))
+        .route("/{id}/standings", get(standings))
+        .route("/{id}/bracket", get(bracket))
+        .merge(creation)
+}
+
+/// Loads stored tournaments, keeps following their unfinished matches and opens
+/// tables for matches that got ready while the server was down
+#[instrument(skip_all)]
+pub async fn resume_tournaments(state: &AppState) -> Result<(), DbError> {
+    for (tournament, tokens) in get_tournaments(&state.db_pool).await? {
+        let id = tournament.id;
+        let entry = Arc::new(Mutex::new(RunningTournament::new(tournament, tokens)));
+        state
+            .tournaments
+            .write()
+            .expect("tournaments write lock was poisoned")
+            .insert(id, entry.clone());
+
+        let running = entry.lock().await;
+        for (number, game) in running.tournament.matches.iter().enumerate() {
+            let Some(uid) = game.table.as_ref().filter(|_| game.outcome.is_none()) else {
+                continue;
+            };
+            let finished = state
+                .game_tables
+                .get(uid)
+                .map(|table_state| table_state.finished());
+            match finished {
+                Some(finished) => watch_result(state.clone(), entry.clone(), number, finished),
+                None => warn!(tournament_id = id, match_id = %uid, "Tournament match is archived"),
+            }
+        }
+        drop(running);
+        advance(state, &entry, None).await;
+    }
+    Ok(())
+}
+
+/// Records the result if there is one, then opens tables for matches that can be played
+async fn advance(
+    state: &AppState,
+    entry: &Arc<Mutex<RunningTournament>>,
+    result: Option<(usize, GameState)>,
+) {
+    let mut running = entry.lock().await;
+    let id = running.tournament.id;
+
+    if let Some((number, game_state)) = result {
+        let winner = game_state
+            .winner
+            .expect("only finished matches have a result");
+        if !running
+            .tournament
+            .record_result(number, winner, game_state.score)
+        {
+            return;
+        }
+        info!(tournament_id = id, number, "Tournament match finished");
+        if let Some(uid) = &running.tournament.matches[number].table {
+            archive_finished(state, id, uid).await;
+        }
+    }
+
+    for number in running.tournament.advance() {
+        match open_tournament_table(state, &running, number).await {
+            Ok((uid, finished)) => {
+                running.tournament.matches[number].table = Some(uid);
+                watch_result(state.clone(), entry.clone(), number, finished);
+            }
+            Err(e) => {
+                // the next result or restart tries again
+                error!(tournament_id = id, number, error = %e, "Failed to open tournament match");
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = save_tournament_matches(&state.db_pool, id, &running.tournament.matches).await {
+        error!(tournament_id = id, error = %e, "Error while saving tournament matches in database");
+    }
+}
+
+/// The result is final, so the table goes the way an admin archives it
+async fn archive_finished(state: &AppState, id: i64, uid: &TableUid) {
+    let Some(table_state) = state.game_tables.get(uid) else {
+        return;
+    };
+    match archive_table(state, uid, &table_state).await {
+        Ok(()) => info!(tournament_id = id, match_id = %uid, "Tournament match archived"),
+        Err(e) => {
+            error!(tournament_id = id, match_id = %uid, error = %e, "Failed to archive tournament match");
+        }
+    }
+}
+
+/// Ends on its own once the table is gone, e.g. archived
+fn watch_result(
+    state: AppState,
+    entry: Arc<Mutex<RunningTournament>>,
+    number: usize,
+    mut finished: watch::Receiver<Option<GameState>>,
+) {
+    tokio::spawn(async move {
+        let Ok(Some(game_state)) = finished
+            .wait_for(Option::is_some)
+            .await
+            .map(|game_state| game_state.clone())
+        else {
+            return;
+        };
+        advance(&state, &entry, Some((number, game_state))).await;
+    });
+}
+
+/// New table with both players seated by their tournament tokens
+async fn open_tournament_table(
+    state: &AppState,
+    running: &RunningTournament,
+    number: usize,
+) -> Result<(TableUid, watch::Receiver<Option<GameState>>), String> {
+    let (ping, pong) = running
+        .tournament
+        .players_of(number)
+        .expect("only ready matches get a table");
+    let settings = MatchSettings {
+        points_to_win: Some(running.tournament.points_to_win),
+        ..state.config.match_defaults.clone()
+    };
+    let [ping, pong] = [ping, pong].map(|player| Claim {
+        token: running.token_of(&player).to_string(),
+        player,
+    });
+
+    let (uid, table_state) = open_table(state, 't', settings, ping, pong).await?;
+    Ok((uid, table_state.finished()))
+}
+
+/// Tournament tables have their players set by the bracket
+pub async fn is_tournament_table(state: &AppState, uid: &TableUid) -> bool {
+    let entries: Vec<_> = state
+        .tournaments
+        .read()
+        .expect("tournaments read lock was poisoned")
+        .values()
+        .cloned()
+        .collect();
+    for entry in entries {
+        let running = entry.lock().await;
+        if running
+            .tournament
+            .matches
+            .iter()
+            .any(|game| game.table.as_ref() == Some(uid))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+fn find(state: &AppState, id: i64) -> Result<Arc<Mutex<RunningTournament>>, (StatusCode, String)> {
+    state
+        .tournaments
+        .read()
+        .expect("tournaments read lock was poisoned")
+        .get(&id)
+        .cloned()
+        .ok_or((StatusCode::NOT_FOUND, format!("Tournament {id} not found")))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewTournament {
+    name: String,
+    format: TournamentFormat,
+    /// best seed first
+    players: Vec<PlayerName>,
+    points_to_win: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Entrant {
+    player: PlayerName,
+    token: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedTournament {
+    id: i64,
+    /// tokens are handed out only here, each player's seats are claimed with theirs
+    players: Vec<Entrant>,
+}
+
+async fn create(
+    State(state): State<AppState>,
+    Json(new): Json<NewTournament>,
+) -> Result<(StatusCode, Json<CreatedTournament>), (StatusCode, String)> {
+    let name = new.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Tournament name must be 1 to {MAX_NAME_LENGTH} characters"),
+        ));
+    }
+    Tournament::validate_players(&new.players).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let points_to_win = new
+        .points_to_win
+        .or(state.config.match_defaults.points_to_win)
+        .unwrap_or(DEFAULT_POINTS_TO_WIN);
+    if points_to_win == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Points to win must be at least 1".to_string(),
+        ));
+    }
+    if points_to_win > MAX_POINTS_TO_WIN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Points to win must be at most {MAX_POINTS_TO_WIN}"),
+        ));
+    }
+
+    let mut tournament = Tournament {
+        id: 0,
+        name,
+        format: new.format,
+        matches: Tournament::generate_matches(new.format, &new.players),
+        players: new.players,
+        points_to_win,
+    };
+    let tokens: Vec<String> = tournament
+        .players
+        .iter()
+        .map(|_| Uuid::new_v4().simple().to_string())
+        .collect();
+    tournament.id = create_tournament(&state.db_pool, &tournament, &tokens)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Failed to create tournament");
+            (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+        })?;
+    info!(tournament_id = tournament.id, format = %tournament.format, "Tournament created");
+
+    let id = tournament.id;
+    let created = CreatedTournament {
+        id,
+        players: tournament
+            .players
+            .iter()
+            .cloned()
+            .zip(tokens.iter().cloned())
+            .map(|(player, token)| Entrant { player, token })
+            .collect(),
+    };
+    let entry = Arc::new(Mutex::new(RunningTournament::new(tournament, tokens)));
+    state
+        .tournaments
+        .write()
+        .expect("tournaments write lock was poisoned")
+        .insert(id, entry.clone());
+    advance(&state, &entry, None).await;
+
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TournamentSummary {
+    id: i64,
+    name: String,
+    format: TournamentFormat,
+    players: usize,
+    finished: bool,
+    champion: Option<PlayerName>,
+}
+
+#[derive(Serialize)]
+struct TournamentList {
+    tournaments: Vec<TournamentSummary>,
+}
+
+async fn list(State(state): State<AppState>) -> (StatusCode, Json<TournamentList>) {
+    let mut entries: Vec<_> = state
+        .tournaments
+        .read()
+        .expect("tournaments read lock was poisoned")
+        .iter()
+        .map(|(&id, entry)| (id, entry.clone()))
+        .collect();
+    entries.sort_by_key(|&(id, _)| id);
+
+    let mut tournaments = vec![];
+    for (_, entry) in entries {
+        let tournament = &entry.lock().await.tournament;
+        tournaments.push(TournamentSummary {
+            id: tournament.id,
+            name: tournament.name.clone(),
+            format: tournament.format,
+            players: tournament.players.len(),
+            finished: tournament.is_finished(),
+            champion: tournament.champion(),
+        });
+    }
+    (StatusCode::OK, Json(TournamentList { tournaments }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Standings {
+    finished: bool,
+    champion: Option<PlayerName>,
+    standings: Vec<Standing>,
+}
+
+async fn standings(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Standings>, (StatusCode, String)> {
+    let entry = find(&state, id)?;
+    let tournament = &entry.lock().await.tournament;
+    Ok(Json(Standings {
+        finished: tournament.is_finished(),
+        champion: tournament.champion(),
+        standings: tournament.standings(),
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BracketMatch {
+    number: usize,
+    ping: Slot,
+    pong: Slot,
+    /// once the slot is decided
+    ping_player: Option<PlayerName>,
+    pong_player: Option<PlayerName>,
+    table: Option<TableUid>,
+    winner: Option<PlayerName>,
+    score: Option<Score>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Round {
+    bracket: Bracket,
+    round: usize,
+    matches: Vec<BracketMatch>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BracketTree {
+    format: TournamentFormat,
+    rounds: Vec<Round>,
+}
+
+async fn bracket(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<BracketTree>, (StatusCode, String)> {
+    let entry = find(&state, id)?;
+    let tournament = &entry.lock().await.tournament;
+
+    let mut rounds: Vec<Round> = vec![];
+    for (number, game) in tournament.matches.iter().enumerate() {
+        let view = BracketMatch {
+            number,
+            ping: game.ping.clone(),
+            pong: game.pong.clone(),
+            ping_player: tournament.player_in(&game.ping),
+            pong_player: tournament.player_in(&game.pong),
+            table: game.table.clone(),
+            winner: game
+                .outcome
+                .as_ref()
+                .and_then(|outcome| outcome.winner.clone()),
+            score: game
+                .outcome
+                .as_ref()
+                .and_then(|outcome| outcome.score.clone()),
+        };
+        match rounds
+            .iter_mut()
+            .find(|round| round.bracket == game.bracket && round.round == game.round)
+        {
+            Some(round) => round.matches.push(view),
+            None => rounds.push(Round {
+                bracket: game.bracket,
+                round: game.round,
+                matches: vec![view],
+            }),
+        }
+    }
+
+    Ok(Json(BracketTree {
+        format: tournament.format,
+        rounds,
+    }))
+}
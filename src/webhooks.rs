@@ -0,0 +1,304 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use tokio::task::JoinSet;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    audit::Audit,
+    auth::{Actor, Scope},
+    config::WebhooksConfig,
+    database::{
+        Attempt, DbError, Delivery, DueDelivery, NewWebhook, TableUid, Webhook,
+        claim_due_deliveries, create_webhook, delete_webhook, enqueue_deliveries, get_deliveries,
+        get_webhooks, record_attempt,
+    },
+    models::{
+        application::AppState,
+        webhooks::{WebhookEvent, retry_delay, sign},
+    },
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// Events looked at in one transaction
+const ENQUEUE_BATCH: i64 = 500;
+/// Deliveries attempted at once
+const DELIVERY_BATCH: i64 = 50;
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Keeps delivering new match events in the background, `config.poll` apart once caught up.
+/// Gameplay only saves events, it never waits for a receiver.
+pub fn follow_webhooks(pool: PgPool, config: WebhooksConfig) {
+    let client = Client::builder()
+        .timeout(config.timeout)
+        .build()
+        .expect("Failed to create webhook HTTP client");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.poll);
+        loop {
+            interval.tick().await;
+            loop {
+                match enqueue_deliveries(&pool, ENQUEUE_BATCH).await {
+                    Ok(seen) if seen as i64 == ENQUEUE_BATCH => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        error!(error = %e, "Error while queueing webhook deliveries");
+                        break;
+                    }
+                }
+            }
+            loop {
+                match deliver_due(&pool, &client, &config).await {
+                    Ok(attempted) if attempted as i64 == DELIVERY_BATCH => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        error!(error = %e, "Error while delivering webhooks");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Attempts deliveries that are due, all at once. Returns how many there were.
+async fn deliver_due(
+    pool: &PgPool,
+    client: &Client,
+    config: &WebhooksConfig,
+) -> Result<usize, DbError> {
+    // long enough for the attempt to be over and recorded
+    let lease = config.timeout * 2;
+    let due = claim_due_deliveries(pool, DELIVERY_BATCH, lease).await?;
+    let attempted = due.len();
+
+    let mut attempts = JoinSet::new();
+    for delivery in due {
+        let (pool, client, config) = (pool.clone(), client.clone(), config.clone());
+        attempts.spawn(async move {
+            let attempt = attempt(&client, &config, &delivery).await;
+            if let Err(e) = record_attempt(&pool, delivery.id, &attempt).await {
+                error!(error = %e, delivery_id = delivery.id, "Error while saving webhook attempt");
+            }
+        });
+    }
+    attempts.join_all().await;
+    Ok(attempted)
+}
+
+async fn attempt(client: &Client, config: &WebhooksConfig, delivery: &DueDelivery) -> Attempt {
+    let body = serde_json::to_vec(&delivery.payload).expect("JSON values always serialize");
+    let result = client
+        .post(&delivery.url)
+        .header("Content-Type", "application/json")
+        .header(SIGNATURE_HEADER, sign(&delivery.secret, &body))
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .body(body)
+        .send()
+        .await;
+
+    let (status_code, error) = match result {
+        Ok(response) if response.status().is_success() => {
+            return Attempt {
+                delivered: true,
+                status_code: Some(response.status().as_u16()),
+                error: None,
+                retry_in: None,
+            };
+        }
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            format!("Receiver answered {}", response.status()),
+        ),
+        Err(e) => (None, e.to_string()),
+    };
+    let attempts = delivery.attempts as u32 + 1;
+    let retry_in = retry_delay(config.retry_base, attempts, config.max_attempts);
+    warn!(
+        delivery_id = delivery.id,
+        url = delivery.url,
+        attempts,
+        %error,
+        given_up = retry_in.is_none(),
+        "Webhook delivery failed"
+    );
+    Attempt {
+        delivered: false,
+        status_code,
+        error: Some(error),
+        retry_in,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRequest {
+    url: String,
+    /// all matches if missing
+    match_id: Option<String>,
+    /// all events if missing
+    events: Option<Vec<WebhookEvent>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    /// only shown once, payloads are signed with it
+    secret: String,
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Json(request): Json<WebhookRequest>,
+) -> Result<(StatusCode, Json<CreatedWebhook>), (StatusCode, String)> {
+    actor.require(Scope::Write)?;
+
+    let url = Url::parse(&request.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Webhook URL must be an absolute http or https URL".to_string(),
+        ))?;
+    let match_uid = request
+        .match_id
+        .map(TableUid::parse)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let events = request.events.unwrap_or_else(|| WebhookEvent::ALL.to_vec());
+    if events.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Webhook needs at least one event".to_string(),
+        ));
+    }
+
+    let webhook = NewWebhook {
+        url: url.to_string(),
+        secret: Uuid::new_v4().simple().to_string(),
+        match_uid,
+        events,
+        created_by: actor.name.clone(),
+    };
+    let created = create_webhook(&state.db_pool, &webhook)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Error while creating webhook");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create webhook".to_string(),
+            )
+        })?;
+    Audit::without_match(&state, &actor.name)
+        .record(
+            "create_webhook",
+            json!({
+                "id": created.id,
+                "url": created.url,
+                "matchId": created.match_id,
+                "events": created.events,
+            }),
+        )
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedWebhook {
+            webhook: created,
+            secret: webhook.secret,
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+pub struct WebhookList {
+    webhooks: Vec<Webhook>,
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+) -> Result<Json<WebhookList>, (StatusCode, String)> {
+    actor.require(Scope::Read)?;
+    let webhooks = get_webhooks(&state.db_pool).await.map_err(|e| {
+        error!(error = %e, "Error while listing webhooks");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to list webhooks".to_string(),
+        )
+    })?;
+    Ok(Json(WebhookList { webhooks }))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    actor.require(Scope::Write)?;
+    delete_webhook(&state.db_pool, id)
+        .await
+        .map_err(|e| match e {
+            DbError::RowNotFound => (StatusCode::NOT_FOUND, format!("Webhook {id} not found")),
+            e => {
+                error!(error = %e, "Error while deleting webhook");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to delete webhook".to_string(),
+                )
+            }
+        })?;
+    Audit::without_match(&state, &actor.name)
+        .record("delete_webhook", json!({ "id": id }))
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct DeliveriesQuery {
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryLog {
+    deliveries: Vec<Delivery>,
+}
+
+/// Newest first
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<i64>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<DeliveryLog>, (StatusCode, String)> {
+    actor.require(Scope::Read)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let deliveries = get_deliveries(&state.db_pool, id, limit)
+        .await
+        .map_err(|e| match e {
+            DbError::RowNotFound => (StatusCode::NOT_FOUND, format!("Webhook {id} not found")),
+            e => {
+                error!(error = %e, "Error while getting webhook deliveries");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to get deliveries".to_string(),
+                )
+            }
+        })?;
+    Ok(Json(DeliveryLog { deliveries }))
+}
@@ -30,7 +30,7 @@ pub fn get_random_port() -> u16 {
 }
 
 pub fn start_server_and_wait_until_ready(db_url: &str, api_port: u16) -> Child {
-    const SUCCESS_MESSAGE: &str = "Database initialized";
+    const SUCCESS_MESSAGE: &str = "Listening";
 
     start_server_and_wait_for_the_message(db_url, api_port, SUCCESS_MESSAGE)
         .expect("Failed to start server")
@@ -40,6 +40,15 @@ pub fn start_server_and_wait_for_the_message(
     db_url: &str,
     api_port: u16,
     message: &str,
+) -> Result<Child, io::Error> {
+    start_server_with_env_and_wait_for_the_message(db_url, api_port, &[], message)
+}
+
+pub fn start_server_with_env_and_wait_for_the_message(
+    db_url: &str,
+    api_port: u16,
+    extra_env: &[(&str, &str)],
+    message: &str,
 ) -> Result<Child, io::Error> {
     let app_binary_path = env!("CARGO_BIN_EXE_ping_pong_api");
 
@@ -54,11 +63,12 @@ pub fn start_server_and_wait_for_the_message(
         .env("RUST_LOG", "info")
         .env("SERVER_PORT", api_port.to_string())
         .env("LLVM_PROFILE_FILE", profile_file_path)
+        .envs(extra_env.iter().copied())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to run application");
 
-    // env_logger logs to stderr by default, so even when looking for 'info' it will be there
+    // logs go to stderr, so even when looking for 'info' it will be there
     let stderr = server_process
         .stderr
         .take()
@@ -1,4 +1,18 @@
 mod common;
+mod test_admin;
+mod test_audit;
+mod test_cluster;
 mod test_db_errors;
+mod test_doubles;
+mod test_health;
+mod test_history;
+mod test_lobby;
+mod test_logging;
 mod test_multi_match;
 mod test_persistence;
+mod test_players;
+mod test_queue;
+mod test_rate_limit;
+mod test_records;
+mod test_tournament;
+mod test_webhooks;
@@ -0,0 +1,181 @@
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+const ADMIN_KEY: &str = "s3cret";
+
+#[tokio::test]
+async fn test_archive_and_list_matches() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api_endpoint = format!("http://127.0.0.1:{api_port}");
+    let admin_keys = format!("root:{ADMIN_KEY}:read,write");
+    let start_server = || {
+        start_server_with_env_and_wait_for_the_message(
+            &connection_string,
+            api_port,
+            &[("ADMIN_API_KEYS", &admin_keys)],
+            "Listening",
+        )
+        .expect("Failed to start server")
+    };
+    // pooled connections would outlive the restarted server
+    let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+
+    let server_process = start_server();
+
+    for id in ["m1", "m2"] {
+        client
+            .get(format!("{api_endpoint}/matches/{id}"))
+            .send()
+            .await
+            .unwrap();
+    }
+    let claim = client
+        .post(format!("{api_endpoint}/matches/m1/claims/ping"))
+        .json(&json!({ "player": "alice" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(claim.status(), StatusCode::CREATED);
+
+    let archived = client
+        .delete(format!("{api_endpoint}/admin/matches/m2"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(archived.status(), StatusCode::NO_CONTENT);
+
+    // archived match can't be played or recreated
+    let recreated = client
+        .get(format!("{api_endpoint}/matches/m2"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(recreated.status(), StatusCode::CONFLICT);
+    let open_matches: Value = client
+        .get(format!("{api_endpoint}/matches"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(open_matches["openMatches"], json!(["m1"]));
+
+    let list_matches = || async {
+        let matches: Value = client
+            .get(format!("{api_endpoint}/admin/matches"))
+            .bearer_auth(ADMIN_KEY)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        matches["matches"].as_array().unwrap().clone()
+    };
+    let matches = list_matches().await;
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0]["id"], "m1");
+    assert_eq!(matches[0]["archivedAt"], Value::Null);
+    assert_eq!(matches[0]["players"]["ping"], "alice");
+    assert_eq!(matches[1]["id"], "m2");
+    assert!(matches[1]["archivedAt"].is_string());
+
+    // give the background claim write a moment before restarting
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+    let server_process = start_server();
+
+    // claims survive restarts, archived matches stay archived
+    let hit = client
+        .get(format!("{api_endpoint}/matches/m1/ping"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(hit.status(), StatusCode::FORBIDDEN);
+    assert_eq!(list_matches().await.len(), 2);
+    let recreated = client
+        .get(format!("{api_endpoint}/matches/m2"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(recreated.status(), StatusCode::CONFLICT);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_match_settings_survive_restart() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api_endpoint = format!("http://127.0.0.1:{api_port}");
+    let admin_keys = format!("root:{ADMIN_KEY}:read,write");
+    let start_server = |min_reaction_time_ms: &str| {
+        start_server_with_env_and_wait_for_the_message(
+            &connection_string,
+            api_port,
+            &[
+                ("ADMIN_API_KEYS", &admin_keys),
+                ("MIN_REACTION_TIME_MS", min_reaction_time_ms),
+            ],
+            "Listening",
+        )
+        .expect("Failed to start server")
+    };
+    let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+    let get_settings = |id: &'static str| {
+        let client = client.clone();
+        let api_endpoint = api_endpoint.clone();
+        async move {
+            let state: Value = client
+                .get(format!("{api_endpoint}/matches/{id}"))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            state["settings"].clone()
+        }
+    };
+
+    let server_process = start_server("1000");
+
+    // admin API doesn't create matches
+    get_settings("m1").await;
+    assert_eq!(
+        get_settings("m2").await,
+        json!({ "minReactionTimeMs": 1000, "tooEarly": "reject", "pointsToWin": null, "airTimeSeconds": null })
+    );
+    let updated = client
+        .put(format!("{api_endpoint}/admin/matches/m1/settings"))
+        .bearer_auth(ADMIN_KEY)
+        .json(&json!({ "minReactionTimeMs": 250, "tooEarly": "miss" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(updated.status(), StatusCode::OK);
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+    let server_process = start_server("500");
+
+    // own settings stay, the rest follow the new defaults
+    assert_eq!(
+        get_settings("m1").await,
+        json!({ "minReactionTimeMs": 250, "tooEarly": "miss", "pointsToWin": null, "airTimeSeconds": null })
+    );
+    assert_eq!(
+        get_settings("m2").await,
+        json!({ "minReactionTimeMs": 500, "tooEarly": "reject", "pointsToWin": null, "airTimeSeconds": null })
+    );
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
@@ -0,0 +1,147 @@
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_audit_log() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api_endpoint = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[(
+            "ADMIN_API_KEYS",
+            "root:rootkey:read,write;ops:opskey:read,write",
+        )],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    for id in ["m1", "m2"] {
+        client
+            .get(format!("{api_endpoint}/matches/{id}"))
+            .send()
+            .await
+            .unwrap();
+    }
+    let response = client
+        .put(format!("{api_endpoint}/admin/matches/m1/score"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "ping": 5, "pong": 3 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client
+        .put(format!("{api_endpoint}/admin/matches/m2/server"))
+        .bearer_auth("opskey")
+        .json(&json!({ "server": "pong" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let get_entries = |query: &'static str| {
+        let request = client
+            .get(format!("{api_endpoint}/admin/audit{query}"))
+            .bearer_auth("rootkey");
+        async move {
+            let log: Value = request.send().await.unwrap().json().await.unwrap();
+            log["entries"].as_array().unwrap().clone()
+        }
+    };
+
+    let entries = get_entries("").await;
+    assert_eq!(entries.len(), 2);
+    // newest first
+    assert_eq!(entries[0]["action"], "set_server");
+    assert_eq!(entries[0]["actor"], "ops");
+    assert_eq!(entries[0]["matchId"], "m2");
+    assert_eq!(entries[0]["details"], json!({ "server": "pong" }));
+
+    let entries = get_entries("?matchId=m1").await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["actor"], "root");
+    assert_eq!(entries[0]["action"], "set_score");
+    assert_eq!(
+        entries[0]["gameStateBefore"]["score"],
+        json!({ "ping": 0, "pong": 0 })
+    );
+    assert_eq!(
+        entries[0]["gameStateAfter"]["score"],
+        json!({ "ping": 5, "pong": 3 })
+    );
+    assert!(entries[0]["occurredAt"].is_string());
+
+    assert_eq!(get_entries("?actor=ops").await.len(), 1);
+    assert_eq!(get_entries("?actor=ops&matchId=m1").await.len(), 0);
+    assert_eq!(get_entries("?limit=1").await.len(), 1);
+    assert_eq!(get_entries("?from=2000-01-01T00:00:00Z").await.len(), 2);
+    assert_eq!(get_entries("?to=2000-01-01T00:00:00Z").await.len(), 0);
+
+    let response = client
+        .get(format!("{api_endpoint}/admin/audit?matchId=INVALID"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // append-only even for someone with direct database access
+    let pool = PgPool::connect(&connection_string).await.unwrap();
+    for statement in [
+        "UPDATE audit_log SET actor = 'someone else'",
+        "DELETE FROM audit_log",
+        "TRUNCATE audit_log",
+    ] {
+        let error = sqlx::query(statement).execute(&pool).await.unwrap_err();
+        assert!(error.to_string().contains("append-only"), "{statement}");
+    }
+    assert_eq!(get_entries("").await.len(), 2);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_change_fails_when_it_cant_be_audited() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api_endpoint = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("ADMIN_API_KEYS", "root:rootkey:read,write")],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+    client
+        .get(format!("{api_endpoint}/matches/m1"))
+        .send()
+        .await
+        .unwrap();
+
+    let pool = PgPool::connect(&connection_string).await.unwrap();
+    sqlx::query("ALTER TABLE audit_log RENAME TO audit_log_away")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let response = client
+        .put(format!("{api_endpoint}/admin/matches/m1/score"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "ping": 5, "pong": 3 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.text().await.unwrap(), "Failed to save audit entry");
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
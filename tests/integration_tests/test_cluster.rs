@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_instances_share_matches() {
+    let (connection_string, _db) = setup_db().await;
+    let start_instance = |port: u16| {
+        let instance_url = format!("http://127.0.0.1:{port}");
+        start_server_with_env_and_wait_for_the_message(
+            &connection_string,
+            port,
+            &[
+                ("INSTANCE_URL", instance_url.as_str()),
+                ("MATCH_LEASE_SECONDS", "2"),
+                ("ADMIN_API_KEYS", "root:rootkey:read,write"),
+            ],
+            "Listening",
+        )
+        .expect("Failed to start server")
+    };
+    let (port_a, port_b) = (get_random_port(), get_random_port());
+    let instance_a = start_instance(port_a);
+    let instance_b = start_instance(port_b);
+    let match_a = format!("http://127.0.0.1:{port_a}/matches/c1");
+    let match_b = format!("http://127.0.0.1:{port_b}/matches/c1");
+    let client = Client::new();
+    let get_state = |endpoint: String| {
+        let client = client.clone();
+        async move {
+            let response = client.get(endpoint).send().await.unwrap();
+            let status = response.status();
+            (status, response.json::<Value>().await.unwrap_or_default())
+        }
+    };
+
+    // created by A, played through B
+    let (status, _) = get_state(match_a.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    for (side, expected) in [("ping", "pong"), ("pong", "ping"), ("pong", "MISS")] {
+        let hit = client
+            .get(format!("{match_b}/{side}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(hit.text().await.unwrap(), expected);
+    }
+    let (_, state) = get_state(match_a.clone()).await;
+    assert_eq!(state["gameState"]["score"], json!({ "ping": 1, "pong": 0 }));
+
+    // admin requests go to A as well
+    let changed = client
+        .put(format!("http://127.0.0.1:{port_b}/admin/matches/c1/server"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "server": "pong" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(changed.status(), StatusCode::OK);
+    let (_, state) = get_state(match_a.clone()).await;
+    assert_eq!(state["gameState"]["server"], "pong");
+
+    let listed: Value = client
+        .get(format!("http://127.0.0.1:{port_b}/matches"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed["openMatches"], json!(["c1"]));
+
+    // B takes over once the lease of A runs out, nothing is lost
+    send_sigterm_and_wait_for_exit(instance_a).expect("Failed to stop server");
+    let mut taken_over = None;
+    for _ in 0..40 {
+        let (status, state) = get_state(match_b.clone()).await;
+        match status {
+            StatusCode::OK => {
+                taken_over = Some(state);
+                break;
+            }
+            status => assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE),
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    let state = taken_over.expect("Match was not taken over");
+    assert_eq!(state["gameState"]["score"], json!({ "ping": 1, "pong": 0 }));
+    assert_eq!(state["gameState"]["server"], "pong");
+    let hit = client.get(format!("{match_b}/pong")).send().await.unwrap();
+    assert_eq!(hit.text().await.unwrap(), "ping");
+
+    send_sigterm_and_wait_for_exit(instance_b).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_refused_writes_drop_the_match() {
+    let (connection_string, _db) = setup_db().await;
+    let port = get_random_port();
+    let instance_url = format!("http://127.0.0.1:{port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        port,
+        &[
+            ("INSTANCE_URL", instance_url.as_str()),
+            // renewals don't notice anything during the test
+            ("MATCH_LEASE_SECONDS", "60"),
+            ("ADMIN_API_KEYS", "root:rootkey:read,write"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+    let match_endpoint = format!("{instance_url}/matches/c2");
+    let created = client.get(&match_endpoint).send().await.unwrap();
+    assert_eq!(created.status(), StatusCode::OK);
+
+    // taken over without anybody announcing it
+    let pool = PgPool::connect(&connection_string).await.unwrap();
+    sqlx::query("UPDATE match SET instance_url = 'http://127.0.0.1:1' WHERE uid = 'c2'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let changed = client
+        .put(format!("{instance_url}/admin/matches/c2/server"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "server": "pong" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(changed.status(), StatusCode::OK);
+
+    let mut dropped = false;
+    for _ in 0..20 {
+        let status = client.get(&match_endpoint).send().await.unwrap().status();
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            dropped = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        dropped,
+        "Match is still served after its writes were refused"
+    );
+    let server: String =
+        sqlx::query_scalar("SELECT data_dump->>'server' FROM game_state JOIN match ON match.game_state_id = game_state.id WHERE uid = 'c2'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(server, "ping");
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
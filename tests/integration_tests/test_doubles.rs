@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db, start_server_and_wait_until_ready,
+};
+
+#[tokio::test]
+async fn test_doubles_teams_survive_restart() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let match_endpoint = format!("http://127.0.0.1:{api_port}/matches/d1");
+    // pooled connections would outlive the restarted server
+    let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+
+    let server_process = start_server_and_wait_until_ready(&connection_string, api_port);
+
+    let created = client
+        .get(format!("{match_endpoint}?mode=doubles"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), StatusCode::OK);
+
+    let mut tokens = vec![];
+    for (side, player) in [
+        ("ping", "alice"),
+        ("ping", "bob"),
+        ("pong", "carol"),
+        ("pong", "dave"),
+    ] {
+        let claim: Value = client
+            .post(format!("{match_endpoint}/claims/{side}"))
+            .json(&json!({ "player": player }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        tokens.push(claim["token"].as_str().unwrap().to_string());
+    }
+    let hit = |side: &'static str, token: String| {
+        let client = client.clone();
+        let match_endpoint = match_endpoint.clone();
+        async move {
+            client
+                .get(format!("{match_endpoint}/{side}"))
+                .header("X-Player-Token", token)
+                .send()
+                .await
+                .unwrap()
+                .status()
+        }
+    };
+
+    // alice serves, carol misses by hitting twice
+    assert_eq!(hit("ping", tokens[0].clone()).await, StatusCode::OK);
+    assert_eq!(hit("pong", tokens[2].clone()).await, StatusCode::OK);
+    assert_eq!(hit("pong", tokens[2].clone()).await, StatusCode::CONFLICT);
+
+    // give the background writes a moment before restarting
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+    let server_process = start_server_and_wait_until_ready(&connection_string, api_port);
+
+    let state: Value = client
+        .get(&match_endpoint)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(state["gameState"]["mode"], "doubles");
+    assert_eq!(
+        state["gameState"]["rotation"],
+        json!({ "ping": "second", "pong": "first" })
+    );
+    assert_eq!(
+        state["players"]["partners"],
+        json!({ "ping": "bob", "pong": "dave" })
+    );
+
+    // carol serves to bob
+    assert_eq!(hit("pong", tokens[2].clone()).await, StatusCode::OK);
+    assert_eq!(hit("ping", tokens[1].clone()).await, StatusCode::OK);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
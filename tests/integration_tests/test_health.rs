@@ -0,0 +1,32 @@
+use serde_json::Value;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db, start_server_and_wait_until_ready,
+};
+
+#[tokio::test]
+async fn test_ready_with_database() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api_endpoint = format!("http://127.0.0.1:{api_port}");
+
+    let server_process = start_server_and_wait_until_ready(&connection_string, api_port);
+
+    let liveness = reqwest::get(&format!("{api_endpoint}/healthz"))
+        .await
+        .unwrap();
+    assert_eq!(liveness.status(), reqwest::StatusCode::OK);
+
+    let readiness = reqwest::get(&format!("{api_endpoint}/readyz"))
+        .await
+        .unwrap();
+    assert_eq!(readiness.status(), reqwest::StatusCode::OK);
+    let report: Value = readiness.json().await.unwrap();
+    assert_eq!(report["ready"], true);
+    assert_eq!(report["shuttingDown"], false);
+    assert_eq!(report["database"]["ok"], true);
+    assert_eq!(report["migrations"]["ok"], true);
+    assert_eq!(report["gameTables"]["ok"], true);
+
+    send_sigterm_and_wait_for_exit(server_process).unwrap();
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_points_and_corrections_are_recorded() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let match_endpoint = format!("http://127.0.0.1:{api_port}/matches/h1");
+    let start_server = || {
+        start_server_with_env_and_wait_for_the_message(
+            &connection_string,
+            api_port,
+            &[("ADMIN_API_KEYS", "ref:refkey:referee")],
+            "Listening",
+        )
+        .expect("Failed to start server")
+    };
+    // pooled connections would outlive the restarted server
+    let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+
+    let server_process = start_server();
+
+    let claim: Value = client
+        .post(format!("{match_endpoint}/claims/ping"))
+        .json(&json!({ "player": "alice" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = claim["token"].as_str().unwrap();
+
+    // pong misses on ping's serve
+    client
+        .get(format!("{match_endpoint}/pong"))
+        .send()
+        .await
+        .unwrap();
+    let response = client
+        .post(format!("{match_endpoint}/undo"))
+        .bearer_auth("refkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // history is written in the background
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let pool = PgPool::connect(&connection_string).await.unwrap();
+    let events: Vec<(String, Value)> = sqlx::query_as(
+        "SELECT kind, data FROM match_event
+         JOIN match ON match.game_state_id = match_event.game_state_id
+         WHERE uid = 'h1' ORDER BY id",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].0, "point");
+    assert_eq!(events[0].1["loser"], "pong");
+    assert_eq!(events[0].1["reason"], "wrongSide");
+    assert_eq!(events[0].1["score"], json!({ "ping": 1, "pong": 0 }));
+    assert_eq!(events[1].0, "undo");
+    assert_eq!(events[1].1["by"], "ref");
+    assert_eq!(events[1].1["score"], json!({ "ping": 0, "pong": 0 }));
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+    let server_process = start_server();
+
+    // ownership survives restarts, so does the owner's token
+    let state: Value = client
+        .get(&match_endpoint)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(state["players"]["owner"], "alice");
+    assert_eq!(state["gameState"]["score"], json!({ "ping": 0, "pong": 0 }));
+    let response = client
+        .get(format!("{match_endpoint}/ping"))
+        .header("x-player-token", token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
@@ -0,0 +1,280 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_rated_lobby_match_changes_ratings() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let preferences = |player: &str| json!({ "player": player, "pointsToWin": 1, "rated": true });
+    let alice = client
+        .post(format!("{base_url}/lobby/join"))
+        .json(&preferences("alice"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(alice.status(), StatusCode::ACCEPTED);
+    let alice: Value = alice.json().await.unwrap();
+
+    let bob = client
+        .post(format!("{base_url}/lobby/join"))
+        .json(&preferences("bob"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(bob.status(), StatusCode::CREATED);
+    let bob: Value = bob.json().await.unwrap();
+    assert_eq!(bob["status"], "matched");
+    assert_eq!(bob["side"], "pong");
+    assert_eq!(bob["opponent"], "alice");
+    let match_id = bob["matchId"].as_str().unwrap();
+
+    let ticket: Value = client
+        .get(format!(
+            "{base_url}/lobby/tickets/{}?wait=5",
+            alice["ticket"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        ticket,
+        json!({ "status": "matched", "matchId": match_id, "side": "ping", "opponent": "bob" })
+    );
+
+    let match_endpoint = format!("{base_url}/matches/{match_id}");
+    let state: Value = client
+        .get(&match_endpoint)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(state["players"]["ping"], "alice");
+    assert_eq!(state["players"]["pong"], "bob");
+    assert_eq!(state["settings"]["pointsToWin"], 1);
+
+    let queued = client
+        .post(format!("{match_endpoint}/queue"))
+        .json(&json!({ "player": "carol" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(queued.status(), StatusCode::CONFLICT);
+    assert_eq!(queued.text().await.unwrap(), "Rated matches have no queue");
+
+    // alice misses twice - a win still needs a two point lead
+    let alice_token = alice["token"].as_str().unwrap();
+    for _ in 0..2 {
+        let state: Value = client
+            .get(&match_endpoint)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if state["gameState"]["server"] == "ping" {
+            client
+                .get(format!("{match_endpoint}/ping"))
+                .header("X-Player-Token", alice_token)
+                .send()
+                .await
+                .unwrap();
+        }
+        let missed = client
+            .get(format!("{match_endpoint}/ping"))
+            .header("X-Player-Token", alice_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missed.text().await.unwrap(), "MISS");
+    }
+
+    // give the rating a moment to be saved
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for (player, rating) in [("bob", 1516), ("alice", 1484)] {
+        let response: Value = client
+            .get(format!("{base_url}/lobby/ratings/{player}"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            json!({ "player": player, "rating": rating, "ratedMatches": 1 })
+        );
+    }
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_rated_name_needs_its_token() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let join = |token: Option<&str>| {
+        let request = client
+            .post(format!("{base_url}/lobby/join"))
+            .json(&json!({ "player": "alice", "pointsToWin": 1, "rated": true }));
+        match token {
+            Some(token) => request.header("X-Player-Token", token),
+            None => request,
+        }
+    };
+    let first = join(None).send().await.unwrap();
+    assert_eq!(first.status(), StatusCode::ACCEPTED);
+    let first: Value = first.json().await.unwrap();
+    let token = first["token"].as_str().unwrap();
+    let left = client
+        .delete(format!(
+            "{base_url}/lobby/tickets/{}",
+            first["ticket"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(left.status(), StatusCode::NO_CONTENT);
+
+    for token in [None, Some("not-alice")] {
+        let impostor = join(token).send().await.unwrap();
+        assert_eq!(impostor.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            impostor.text().await.unwrap(),
+            "alice plays rated matches with another token"
+        );
+    }
+
+    let again = join(Some(token)).send().await.unwrap();
+    assert_eq!(again.status(), StatusCode::ACCEPTED);
+    let again: Value = again.json().await.unwrap();
+    assert_eq!(again["token"], token);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_undone_win_takes_rating_back() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("ADMIN_API_KEYS", "ref:refkey:referee")],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let mut joined = vec![];
+    for player in ["alice", "bob"] {
+        let response: Value = client
+            .post(format!("{base_url}/lobby/join"))
+            .json(&json!({ "player": player, "pointsToWin": 1, "rated": true }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        joined.push(response);
+    }
+    let match_endpoint = format!(
+        "{base_url}/matches/{}",
+        joined[1]["matchId"].as_str().unwrap()
+    );
+    let alice_token = joined[0]["token"].as_str().unwrap();
+    let alice_misses = || async {
+        let state: Value = client
+            .get(&match_endpoint)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if state["gameState"]["server"] == "ping" {
+            client
+                .get(format!("{match_endpoint}/ping"))
+                .header("X-Player-Token", alice_token)
+                .send()
+                .await
+                .unwrap();
+        }
+        let missed = client
+            .get(format!("{match_endpoint}/ping"))
+            .header("X-Player-Token", alice_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missed.text().await.unwrap(), "MISS");
+    };
+    let rating = |player: &'static str| {
+        let url = format!("{base_url}/lobby/ratings/{player}");
+        let client = client.clone();
+        async move {
+            let response: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            (response["rating"].clone(), response["ratedMatches"].clone())
+        }
+    };
+
+    for _ in 0..2 {
+        alice_misses().await;
+    }
+    // give the rating a moment to be saved
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(rating("bob").await, (json!(1516), json!(1)));
+
+    let undone = client
+        .post(format!("{match_endpoint}/undo"))
+        .bearer_auth("refkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(undone.status(), StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for player in ["alice", "bob"] {
+        assert_eq!(rating(player).await, (json!(1500), json!(0)), "{player}");
+    }
+
+    // won again, rated once
+    alice_misses().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(rating("bob").await, (json!(1516), json!(1)));
+    assert_eq!(rating("alice").await, (json!(1484), json!(1)));
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
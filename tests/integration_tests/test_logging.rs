@@ -0,0 +1,20 @@
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_json_log_format() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("LOG_FORMAT", "json")],
+        r#""message":"Database initialized""#,
+    )
+    .expect("Server did not log in JSON format");
+
+    send_sigterm_and_wait_for_exit(server_process).unwrap();
+}
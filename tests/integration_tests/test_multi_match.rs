@@ -62,3 +62,21 @@ async fn test_multi_match() {
 
     send_sigterm_and_wait_for_exit(server_process).unwrap();
 }
+
+#[tokio::test]
+async fn test_concurrent_first_requests_share_the_match() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let match_endpoint = format!("http://127.0.0.1:{api_port}/matches/race");
+    let server_process = start_server_and_wait_until_ready(&connection_string, api_port);
+
+    let requests: Vec<_> = (0..20)
+        .map(|_| tokio::spawn(reqwest::get(match_endpoint.clone())))
+        .collect();
+    for request in requests {
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+
+    send_sigterm_and_wait_for_exit(server_process).unwrap();
+}
@@ -0,0 +1,458 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+/// Claims both sides, then `loser` misses `points` times. Returns the token of the loser.
+async fn play_match(
+    client: &Client,
+    match_endpoint: &str,
+    [ping, pong]: [&str; 2],
+    loser: &str,
+    points: usize,
+) -> String {
+    let mut token = String::new();
+    for (side, player) in [("ping", ping), ("pong", pong)] {
+        let claim: Value = client
+            .post(format!("{match_endpoint}/claims/{side}"))
+            .json(&json!({ "player": player }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if side == loser {
+            token = claim["token"].as_str().unwrap().to_string();
+        }
+    }
+
+    let endpoint = format!("{match_endpoint}/{loser}");
+    for _ in 0..points {
+        let state: Value = client
+            .get(match_endpoint)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if state["gameState"]["server"] == loser {
+            client
+                .get(&endpoint)
+                .header("X-Player-Token", &token)
+                .send()
+                .await
+                .unwrap();
+        }
+        let missed = client
+            .get(&endpoint)
+            .header("X-Player-Token", &token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missed.text().await.unwrap(), "MISS");
+    }
+    token
+}
+
+/// Claims a doubles match for alice and bob against carol and dave, then carol
+/// serves when it's her turn and hits out of turn otherwise, until the match is won
+async fn lose_doubles_match(client: &Client, match_endpoint: &str) {
+    let mut carol = String::new();
+    for (side, player) in [
+        ("ping", "alice"),
+        ("ping", "bob"),
+        ("pong", "carol"),
+        ("pong", "dave"),
+    ] {
+        let claim = client
+            .post(format!("{match_endpoint}/claims/{side}?mode=doubles"))
+            .json(&json!({ "player": player }))
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap();
+        if player == "carol" {
+            carol = claim["token"].as_str().unwrap().to_string();
+        }
+    }
+    while get_json(client, match_endpoint.to_string()).await["gameState"]["winner"].is_null() {
+        client
+            .get(format!("{match_endpoint}/pong"))
+            .header("X-Player-Token", &carol)
+            .send()
+            .await
+            .unwrap();
+    }
+}
+
+async fn get_json(client: &Client, url: String) -> Value {
+    client.get(url).send().await.unwrap().json().await.unwrap()
+}
+
+fn match_ids(matches: &Value) -> Vec<&str> {
+    matches["matches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|played| played["matchId"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_player_careers_come_from_won_matches() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("POINTS_TO_WIN", "1")],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    // a win still needs a two point lead
+    let p1 = format!("{base_url}/matches/p1");
+    play_match(&client, &p1, ["alice", "bob"], "pong", 2).await;
+    // tables save their events on their own, p1 has to be in before p2 finishes
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let p2 = format!("{base_url}/matches/p2");
+    play_match(&client, &p2, ["carol", "alice"], "pong", 2).await;
+    // give the events a moment to be saved
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats = get_json(&client, format!("{base_url}/players/alice/stats")).await;
+    assert_eq!(
+        stats,
+        json!({
+            "matches": 2,
+            "wins": 1,
+            "losses": 1,
+            "headToHead": [
+                { "opponent": "bob", "wins": 1, "losses": 0 },
+                { "opponent": "carol", "wins": 0, "losses": 1 },
+            ],
+            // misses and serves don't react to anything
+            "averageReactionTimeMs": null,
+            "longestRally": 1,
+            "currentStreak": { "outcome": "lost", "matches": 1 },
+        })
+    );
+
+    let matches = get_json(&client, format!("{base_url}/players/alice/matches")).await;
+    assert_eq!(match_ids(&matches), vec!["p2", "p1"]);
+    let latest = &matches["matches"][0];
+    assert_eq!(latest["side"], "pong");
+    assert_eq!(latest["opponent"], "carol");
+    assert_eq!(latest["outcome"], "lost");
+    assert_eq!(latest["score"], json!({ "ping": 2, "pong": 0 }));
+
+    let against_bob = get_json(
+        &client,
+        format!("{base_url}/players/alice/matches?opponent=bob"),
+    )
+    .await;
+    assert_eq!(match_ids(&against_bob), vec!["p1"]);
+    let second_page = get_json(
+        &client,
+        format!("{base_url}/players/alice/matches?limit=1&offset=1"),
+    )
+    .await;
+    assert_eq!(match_ids(&second_page), vec!["p1"]);
+    let in_the_future = get_json(
+        &client,
+        format!("{base_url}/players/alice/matches?from=2999-01-01T00:00:00Z"),
+    )
+    .await;
+    assert_eq!(in_the_future, json!({ "matches": [] }));
+
+    let stranger = get_json(&client, format!("{base_url}/players/dave/stats")).await;
+    assert_eq!(stranger["matches"], 0);
+    assert_eq!(stranger["currentStreak"], Value::Null);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_doubles_partners_share_the_result() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("POINTS_TO_WIN", "1")],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let d1 = format!("{base_url}/matches/d1");
+    lose_doubles_match(&client, &d1).await;
+    // give the events a moment to be saved
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats = get_json(&client, format!("{base_url}/players/dave/stats")).await;
+    assert_eq!(stats["matches"], 1);
+    assert_eq!(stats["losses"], 1);
+    assert_eq!(
+        stats["headToHead"],
+        json!([
+            { "opponent": "alice", "wins": 0, "losses": 1 },
+            { "opponent": "bob", "wins": 0, "losses": 1 },
+        ])
+    );
+    let matches = get_json(
+        &client,
+        format!("{base_url}/players/bob/matches?opponent=dave"),
+    )
+    .await;
+    assert_eq!(match_ids(&matches), vec!["d1"]);
+    assert_eq!(matches["matches"][0]["outcome"], "won");
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_doubles_partners_unlock_achievements_together() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("POINTS_TO_WIN", "11")],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    lose_doubles_match(&client, &format!("{base_url}/matches/d1")).await;
+    // give the events a moment to be saved
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    for player in ["alice", "bob"] {
+        let achievements =
+            get_json(&client, format!("{base_url}/players/{player}/achievements")).await;
+        assert_eq!(
+            achievements["achievements"][0]["id"], "flawless",
+            "{player}"
+        );
+    }
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_undone_win_does_not_count() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[
+            ("POINTS_TO_WIN", "1"),
+            ("ADMIN_API_KEYS", "ref:refkey:referee"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let p1 = format!("{base_url}/matches/p1");
+    let bob = play_match(&client, &p1, ["alice", "bob"], "pong", 2).await;
+    let undone: Value = client
+        .post(format!("{p1}/undo"))
+        .bearer_auth("refkey")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(undone["gameState"]["winner"], Value::Null);
+    assert_eq!(
+        undone["gameState"]["score"],
+        json!({ "ping": 1, "pong": 0 })
+    );
+    // give the events a moment to be saved
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats = get_json(&client, format!("{base_url}/players/alice/stats")).await;
+    assert_eq!(stats["matches"], 0);
+
+    // won again, counted once
+    let state = get_json(&client, p1.clone()).await;
+    if state["gameState"]["server"] == "pong" {
+        client
+            .get(format!("{p1}/pong"))
+            .header("X-Player-Token", &bob)
+            .send()
+            .await
+            .unwrap();
+    }
+    let missed = client
+        .get(format!("{p1}/pong"))
+        .header("X-Player-Token", &bob)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missed.text().await.unwrap(), "MISS");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats = get_json(&client, format!("{base_url}/players/alice/stats")).await;
+    assert_eq!(stats["matches"], 1);
+    assert_eq!(stats["wins"], 1);
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_undone_win_takes_back_its_achievements() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[
+            ("POINTS_TO_WIN", "11"),
+            ("ADMIN_API_KEYS", "ref:refkey:referee"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+    let undo = |match_endpoint: &str| {
+        client
+            .post(format!("{match_endpoint}/undo"))
+            .bearer_auth("refkey")
+            .send()
+    };
+    let achievement_matches = |player: &str| {
+        let url = format!("{base_url}/players/{player}/achievements");
+        let client = client.clone();
+        async move {
+            get_json(&client, url).await["achievements"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|achievement| achievement["matchId"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    play_match(
+        &client,
+        &format!("{base_url}/matches/a1"),
+        ["alice", "bob"],
+        "pong",
+        11,
+    )
+    .await;
+    // unlocked before, the undone win doesn't take it back
+    let a2 = format!("{base_url}/matches/a2");
+    play_match(&client, &a2, ["alice", "bob"], "pong", 11).await;
+    undo(&a2).await.unwrap();
+    // unlocked by the undone win
+    let a3 = format!("{base_url}/matches/a3");
+    let dave = play_match(&client, &a3, ["carol", "dave"], "pong", 11).await;
+    undo(&a3).await.unwrap();
+    // give the events a moment to be saved
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(achievement_matches("alice").await, vec!["a1"]);
+    assert!(achievement_matches("carol").await.is_empty());
+
+    // won again, unlocked again
+    let state = get_json(&client, a3.clone()).await;
+    if state["gameState"]["server"] == "pong" {
+        client
+            .get(format!("{a3}/pong"))
+            .header("X-Player-Token", &dave)
+            .send()
+            .await
+            .unwrap();
+    }
+    let missed = client
+        .get(format!("{a3}/pong"))
+        .header("X-Player-Token", &dave)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missed.text().await.unwrap(), "MISS");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(achievement_matches("carol").await, vec!["a3"]);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_achievements_are_unlocked_once() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("POINTS_TO_WIN", "11")],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    for id in ["a1", "a2"] {
+        let match_endpoint = format!("{base_url}/matches/{id}");
+        play_match(&client, &match_endpoint, ["alice", "bob"], "pong", 11).await;
+    }
+    // give the events a moment to be saved
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let achievements = get_json(&client, format!("{base_url}/players/alice/achievements")).await;
+    let achievements = achievements["achievements"].as_array().unwrap();
+    assert_eq!(achievements.len(), 1);
+    assert_eq!(achievements[0]["id"], "flawless");
+    assert_eq!(achievements[0]["title"], "Flawless");
+    assert_eq!(achievements[0]["matchId"], "a1");
+    assert_eq!(
+        get_json(&client, format!("{base_url}/players/bob/achievements")).await,
+        json!({ "achievements": [] })
+    );
+
+    // announced right after the win, only the first time
+    let pool = PgPool::connect(&connection_string).await.unwrap();
+    let announced: Vec<(String, String, Value)> = sqlx::query_as(
+        "SELECT match.uid, kind, data FROM match_event
+         JOIN match ON match.game_state_id = match_event.game_state_id
+         WHERE kind IN ('won', 'achievement')
+         ORDER BY match_event.id",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let kinds: Vec<_> = announced
+        .iter()
+        .map(|(uid, kind, _)| (uid.as_str(), kind.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![("a1", "won"), ("a1", "achievement"), ("a2", "won")]
+    );
+    assert_eq!(
+        announced[1].2,
+        json!({ "kind": "achievement", "player": "alice", "achievement": "flawless" })
+    );
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
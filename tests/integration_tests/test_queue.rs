@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_queue_survives_restart() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let match_endpoint = format!("http://127.0.0.1:{api_port}/matches/q1");
+    let start_server = || {
+        start_server_with_env_and_wait_for_the_message(
+            &connection_string,
+            api_port,
+            &[("POINTS_TO_WIN", "1")],
+            "Listening",
+        )
+        .expect("Failed to start server")
+    };
+    // pooled connections would outlive the restarted server
+    let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+
+    let server_process = start_server();
+
+    let mut tokens = vec![];
+    for player in ["alice", "bob"] {
+        let joined = client
+            .post(format!("{match_endpoint}/queue"))
+            .json(&json!({ "player": player }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(joined.status(), StatusCode::CREATED);
+        let joined: Value = joined.json().await.unwrap();
+        tokens.push(joined["token"].as_str().unwrap().to_string());
+    }
+    let left = client
+        .delete(format!("{match_endpoint}/queue"))
+        .header("X-Player-Token", &tokens[0])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(left.status(), StatusCode::NO_CONTENT);
+
+    // give the background writes a moment before restarting
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+    let server_process = start_server();
+
+    let queue: Value = client
+        .get(format!("{match_endpoint}/queue"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(queue, json!({ "queue": ["bob"] }));
+
+    // ping misses twice - a win still needs a two point lead
+    for _ in 0..2 {
+        let state: Value = client
+            .get(&match_endpoint)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if state["gameState"]["server"] == "ping" {
+            client
+                .get(format!("{match_endpoint}/ping"))
+                .send()
+                .await
+                .unwrap();
+        }
+        client
+            .get(format!("{match_endpoint}/ping"))
+            .send()
+            .await
+            .unwrap();
+    }
+    let state: Value = client
+        .get(&match_endpoint)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(state["players"]["ping"], "bob");
+    assert_eq!(state["players"]["queue"], json!([]));
+    // bob's queue token is his claim token now
+    let hit = client
+        .get(format!("{match_endpoint}/ping"))
+        .header("X-Player-Token", &tokens[1])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(hit.status(), StatusCode::OK);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
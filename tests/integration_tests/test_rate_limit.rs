@@ -0,0 +1,47 @@
+use reqwest::StatusCode;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[tokio::test]
+async fn test_match_creation_limit() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api_endpoint = format!("http://127.0.0.1:{api_port}");
+
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[("RATE_LIMIT_MATCH_CREATIONS_PER_MINUTE_PER_IP", "2")],
+        "Listening",
+    )
+    .unwrap();
+
+    for uid in ["m1", "m2"] {
+        let response = reqwest::get(&format!("{api_endpoint}/matches/{uid}"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = reqwest::get(&format!("{api_endpoint}/matches/m3"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+
+    // existing matches are still there to play
+    let response = reqwest::get(&format!("{api_endpoint}/matches/m1/ping"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    send_sigterm_and_wait_for_exit(server_process).unwrap();
+}
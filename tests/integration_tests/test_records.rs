@@ -0,0 +1,262 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+/// `side` serves if it has to, then hits out of turn
+async fn lose_point(client: &Client, match_endpoint: &str, side: &str, token: &str) {
+    let state: Value = client
+        .get(match_endpoint)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let endpoint = format!("{match_endpoint}/{side}");
+    if state["gameState"]["server"] == side {
+        client
+            .get(&endpoint)
+            .header("X-Player-Token", token)
+            .send()
+            .await
+            .unwrap();
+    }
+    let missed = client
+        .get(&endpoint)
+        .header("X-Player-Token", token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missed.text().await.unwrap(), "MISS");
+}
+
+async fn get_records(client: &Client, base_url: &str) -> Value {
+    client
+        .get(format!("{base_url}/records"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_records_follow_matches_and_can_be_rebuilt() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let match_endpoint = format!("{base_url}/matches/r1");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[
+            ("POINTS_TO_WIN", "2"),
+            ("RECORDS_REFRESH_SECONDS", "1"),
+            ("ADMIN_API_KEYS", "root:rootkey:read,write"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let mut tokens = vec![];
+    for (side, player) in [("ping", "alice"), ("pong", "bob")] {
+        let claim: Value = client
+            .post(format!("{match_endpoint}/claims/{side}"))
+            .json(&json!({ "player": player }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        tokens.push(claim["token"].as_str().unwrap().to_string());
+    }
+
+    // alice comes back from 0:1 to win 3:1
+    lose_point(&client, &match_endpoint, "ping", &tokens[0]).await;
+    for _ in 0..3 {
+        lose_point(&client, &match_endpoint, "pong", &tokens[1]).await;
+    }
+    // records go to who played, not to who sits at the table once they are noticed
+    let kicked = client
+        .delete(format!("{base_url}/admin/matches/r1/claims/pong"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(kicked.status(), StatusCode::OK);
+    client
+        .post(format!("{match_endpoint}/claims/pong"))
+        .json(&json!({ "player": "carol" }))
+        .send()
+        .await
+        .unwrap();
+
+    // events are taken once they are a second old
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let records = get_records(&client, &base_url).await;
+    let comeback = &records["biggestComeback"];
+    assert_eq!(comeback["value"], 1);
+    assert_eq!(comeback["matchId"], "r1");
+    assert_eq!(
+        comeback["players"],
+        json!({ "ping": "alice", "pong": "bob", "partners": { "ping": null, "pong": null } })
+    );
+    assert_eq!(records["mostPoints"]["value"], 4);
+    assert_eq!(
+        records["longestRallyByHits"]["players"],
+        json!({ "ping": "alice", "pong": "bob", "partners": { "ping": null, "pong": null } })
+    );
+    // serves are the only hits that aren't misses
+    assert_eq!(records["longestRallyByHits"]["value"], 1);
+    assert!(records["fastestGame"]["value"].is_i64());
+
+    let rebuilt = client
+        .post(format!("{base_url}/admin/records/rebuild"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rebuilt.status(), StatusCode::ACCEPTED);
+    let log: Value = client
+        .get(format!("{base_url}/admin/audit?actor=root"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(log["entries"][0]["action"], "rebuild_records");
+    assert_eq!(log["entries"][0]["matchId"], Value::Null);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(get_records(&client, &base_url).await, records);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_set_scores_are_not_played_points() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let match_endpoint = format!("{base_url}/matches/r2");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[
+            ("POINTS_TO_WIN", "2"),
+            ("RECORDS_REFRESH_SECONDS", "1"),
+            ("ADMIN_API_KEYS", "root:rootkey:read,write"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+    let claim: Value = client
+        .post(format!("{match_endpoint}/claims/pong"))
+        .json(&json!({ "player": "bob" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = claim["token"].as_str().unwrap().to_string();
+
+    let set = client
+        .put(format!("{base_url}/admin/matches/r2/score"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "ping": 1, "pong": 0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(set.status(), StatusCode::OK);
+    lose_point(&client, &match_endpoint, "pong", &token).await;
+
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let records = get_records(&client, &base_url).await;
+    // the rally still counts, the game and its points don't
+    assert_eq!(records["longestRallyByHits"]["matchId"], "r2");
+    assert_eq!(records["mostPoints"], Value::Null);
+    assert_eq!(records["fastestGame"], Value::Null);
+    assert_eq!(records["biggestComeback"], Value::Null);
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
+
+#[tokio::test]
+async fn test_undone_wins_set_no_records() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let match_endpoint = format!("{base_url}/matches/r3");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[
+            ("POINTS_TO_WIN", "2"),
+            ("RECORDS_REFRESH_SECONDS", "1"),
+            ("ADMIN_API_KEYS", "root:rootkey:read,write"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+
+    let mut tokens = vec![];
+    for (side, player) in [("ping", "alice"), ("pong", "bob")] {
+        let claim: Value = client
+            .post(format!("{match_endpoint}/claims/{side}"))
+            .json(&json!({ "player": player }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        tokens.push(claim["token"].as_str().unwrap().to_string());
+    }
+
+    // alice comes back from 0:1 to win 3:1, then the win is undone
+    lose_point(&client, &match_endpoint, "ping", &tokens[0]).await;
+    for _ in 0..3 {
+        lose_point(&client, &match_endpoint, "pong", &tokens[1]).await;
+    }
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(
+        get_records(&client, &base_url).await["biggestComeback"]["value"],
+        1
+    );
+    let undone = client
+        .post(format!("{match_endpoint}/undo"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(undone.status(), StatusCode::OK);
+
+    // records of the undone win are taken back
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+    let records = get_records(&client, &base_url).await;
+    assert_eq!(records["biggestComeback"], Value::Null);
+    assert_eq!(records["fastestGame"], Value::Null);
+
+    // won again, the game still started at 0:0
+    lose_point(&client, &match_endpoint, "pong", &tokens[1]).await;
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let records = get_records(&client, &base_url).await;
+    assert_eq!(records["biggestComeback"]["value"], 1);
+    assert_eq!(records["biggestComeback"]["matchId"], "r3");
+    assert!(records["fastestGame"]["value"].is_i64());
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
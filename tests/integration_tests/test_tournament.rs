@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db, start_server_and_wait_until_ready,
+};
+
+struct Api {
+    client: Client,
+    base: String,
+}
+
+impl Api {
+    async fn get(&self, path: &str) -> Value {
+        self.client
+            .get(format!("{}{path}", self.base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    async fn hit(&self, table: &str, side: &str, token: &str) -> StatusCode {
+        self.client
+            .get(format!("{}/matches/{table}/{side}", self.base))
+            .header("X-Player-Token", token)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    }
+
+    /// `side` serves if it has to, then hits out of turn until the other side wins
+    async fn lose_match(&self, table: &str, side: &str, token: &str) {
+        loop {
+            let response = self
+                .client
+                .get(format!("{}/matches/{table}", self.base))
+                .send()
+                .await
+                .unwrap();
+            // archived as soon as the result is in
+            if response.status() == StatusCode::CONFLICT {
+                return;
+            }
+            let state: Value = response.json().await.unwrap();
+            if !state["gameState"]["winner"].is_null() {
+                return;
+            }
+            if state["gameState"]["server"] == side {
+                assert_eq!(self.hit(table, side, token).await, StatusCode::OK);
+            }
+            assert_eq!(self.hit(table, side, token).await, StatusCode::CONFLICT);
+        }
+    }
+
+    /// Tables are opened in the background once a match is decided
+    async fn wait_for_table(&self, tournament: i64, number: usize) -> String {
+        for _ in 0..50 {
+            let bracket = self
+                .get(&format!("/tournaments/{tournament}/bracket"))
+                .await;
+            let table = bracket["rounds"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .flat_map(|round| round["matches"].as_array().unwrap())
+                .find(|game| game["number"] == number)
+                .and_then(|game| game["table"].as_str());
+            if let Some(table) = table {
+                return table.to_string();
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("match {number} of tournament {tournament} never got a table");
+    }
+}
+
+#[tokio::test]
+async fn test_tournament_advances_on_its_own() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let api = Api {
+        // pooled connections would outlive the restarted server
+        client: Client::builder().pool_max_idle_per_host(0).build().unwrap(),
+        base: format!("http://127.0.0.1:{api_port}"),
+    };
+
+    let server_process = start_server_and_wait_until_ready(&connection_string, api_port);
+
+    let response = api
+        .client
+        .post(format!("{}/tournaments", api.base))
+        .json(&json!({
+            "name": "Friday cup",
+            "format": "single_elimination",
+            "players": ["alice", "bob", "carol"],
+            "pointsToWin": 2
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    let id = created["id"].as_i64().unwrap();
+    let token = |player: &str| {
+        created["players"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entrant| entrant["player"] == player)
+            .unwrap()["token"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    // alice has a bye, bob and carol play right away
+    let semifinal = api.wait_for_table(id, 1).await;
+    let state = api.get(&format!("/matches/{semifinal}")).await;
+    assert_eq!(state["players"]["ping"], "bob");
+    assert_eq!(state["players"]["pong"], "carol");
+    assert_eq!(state["settings"]["pointsToWin"], 2);
+    // a stranger can't play for them
+    assert_eq!(
+        api.hit(&semifinal, "ping", "guess").await,
+        StatusCode::FORBIDDEN
+    );
+
+    api.lose_match(&semifinal, "ping", &token("bob")).await;
+    let final_table = api.wait_for_table(id, 2).await;
+    // decided matches are archived before the next ones open
+    let archived = api
+        .client
+        .get(format!("{}/matches/{semifinal}", api.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(archived.status(), StatusCode::CONFLICT);
+
+    // give the background writes a moment before restarting
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+    let server_process = start_server_and_wait_until_ready(&connection_string, api_port);
+
+    let state = api.get(&format!("/matches/{final_table}")).await;
+    assert_eq!(state["players"]["ping"], "alice");
+    assert_eq!(state["players"]["pong"], "carol");
+
+    // results of matches finished after the restart still count
+    api.lose_match(&final_table, "pong", &token("carol")).await;
+    let mut standings = Value::Null;
+    for _ in 0..50 {
+        standings = api.get(&format!("/tournaments/{id}/standings")).await;
+        if standings["finished"] == true {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(standings["champion"], "alice");
+    assert_eq!(standings["standings"][0]["wins"], 1);
+    assert_eq!(standings["standings"][1]["player"], "carol");
+    assert_eq!(
+        api.get("/tournaments").await["tournaments"][0]["champion"],
+        "alice"
+    );
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}
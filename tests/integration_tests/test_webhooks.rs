@@ -0,0 +1,252 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tokio::net::TcpListener;
+
+use crate::common::{
+    get_random_port, send_sigterm_and_wait_for_exit, setup_db,
+    start_server_with_env_and_wait_for_the_message,
+};
+
+#[derive(Clone, Default)]
+struct Receiver {
+    /// headers and body of every request, failed ones included
+    received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+}
+
+/// Fails the first request to check retries, takes all later ones
+async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let mut received = receiver.received.lock().unwrap();
+    received.push((headers, body));
+    if received.len() == 1 {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::OK
+    }
+}
+
+async fn start_receiver() -> (String, Receiver) {
+    let receiver = Receiver::default();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(receiver.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, receiver)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers.get(name).unwrap().to_str().unwrap()
+}
+
+#[tokio::test]
+async fn test_webhooks_deliver_signed_events_with_retries() {
+    let (connection_string, _db) = setup_db().await;
+    let api_port = get_random_port();
+    let base_url = format!("http://127.0.0.1:{api_port}");
+    let server_process = start_server_with_env_and_wait_for_the_message(
+        &connection_string,
+        api_port,
+        &[
+            ("POINTS_TO_WIN", "1"),
+            ("RECORDS_REFRESH_SECONDS", "1"),
+            ("WEBHOOK_POLL_SECONDS", "1"),
+            ("WEBHOOK_RETRY_BASE_SECONDS", "1"),
+            ("ADMIN_API_KEYS", "root:rootkey:read,write"),
+        ],
+        "Listening",
+    )
+    .expect("Failed to start server");
+    let client = Client::new();
+    let (url, receiver) = start_receiver().await;
+
+    let created = client
+        .post(format!("{base_url}/admin/webhooks"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "url": url }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), StatusCode::CREATED);
+    let created: Value = created.json().await.unwrap();
+    let secret = created["secret"].as_str().unwrap().to_string();
+    assert_eq!(created["events"], json!(["point", "record", "won"]));
+    // another match only, never called
+    let other = client
+        .post(format!("{base_url}/admin/webhooks"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "url": url, "matchId": "other", "events": ["won"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(other.status(), StatusCode::CREATED);
+    let invalid = client
+        .post(format!("{base_url}/admin/webhooks"))
+        .bearer_auth("rootkey")
+        .json(&json!({ "url": "ftp://example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+
+    // pong misses twice, a win still needs a two point lead
+    let match_endpoint = format!("{base_url}/matches/w1");
+    for _ in 0..2 {
+        let state: Value = client
+            .get(&match_endpoint)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if state["gameState"]["server"] == "pong" {
+            client
+                .get(format!("{match_endpoint}/pong"))
+                .send()
+                .await
+                .unwrap();
+        }
+        let missed = client
+            .get(format!("{match_endpoint}/pong"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missed.text().await.unwrap(), "MISS");
+    }
+
+    // records are noticed a few seconds later, and announced as events of their own
+    let mut events = vec![];
+    for _ in 0..60 {
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        events = receiver
+            .received
+            .lock()
+            .unwrap()
+            .iter()
+            .skip(1)
+            .map(|(headers, _)| header(headers, "X-Webhook-Event").to_string())
+            .collect();
+        // the failed point, retried, and the other one
+        let points = events.iter().filter(|event| *event == "point").count();
+        if events.contains(&"record".to_string()) && points == 2 {
+            break;
+        }
+    }
+    assert_eq!(events.iter().filter(|event| *event == "point").count(), 2);
+    assert_eq!(events.iter().filter(|event| *event == "won").count(), 1);
+    assert!(events.contains(&"record".to_string()));
+
+    let received = receiver.received.lock().unwrap().clone();
+    for (headers, body) in &received {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(header(headers, "X-Webhook-Signature"), expected);
+    }
+    // the failed delivery was made again
+    let failed = header(&received[0].0, "X-Webhook-Delivery");
+    let retried = received
+        .iter()
+        .skip(1)
+        .filter(|(headers, _)| header(headers, "X-Webhook-Delivery") == failed)
+        .count();
+    assert_eq!(retried, 1);
+
+    let payloads: Vec<Value> = received
+        .iter()
+        .map(|(_, body)| serde_json::from_slice(body).unwrap())
+        .collect();
+    assert!(
+        payloads.iter().all(
+            |payload| payload["matchId"] == "w1" && payload["data"]["kind"] == payload["event"]
+        )
+    );
+    assert!(payloads.iter().any(|payload| payload["event"] == "point"
+        && payload["data"]["loser"] == "pong"
+        && payload["data"]["score"] == json!({ "ping": 1, "pong": 0 })));
+
+    let log: Value = client
+        .get(format!(
+            "{base_url}/admin/webhooks/{}/deliveries",
+            created["id"]
+        ))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let failed = log["deliveries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|delivery| delivery["id"] == failed.parse::<i64>().unwrap())
+        .unwrap();
+    assert_eq!(failed["attempts"], 2);
+    assert_eq!(failed["lastStatusCode"], 200);
+    assert!(failed["deliveredAt"].is_string());
+    assert!(failed["nextAttemptAt"].is_null());
+
+    let other: Value = other.json().await.unwrap();
+    let deleted = client
+        .delete(format!("{base_url}/admin/webhooks/{}", other["id"]))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+    let webhooks: Value = client
+        .get(format!("{base_url}/admin/webhooks"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(webhooks["webhooks"].as_array().unwrap().len(), 1);
+    assert!(webhooks["webhooks"][0].get("secret").is_none());
+
+    let audit: Value = client
+        .get(format!("{base_url}/admin/audit"))
+        .bearer_auth("rootkey")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let actions: Vec<_> = audit["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    // newest first
+    assert_eq!(
+        actions,
+        ["delete_webhook", "create_webhook", "create_webhook"]
+    );
+    assert_eq!(audit["entries"][0]["details"], json!({ "id": other["id"] }));
+    assert_eq!(audit["entries"][1]["details"]["matchId"], "other");
+    // the secret stays out of the log
+    assert!(audit["entries"][2]["details"].get("secret").is_none());
+
+    send_sigterm_and_wait_for_exit(server_process).expect("Failed to stop server");
+}